@@ -1,20 +1,90 @@
 use cgmath::*;
 use crate::timing::Duration;
+use crate::picking::Ray;
 use winit::dpi::PhysicalPosition;
 use winit::event::*;
 use winit::keyboard::KeyCode;
 
+// Maps OpenGL depth (-1..1) to wgpu depth (0..1): z' = 0.5 * z + 0.5 * w.
+// cgmath::Matrix4::new takes columns, so the 0.5 * w term sits in the last column.
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
 );
 
-// Camera constraints
-const MIN_ZOOM_DISTANCE: f32 = 0.5;
-const MAX_ZOOM_DISTANCE: f32 = 100.0;
+// Camera constraints, relative to the scene radius so kilometre sites and millimetre details both work
+const DEFAULT_SCENE_RADIUS: f32 = 10.0;
+const MIN_ZOOM_FACTOR: f32 = 1e-4; // Closest zoom distance = scene radius * factor
+const MAX_ZOOM_FACTOR: f32 = 20.0; // Farthest zoom distance = scene radius * factor
+const FRAME_MARGIN: f32 = 1.1;     // Framed bounds fill about 90% of the view
+const MIN_NEAR_FACTOR: f32 = 1e-5; // Closest near plane = far plane * factor, below the closest zoom
+
+// Perspective, or parallel projection for plans and elevations
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    // The visible height equals the perspective frustum height at the target,
+    // so switching keeps the model the same size and zooming (distance) still works
+    Orthographic,
+}
+
+impl Projection {
+    pub fn toggle(self) -> Self {
+        match self {
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Perspective,
+        }
+    }
+}
+
+// Standard views of the Z-up world, named after the side the camera looks from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NamedView {
+    Top,
+    Bottom,
+    Front, // From -Y looking along +Y
+    Back,
+    Left,  // From -X looking along +X
+    Right,
+    Iso,   // From the -X/-Y/+Z octant
+}
+
+// Lowercase names, e.g. "top" or "iso", as used by the JavaScript API
+impl std::str::FromStr for NamedView {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "top" => Ok(NamedView::Top),
+            "bottom" => Ok(NamedView::Bottom),
+            "front" => Ok(NamedView::Front),
+            "back" => Ok(NamedView::Back),
+            "left" => Ok(NamedView::Left),
+            "right" => Ok(NamedView::Right),
+            "iso" => Ok(NamedView::Iso),
+            _ => Err(format!("unknown view '{}', expected top, bottom, front, back, left, right or iso", name)),
+        }
+    }
+}
+
+impl NamedView {
+    // View direction (from the eye towards the target) and the screen up direction
+    fn directions(self) -> (Vector3<f32>, Vector3<f32>) {
+        match self {
+            NamedView::Top => (-Vector3::unit_z(), Vector3::unit_y()),
+            NamedView::Bottom => (Vector3::unit_z(), -Vector3::unit_y()),
+            NamedView::Front => (Vector3::unit_y(), Vector3::unit_z()),
+            NamedView::Back => (-Vector3::unit_y(), Vector3::unit_z()),
+            NamedView::Left => (Vector3::unit_x(), Vector3::unit_z()),
+            NamedView::Right => (-Vector3::unit_x(), Vector3::unit_z()),
+            NamedView::Iso => (Vector3::new(1.0, 1.0, -1.0).normalize(), Vector3::unit_z()),
+        }
+    }
+}
 
 // Professional 3D orbit camera implementation
 #[derive(Debug)]
@@ -43,6 +113,16 @@ pub struct Camera {
     pub initial_orientation: Quaternion<f32>,
     pub initial_distance: f32,
 
+    // Perspective or orthographic, the orthographic scale follows distance
+    pub projection: Projection,
+
+    // Size of the loaded model; drives the zoom limits and the clip planes
+    pub scene_radius: f32,
+    // Center of the loaded model; named views orbit around it
+    pub scene_center: Point3<f32>,
+    pub min_distance: f32,
+    pub max_distance: f32,
+
     // Legacy fields for compatibility
     pub aspect: f32,
     pub fovy: f32,
@@ -62,14 +142,14 @@ impl Camera {
         let dir = (target - position).normalize();
 
         // Define world up vector (Z-up for professional 3D software standard)
-        let world_up = Vector3::unit_z();
+        let world_up: Vector3<f32> = Vector3::unit_z();
 
         // Calculate initial orientation quaternion
         let orientation = Quaternion::look_at(dir, world_up);
 
         // Initialize stable reference frame
         let forward = -dir;
-        let right = if (forward.dot(world_up) as f32).abs() > 0.99 {
+        let right = if forward.dot(world_up).abs() > 0.99 {
             // If aligned with pole, pick an arbitrary but consistent right vector
             Vector3::unit_x()
         } else {
@@ -99,6 +179,13 @@ impl Camera {
             initial_orientation: orientation,
             initial_distance: distance,
 
+            projection: Projection::default(),
+
+            scene_radius: DEFAULT_SCENE_RADIUS,
+            scene_center: target,
+            min_distance: DEFAULT_SCENE_RADIUS * MIN_ZOOM_FACTOR,
+            max_distance: DEFAULT_SCENE_RADIUS * MAX_ZOOM_FACTOR,
+
             // Legacy fields
             aspect: width / height,
             fovy: 45.0,
@@ -131,7 +218,7 @@ impl Camera {
             // Update reference frame to maintain continuity
             // When we get close to the poles, we use the previous reference frame's right vector
             // as a stable reference, rather than recomputing it from scratch
-            let alignment = (forward.dot(self.world_up) as f32).abs();
+            let alignment = forward.dot(self.world_up).abs();
 
             let right = if alignment > 0.98 {
                 // Near pole - use the last stable right vector
@@ -169,6 +256,55 @@ impl Camera {
             self.position = self.target + final_offset;
             self.up = self.orientation.rotate_vector(Vector3::unit_y());
         }
+
+        self.update_clip_planes();
+    }
+
+    // Near and far planes follow the eye's distance to the scene: tight around the model
+    // when looking from outside, and close to the eye when it is inside the model.
+    // The target can be anywhere after panning or framing a selection, so it is not used.
+    pub fn update_clip_planes(&mut self) {
+        let to_center = self.scene_center - self.position;
+        self.zfar = (to_center.magnitude() + self.scene_radius) * 2.0;
+        // Depth along the view: the model off to the side of the view axis is nearer than its distance
+        let center_depth = to_center.dot((self.target - self.position).normalize());
+        self.znear = ((center_depth - self.scene_radius) * 0.5).max(self.zfar * MIN_NEAR_FACTOR);
+    }
+
+    // Adapt zoom limits and clip planes to the world-space bounds of the loaded model
+    pub fn set_scene_bounds(&mut self, min: Point3<f32>, max: Point3<f32>) {
+        self.scene_radius = ((max - min).magnitude() * 0.5).max(f32::EPSILON);
+        self.scene_center = min.midpoint(max);
+        self.min_distance = self.scene_radius * MIN_ZOOM_FACTOR;
+        self.max_distance = self.scene_radius * MAX_ZOOM_FACTOR;
+        self.update_clip_planes();
+    }
+
+    // Move the target to the center of the bounds and zoom so their bounding sphere fits the view.
+    // The orientation is kept; zoom limits are widened if the bounds are very small or large.
+    pub fn frame_bounds(&mut self, min: Point3<f32>, max: Point3<f32>) {
+        let radius = ((max - min).magnitude() * 0.5).max(self.min_distance);
+        // Fit into the narrower of the vertical and horizontal field of view
+        let half_fovy = Rad::from(Deg(self.fovy)) / 2.0;
+        let half_fov = if self.aspect < 1.0 { Rad((half_fovy.0.tan() * self.aspect).atan()) } else { half_fovy };
+        let distance = match self.projection {
+            Projection::Perspective => radius / half_fov.sin(),
+            Projection::Orthographic => radius / half_fov.tan(),
+        } * FRAME_MARGIN;
+
+        self.target = min.midpoint(max);
+        self.distance = distance;
+        self.min_distance = self.min_distance.min(radius * MIN_ZOOM_FACTOR);
+        self.max_distance = self.max_distance.max(distance);
+        self.update_position();
+    }
+
+    // Make the current view the one the reset key (C) returns to
+    pub fn set_initial_to_current(&mut self) {
+        self.initial_position = self.position;
+        self.initial_target = self.target;
+        self.initial_orientation = self.orientation;
+        self.initial_distance = self.distance;
     }
 
     // Reset the camera to its initial position and orientation
@@ -189,13 +325,46 @@ impl Camera {
         self.update_position();
     }
 
+    // Look at the scene center along one of the world axes (or the iso diagonal), keeping distance.
+    // The pivot moves back to the center, so a named view after panning still orbits the model.
+    // The turntable offset is -Y rotated by orientation, so orientation maps
+    // X to screen right, Y to the view direction and Z to screen up.
+    pub fn set_view(&mut self, view: NamedView) {
+        let (direction, screen_up) = view.directions();
+        let right = direction.cross(screen_up).normalize();
+        let up = right.cross(direction).normalize();
+        self.orientation = Quaternion::from(Matrix3::from_cols(right, direction, up)).normalize();
+        // Top and bottom look along the pole, where update_position keeps the last right vector.
+        // That vector is eye direction x world up, i.e. it points to the left of the screen.
+        self.last_right = -right;
+        self.target = self.scene_center;
+        self.update_position();
+    }
+
     // Legacy method for compatibility
     pub fn build_view_projection_matrix(&self) -> Matrix4<f32> {
         let view = Matrix4::look_at_rh(self.position, self.target, self.up);
-        let proj = perspective(Deg(self.fovy), self.aspect, self.znear, self.zfar);
+        let proj = match self.projection {
+            Projection::Perspective => perspective(Deg(self.fovy), self.aspect, self.znear, self.zfar),
+            Projection::Orthographic => {
+                let half_height = self.distance * (Deg(self.fovy) / 2.0).tan();
+                let half_width = half_height * self.aspect;
+                // Depth range reaches behind the eye, so zooming in never clips the model
+                ortho(-half_width, half_width, -half_height, half_height, -self.zfar, self.zfar)
+            }
+        };
         OPENGL_TO_WGPU_MATRIX * proj * view
     }
 
+    // Ray through a point in normalized device coordinates (x, y in -1..1, y up),
+    // from the near plane to the far plane
+    pub fn ray_through(&self, ndc_x: f32, ndc_y: f32) -> Ray {
+        let inverse = self.build_view_projection_matrix().invert().unwrap_or(Matrix4::identity());
+        let unproject = |z: f32| Point3::from_homogeneous(inverse * Vector4::new(ndc_x, ndc_y, z, 1.0));
+        let (near, far) = (unproject(0.0), unproject(1.0));
+        Ray { origin: near, direction: far - near }
+    }
+
     // Pan camera in view plane (right and up vectors)
     pub fn pan(&mut self, right_amount: f32, up_amount: f32) {
         // Get current view vectors from reference frame
@@ -210,6 +379,7 @@ impl Camera {
         // Update initial target for reset functionality
         self.initial_target += pan_offset;
         self.initial_position += pan_offset;
+        self.update_clip_planes();
     }
 
     // Legacy compatibility - map position to eye
@@ -222,17 +392,32 @@ impl Camera {
 #[repr(C)]
 pub struct CameraUniform {
     pub view_proj: [[f32; 4]; 4],
+    pub viewport: [f32; 4], // Surface width and height in pixels (zw unused, keeps 16-byte alignment)
+    pub eye: [f32; 4], // Camera position for the headlight (w unused)
+}
+
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CameraUniform {
     pub fn new() -> Self {
         Self {
             view_proj: Matrix4::identity().into(),
+            viewport: [1.0, 1.0, 0.0, 0.0],
+            eye: [0.0; 4],
         }
     }
 
+    pub fn update_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = [width.max(1) as f32, height.max(1) as f32, 0.0, 0.0];
+    }
+
     pub fn update_view_proj(&mut self, camera: &Camera) {
         self.view_proj = camera.build_view_projection_matrix().into();
+        self.eye = [camera.position.x, camera.position.y, camera.position.z, 1.0];
     }
 }
 
@@ -266,6 +451,9 @@ pub struct CameraController {
 
     // Reset functionality
     reset_camera_pressed: bool,
+    // Named views and projection switching, applied on the next update
+    pending_view: Option<NamedView>,
+    toggle_projection_pressed: bool,
 }
 
 impl CameraController {
@@ -291,6 +479,8 @@ impl CameraController {
             orbit_invert_y: false, // Standard behavior in most 3D software
             max_rotation_per_frame: 0.1, // Limit to about 5.7 degrees per frame
             reset_camera_pressed: false,
+            pending_view: None,
+            toggle_projection_pressed: false,
         }
     }
 
@@ -327,6 +517,29 @@ impl CameraController {
                 }
                 true
             }
+            // 1-7: top, bottom, front, back, left, right, iso
+            KeyCode::Digit1 | KeyCode::Digit2 | KeyCode::Digit3 | KeyCode::Digit4
+            | KeyCode::Digit5 | KeyCode::Digit6 | KeyCode::Digit7 => {
+                if state == ElementState::Pressed {
+                    self.pending_view = Some(match key {
+                        KeyCode::Digit1 => NamedView::Top,
+                        KeyCode::Digit2 => NamedView::Bottom,
+                        KeyCode::Digit3 => NamedView::Front,
+                        KeyCode::Digit4 => NamedView::Back,
+                        KeyCode::Digit5 => NamedView::Left,
+                        KeyCode::Digit6 => NamedView::Right,
+                        _ => NamedView::Iso,
+                    });
+                }
+                true
+            }
+            // O: toggle perspective / orthographic
+            KeyCode::KeyO => {
+                if state == ElementState::Pressed {
+                    self.toggle_projection_pressed = true;
+                }
+                true
+            }
             _ => false,
         }
     }
@@ -453,7 +666,7 @@ impl CameraController {
             camera.distance *= 1.0 + self.scroll * self.zoom_speed;
 
             // Ensure camera doesn't get too close or too far
-            camera.distance = camera.distance.max(MIN_ZOOM_DISTANCE).min(MAX_ZOOM_DISTANCE);
+            camera.distance = camera.distance.clamp(camera.min_distance, camera.max_distance);
 
             // Reset scroll and update position
             self.scroll = 0.0;
@@ -466,6 +679,15 @@ impl CameraController {
             self.reset_camera_pressed = false;
         }
 
+        // Handle named views (1-7 keys) and projection switching (o key)
+        if let Some(view) = self.pending_view.take() {
+            camera.set_view(view);
+        }
+        if self.toggle_projection_pressed {
+            camera.projection = camera.projection.toggle();
+            self.toggle_projection_pressed = false;
+        }
+
         // Reset mouse deltas after processing
         self.mouse_delta_x = 0.0;
         self.mouse_delta_y = 0.0;
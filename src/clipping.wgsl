@@ -0,0 +1,28 @@
+// Section planes shared by the geometry pipelines, see section.rs.
+// Prepended to shader_solid.wgsl, shader_color.wgsl, shader_point.wgsl, shader_edge.wgsl and the
+// shadow and post-processing prepasses.
+// clipping.count.x planes are active; geometry where dot(plane.xyz, p) + plane.w > 0 is cut away.
+
+struct ClipUniform {
+    planes: array<vec4<f32>, 6>,
+    fill: vec4<f32>, // Fill of cut solids (a: 1 on, 0 off)
+    count: vec4<u32>, // x: active planes (yzw unused)
+}
+@group(0) @binding(5)
+var<uniform> clipping: ClipUniform;
+
+fn clipped(world_position: vec3<f32>) -> bool {
+    for (var i = 0u; i < clipping.count.x; i++) {
+        let plane = clipping.planes[i];
+        if (dot(plane.xyz, world_position) + plane.w > 0.0) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// A cut closed solid shows its back faces through the opening: fill them to read as a section.
+// Open meshes seen from behind keep their colors; closed is the instance flag (1 closed, 0 open).
+fn section_cap(is_front: bool, closed: f32) -> bool {
+    return !is_front && closed > 0.5 && clipping.count.x > 0u && clipping.fill.a > 0.5;
+}
@@ -0,0 +1,459 @@
+// Color by attribute: a named f64 attribute of mesh vertices (VertexData.attributes) or faces
+// (Mesh.facedata) mapped through a colormap, with a legend in the bottom left corner.
+// The chosen attribute is baked into the vertices (Vertex.value); the colormap is a small texture
+// and the range is a small uniform, so both change without rebuilding geometry.
+use std::collections::BTreeSet;
+use openmodel::AllGeometryData;
+use openmodel::geometry::Mesh;
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+use crate::texture::Texture;
+
+// Texels of the colormap lookup texture
+pub const COLORMAP_SIZE: u32 = 256;
+// Number of bands of Colormap::Stepped
+pub const STEPPED_BANDS: u32 = 8;
+
+// Vertex attributes the viewer already reads (position defaults, color, alpha, normal, texture coordinates)
+pub const RESERVED_VERTEX_ATTRIBUTES: [&str; 12] = ["x", "y", "z", "r", "g", "b", "a", "nx", "ny", "nz", "u", "v"];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Colormap {
+    #[default]
+    Viridis,   // Perceptually uniform, dark blue to yellow
+    Diverging, // Blue through light gray to red, for values around a midpoint
+    Stepped,   // Viridis in STEPPED_BANDS flat bands
+}
+
+// Lowercase names, e.g. "viridis", as used by the JavaScript API
+impl std::str::FromStr for Colormap {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "viridis" => Ok(Colormap::Viridis),
+            "diverging" => Ok(Colormap::Diverging),
+            "stepped" => Ok(Colormap::Stepped),
+            _ => Err(format!("unknown colormap '{}', expected viridis, diverging or stepped", name)),
+        }
+    }
+}
+
+// sRGB control points, evenly spaced from 0 to 1
+const VIRIDIS: [[u8; 3]; 9] = [
+    [68, 1, 84], [72, 40, 120], [62, 73, 137], [49, 104, 142], [38, 130, 142],
+    [31, 158, 137], [53, 183, 121], [110, 206, 88], [253, 231, 37],
+];
+const DIVERGING: [[u8; 3]; 5] = [[59, 76, 192], [141, 176, 254], [221, 221, 221], [244, 154, 123], [180, 4, 38]];
+
+fn interpolate(stops: &[[u8; 3]], t: f32) -> [u8; 3] {
+    let x = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (x.floor() as usize).min(stops.len() - 2);
+    let f = x - i as f32;
+    let (a, b) = (stops[i], stops[i + 1]);
+    [0, 1, 2].map(|c| (a[c] as f32 + (b[c] as f32 - a[c] as f32) * f).round() as u8)
+}
+
+impl Colormap {
+    // Cycle Viridis -> Diverging -> Stepped -> Viridis
+    pub fn next(self) -> Self {
+        match self {
+            Colormap::Viridis => Colormap::Diverging,
+            Colormap::Diverging => Colormap::Stepped,
+            Colormap::Stepped => Colormap::Viridis,
+        }
+    }
+
+    // sRGB color of a normalized value (0 at the range minimum, 1 at the maximum)
+    pub fn sample(self, t: f32) -> [u8; 3] {
+        match self {
+            Colormap::Viridis => interpolate(&VIRIDIS, t),
+            Colormap::Diverging => interpolate(&DIVERGING, t),
+            Colormap::Stepped => {
+                let band = ((t.clamp(0.0, 1.0) * STEPPED_BANDS as f32) as u32).min(STEPPED_BANDS - 1);
+                interpolate(&VIRIDIS, band as f32 / (STEPPED_BANDS - 1) as f32)
+            }
+        }
+    }
+
+    // Values labelled in the legend: the band edges of Stepped, quarters otherwise
+    pub fn ticks(self) -> u32 {
+        match self {
+            Colormap::Stepped => STEPPED_BANDS + 1,
+            _ => 5,
+        }
+    }
+}
+
+// Where the attribute values live
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeSource {
+    #[default]
+    Vertex, // VertexData.attributes, interpolated across the faces
+    Face,   // Mesh.facedata, one flat color per face
+}
+
+// What colors the meshes; set with Renderer::set_attribute_coloring, the T key cycles the attributes
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AttributeColoring {
+    pub attribute: String,
+    #[serde(default)]
+    pub source: AttributeSource,
+    #[serde(default)]
+    pub colormap: Colormap,
+    #[serde(default)]
+    pub range: Option<[f32; 2]>, // Values mapped to both ends of the colormap, None fits the loaded values
+}
+
+impl AttributeColoring {
+    pub fn new(source: AttributeSource, attribute: &str) -> Self {
+        Self { attribute: attribute.to_string(), source, colormap: Colormap::default(), range: None }
+    }
+}
+
+// Value of a vertex or face attribute, falling back to the mesh's default attributes
+pub fn vertex_value(mesh: &Mesh, vertex_key: usize, attribute: &str) -> Option<f64> {
+    let vertex = mesh.vertex.get(&vertex_key)?;
+    vertex.get_attribute(attribute).or_else(|| mesh.default_vertex_attributes.get(attribute).copied())
+}
+
+pub fn face_value(mesh: &Mesh, face_key: usize, attribute: &str) -> Option<f64> {
+    let face = mesh.facedata.get(&face_key).and_then(|data| data.get(attribute));
+    face.or_else(|| mesh.default_face_attributes.get(attribute)).copied()
+}
+
+// Attributes the meshes of the sources can be colored by, sorted by source and name
+pub fn attribute_names(sources: &[AllGeometryData]) -> Vec<(AttributeSource, String)> {
+    let mut names = BTreeSet::new();
+    for mesh in sources.iter().flat_map(|source| &source.meshes) {
+        let vertex_names = mesh.vertex.values().flat_map(|vertex| vertex.attributes.keys()).chain(mesh.default_vertex_attributes.keys());
+        for name in vertex_names.filter(|name| !RESERVED_VERTEX_ATTRIBUTES.contains(&name.as_str())) {
+            names.insert((AttributeSource::Vertex, name.clone()));
+        }
+        for name in mesh.facedata.values().flat_map(|data| data.keys()).chain(mesh.default_face_attributes.keys()) {
+            names.insert((AttributeSource::Face, name.clone()));
+        }
+    }
+    names.into_iter().collect()
+}
+
+// Smallest and largest finite value of the attribute over the meshes of the sources
+pub fn attribute_range(sources: &[AllGeometryData], source: AttributeSource, attribute: &str) -> Option<[f32; 2]> {
+    let meshes = sources.iter().flat_map(|source| &source.meshes);
+    let values: Vec<f64> = match source {
+        AttributeSource::Vertex => meshes.flat_map(|mesh| mesh.vertex.keys().filter_map(|&key| vertex_value(mesh, key, attribute))).collect(),
+        AttributeSource::Face => meshes.flat_map(|mesh| mesh.face.keys().filter_map(|&key| face_value(mesh, key, attribute))).collect(),
+    };
+    values.into_iter().filter(|v| v.is_finite()).fold(None, |range, v| {
+        let v = v as f32;
+        Some(range.map_or([v, v], |[min, max]: [f32; 2]| [min.min(v), max.max(v)]))
+    })
+}
+
+// Short legend label: up to three decimals, exponent notation for very large or small values
+pub fn format_value(value: f32) -> String {
+    let magnitude = value.abs();
+    if magnitude != 0.0 && !(1e-2..1e5).contains(&magnitude) {
+        return format!("{:.2e}", value);
+    }
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" { "0".to_string() } else { text.to_string() }
+}
+
+// 3x5 pixel glyphs of the characters format_value produces, one row per byte (bit 2 is the left column)
+const GLYPHS: [(char, [u8; 5]); 13] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('e', [0b010, 0b101, 0b111, 0b100, 0b011]),
+];
+const GLYPH_SCALE: u32 = 2; // Glyphs are drawn at 6x10 pixels
+const GLYPH_ADVANCE: u32 = 4 * GLYPH_SCALE;
+const GLYPH_HEIGHT: u32 = 5 * GLYPH_SCALE;
+
+const LEGEND_PADDING: u32 = 8;
+const LEGEND_BAR: [u32; 2] = [16, 160];
+const LEGEND_TICK: u32 = 3; // Tick mark length right of the bar
+const LEGEND_BACKGROUND: [u8; 4] = [255, 255, 255, 210];
+const LEGEND_INK: [u8; 4] = [30, 30, 30, 255];
+// Distance of the legend from the bottom left corner of the viewport
+pub const LEGEND_MARGIN: f32 = 12.0;
+
+// sRGB image with straight alpha, rows from the top
+pub struct LegendImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl LegendImage {
+    fn fill(&mut self, x: u32, y: u32, width: u32, height: u32, color: [u8; 4]) {
+        for row in y..(y + height).min(self.height) {
+            for column in x..(x + width).min(self.width) {
+                self.pixels[(row * self.width + column) as usize] = color;
+            }
+        }
+    }
+
+    fn text(&mut self, x: u32, y: u32, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            let Some((_, rows)) = GLYPHS.iter().find(|(glyph, _)| *glyph == c) else { continue };
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        let (px, py) = (x + i as u32 * GLYPH_ADVANCE + column * GLYPH_SCALE, y + row as u32 * GLYPH_SCALE);
+                        self.fill(px, py, GLYPH_SCALE, GLYPH_SCALE, LEGEND_INK);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Color bar with the maximum on top and labelled ticks on its right
+pub fn legend_image(colormap: Colormap, [min, max]: [f32; 2]) -> LegendImage {
+    let ticks = colormap.ticks();
+    let labels: Vec<String> = (0..ticks).map(|i| format_value(min + (max - min) * i as f32 / (ticks - 1) as f32)).collect();
+    let label_width = labels.iter().map(|label| label.len() as u32).max().unwrap_or(0) * GLYPH_ADVANCE;
+    let [bar_width, bar_height] = LEGEND_BAR;
+    // A wider gap than the tick, so the tick does not read as a minus sign
+    let label_x = LEGEND_PADDING + bar_width + 2 * LEGEND_TICK;
+    let width = label_x + label_width + LEGEND_PADDING;
+    let height = bar_height + 2 * LEGEND_PADDING;
+    let mut image = LegendImage { width, height, pixels: vec![LEGEND_BACKGROUND; (width * height) as usize] };
+
+    for row in 0..bar_height {
+        let [r, g, b] = colormap.sample(1.0 - row as f32 / (bar_height - 1) as f32);
+        image.fill(LEGEND_PADDING, LEGEND_PADDING + row, bar_width, 1, [r, g, b, 255]);
+    }
+    for (i, label) in labels.iter().enumerate() {
+        let y = LEGEND_PADDING + (bar_height - 1) * (ticks - 1 - i as u32) / (ticks - 1);
+        image.fill(LEGEND_PADDING + bar_width, y, LEGEND_TICK, 1, LEGEND_INK);
+        image.text(label_x, y.saturating_sub(GLYPH_HEIGHT / 2), label);
+    }
+    image
+}
+
+// Sampled texture filled from an sRGB image
+fn upload(device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32, pixels: &[[u8; 4]], label: &str) -> Texture {
+    let texture = Texture::create_image_texture(device, wgpu::TextureFormat::Rgba8UnormSrgb, width, height, label);
+    queue.write_texture(
+        texture.texture.as_image_copy(),
+        bytemuck::cast_slice(pixels),
+        wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(4 * width), rows_per_image: Some(height) },
+        wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+    );
+    texture
+}
+
+// GPU side of attribute coloring: the colormap texture bound with the camera (bindings 1 and 2,
+// read by colormap.wgsl) and the legend, drawn into the resolved target like the view gizmo
+// Range of the colored attribute, see colormap.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ColormapUniform {
+    pub range: [f32; 4], // x: min, y: max, z: 1 on, 0 off
+}
+
+impl ColormapUniform {
+    // None draws every object in its own colors
+    pub fn new(range: Option<[f32; 2]>) -> Self {
+        Self { range: range.map_or([0.0; 4], |[min, max]| [min, max, 1.0, 0.0]) }
+    }
+}
+
+pub struct AttributeColors {
+    coloring: Option<AttributeColoring>,
+    source_range: Option<[f32; 2]>, // Range of the values in the loaded sources
+    colormap_texture: Texture,
+    sampler: wgpu::Sampler,
+    range_buffer: wgpu::Buffer,
+    uploaded: Option<(Colormap, Option<[f32; 2]>)>, // What the textures currently show
+    legend_pipeline: wgpu::RenderPipeline,
+    legend_bind_group_layout: wgpu::BindGroupLayout,
+    legend: Option<(wgpu::BindGroup, [u32; 2])>,
+}
+
+impl AttributeColors {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
+        let colormap_texture = Texture::create_image_texture(device, wgpu::TextureFormat::Rgba8UnormSrgb, COLORMAP_SIZE, 1, "colormap_texture");
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("colormap_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let range_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("colormap_range_buffer"),
+            contents: bytemuck::cast_slice(&[ColormapUniform::new(None)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Legend Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader_legend.wgsl").into()),
+        });
+        let legend_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("legend_bind_group_layout"),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Legend Pipeline Layout"),
+            bind_group_layouts: &[&legend_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let legend_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Legend Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            coloring: None,
+            source_range: None,
+            colormap_texture,
+            sampler,
+            range_buffer,
+            uploaded: None,
+            legend_pipeline,
+            legend_bind_group_layout,
+            legend: None,
+        }
+    }
+
+    // Entries of the camera bind group
+    pub fn bind_group_entries(&self) -> [wgpu::BindGroupEntry<'_>; 3] {
+        [
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&self.colormap_texture.view) },
+            wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+            wgpu::BindGroupEntry { binding: 6, resource: self.range_buffer.as_entire_binding() },
+        ]
+    }
+
+    pub fn coloring(&self) -> Option<&AttributeColoring> {
+        self.coloring.as_ref()
+    }
+
+    pub fn set_coloring(&mut self, coloring: Option<AttributeColoring>) {
+        self.coloring = coloring;
+    }
+
+    // Fit the automatic range to the values of the sources
+    pub fn set_sources(&mut self, sources: &[AllGeometryData]) {
+        self.source_range = self.coloring.as_ref().and_then(|c| attribute_range(sources, c.source, &c.attribute));
+    }
+
+    // Range the colormap spans: the one set, or the one of the loaded values
+    pub fn range(&self) -> Option<[f32; 2]> {
+        let coloring = self.coloring.as_ref()?;
+        coloring.range.or(self.source_range)
+    }
+
+    // Rewrite the colormap and the legend after the colormap or range changed
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let colormap = self.coloring.as_ref().map_or(Colormap::default(), |c| c.colormap);
+        let state = (colormap, self.range());
+        if self.uploaded == Some(state) {
+            return;
+        }
+        queue.write_buffer(&self.range_buffer, 0, bytemuck::cast_slice(&[ColormapUniform::new(state.1)]));
+        if self.uploaded.is_none_or(|(uploaded, _)| uploaded != colormap) {
+            let texels: Vec<[u8; 4]> = (0..COLORMAP_SIZE)
+                .map(|i| {
+                    let [r, g, b] = colormap.sample(i as f32 / (COLORMAP_SIZE - 1) as f32);
+                    [r, g, b, 255]
+                })
+                .collect();
+            queue.write_texture(
+                self.colormap_texture.texture.as_image_copy(),
+                bytemuck::cast_slice(&texels),
+                wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(4 * COLORMAP_SIZE), rows_per_image: Some(1) },
+                wgpu::Extent3d { width: COLORMAP_SIZE, height: 1, depth_or_array_layers: 1 },
+            );
+        }
+        self.legend = state.1.map(|range| {
+            let image = legend_image(colormap, range);
+            let texture = upload(device, queue, image.width, image.height, &image.pixels, "legend_texture");
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.legend_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&texture.view) },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                ],
+                label: Some("legend_bind_group"),
+            });
+            (bind_group, [image.width, image.height])
+        });
+        self.uploaded = Some(state);
+    }
+
+    pub fn draws_legend(&self) -> bool {
+        self.legend.is_some()
+    }
+
+    // Legend in the bottom left corner of a viewport of the given size, in a pass on the resolved target
+    pub fn draw_legend(&self, render_pass: &mut wgpu::RenderPass, viewport: [f32; 2]) {
+        let Some((bind_group, [width, height])) = &self.legend else { return };
+        let (width, height) = (*width as f32, *height as f32);
+        let y = viewport[1] - LEGEND_MARGIN - height;
+        if LEGEND_MARGIN + width > viewport[0] || y < 0.0 {
+            return; // Viewport too small
+        }
+        render_pass.set_viewport(LEGEND_MARGIN, y, width, height, 0.0, 1.0);
+        render_pass.set_pipeline(&self.legend_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        render_pass.set_viewport(0.0, 0.0, viewport[0], viewport[1], 0.0, 1.0);
+    }
+}
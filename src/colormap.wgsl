@@ -0,0 +1,25 @@
+// Color by attribute, see colormap.rs.
+// Prepended to shader_solid.wgsl and shader_color.wgsl.
+
+@group(0) @binding(1)
+var colormap_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var colormap_sampler: sampler;
+
+struct ColormapUniform {
+    range: vec4<f32>, // x min, y max, z 1 while coloring by an attribute (w unused)
+}
+@group(0) @binding(6)
+var<uniform> colormap: ColormapUniform;
+
+// The colormap color where the vertex carries the colored attribute (value.y = 1), else `color`
+fn attribute_color(color: vec3<f32>, value: vec2<f32>) -> vec3<f32> {
+    let range = colormap.range;
+    var t = 0.5;
+    if (range.y > range.x) {
+        t = clamp((value.x - range.x) / (range.y - range.x), 0.0, 1.0);
+    }
+    // Explicit level: no derivatives, so this is fine after branching
+    let mapped = textureSampleLevel(colormap_texture, colormap_sampler, vec2<f32>(t, 0.5), 0.0).rgb;
+    return select(color, mapped, range.z > 0.5 && value.y > 0.5);
+}
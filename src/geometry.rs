@@ -0,0 +1,410 @@
+// Conversion of openmodel geometry into GPU-ready buffers
+use std::collections::{BTreeSet, HashMap};
+use openmodel::AllGeometryData;
+use openmodel::common::Data;
+use cgmath::Matrix4;
+use openmodel::geometry::mesh::NormalWeighting;
+use openmodel::geometry::{Line, LineCloud, Mesh, Plane, Point, PointCloud, Pline, Vector};
+use crate::colormap::{face_value, vertex_value, AttributeSource};
+use crate::vertex::{EdgeVertex, PointInstance, Vertex};
+use crate::fnv1a64;
+
+// Fallback colors for objects whose Data color is left at the default (black)
+pub const DEFAULT_MESH_COLOR: [f32; 3] = [0.8, 0.8, 0.8];
+pub const DEFAULT_CURVE_COLOR: [f32; 3] = [0.2, 0.2, 0.2];
+pub const DEFAULT_POINT_COLOR: [f32; 3] = [0.1, 0.1, 0.1];
+
+// Alpha at or above this is opaque; same threshold as OPAQUE_ALPHA in transparency.wgsl.
+// Alphas are snapped to 1.0 above it, so a geometry is either opaque or drawn in both passes.
+pub const OPAQUE_ALPHA: f32 = 0.999;
+
+// Point sprites are drawn in screen space: diameter in pixels per unit of Data thickness
+const POINT_SIZE_PX: f32 = 8.0;
+// Planes are drawn as a framed square of this half-size with x/y/z axes
+const PLANE_DISPLAY_HALF_SIZE: f64 = 0.5;
+// Radius of the plane frame and axis pipes per unit of Data thickness
+const PLANE_PIPE_RADIUS: f64 = 0.01;
+
+// One drawable openmodel object borrowed from loaded geometry.
+// Meshes carry the color used when neither the mesh nor its vertices define one.
+#[derive(Clone, Copy)]
+pub enum GeometryObject<'a> {
+    Mesh(&'a Mesh, [f32; 3]),
+    Point(&'a Point),
+    PointCloud(&'a PointCloud),
+    Line(&'a Line),
+    Pline(&'a Pline),
+    LineCloud(&'a LineCloud),
+    Plane(&'a Plane),
+}
+
+impl<'a> GeometryObject<'a> {
+    // Every drawable object of a JSON file, in a stable order
+    pub fn all(geometry: &'a AllGeometryData) -> Vec<GeometryObject<'a>> {
+        let mut objects = Vec::new();
+        objects.extend(geometry.meshes.iter().map(|m| GeometryObject::Mesh(m, DEFAULT_MESH_COLOR)));
+        objects.extend(geometry.points.iter().map(GeometryObject::Point));
+        objects.extend(geometry.point_clouds.iter().map(GeometryObject::PointCloud));
+        objects.extend(geometry.lines.iter().map(GeometryObject::Line));
+        objects.extend(geometry.plines.iter().map(GeometryObject::Pline));
+        objects.extend(geometry.line_clouds.iter().map(GeometryObject::LineCloud));
+        objects.extend(geometry.planes.iter().map(GeometryObject::Plane));
+        objects
+    }
+
+    pub fn data(&self) -> &'a Data {
+        match *self {
+            GeometryObject::Mesh(m, _) => &m.data,
+            GeometryObject::Point(p) => &p.data,
+            GeometryObject::PointCloud(p) => &p.data,
+            GeometryObject::Line(l) => &l.data,
+            GeometryObject::Pline(p) => &p.data,
+            GeometryObject::LineCloud(l) => &l.data,
+            GeometryObject::Plane(p) => &p.data,
+        }
+    }
+
+    // openmodel type name, as written in the COMPAS-style JSON "dtype" field
+    pub fn dtype(&self) -> &'static str {
+        match self {
+            GeometryObject::Mesh(..) => "openmodel.geometry/Mesh",
+            GeometryObject::Point(_) => "openmodel.geometry/Point",
+            GeometryObject::PointCloud(_) => "openmodel.geometry/PointCloud",
+            GeometryObject::Line(_) => "openmodel.geometry/Line",
+            GeometryObject::Pline(_) => "openmodel.geometry/Pline",
+            GeometryObject::LineCloud(_) => "openmodel.geometry/LineCloud",
+            GeometryObject::Plane(_) => "openmodel.geometry/Plane",
+        }
+    }
+
+    fn to_json(self) -> serde_json::Value {
+        let json = match self {
+            GeometryObject::Mesh(m, _) => serde_json::to_value(m),
+            GeometryObject::Point(p) => serde_json::to_value(p),
+            GeometryObject::PointCloud(p) => serde_json::to_value(p),
+            GeometryObject::Line(l) => serde_json::to_value(l),
+            GeometryObject::Pline(p) => serde_json::to_value(p),
+            GeometryObject::LineCloud(l) => serde_json::to_value(l),
+            GeometryObject::Plane(p) => serde_json::to_value(p),
+        };
+        json.unwrap_or_default()
+    }
+
+    // Hash of the serialized object, used to detect which objects changed between reloads
+    pub fn content_hash(&self) -> u64 {
+        fnv1a64(self.to_json().to_string().as_bytes())
+    }
+
+    // Hash of the object without its identity and placement (guid, name, transformation, xform).
+    // Objects with the same geometry hash share GPU buffers and are drawn as instances.
+    pub fn geometry_hash(&self) -> u64 {
+        let mut json = self.to_json();
+        if let Some(object) = json.as_object_mut() {
+            object.remove("xform");
+            if let Some(data) = object.get_mut("data").and_then(|d| d.as_object_mut()) {
+                for key in ["guid", "name", "transformation"] {
+                    data.remove(key);
+                }
+            }
+            if let GeometryObject::Mesh(_, color) = *self {
+                // The fallback color is baked into the vertices
+                object.insert("wink_default_color".to_string(), serde_json::json!(color));
+            }
+        }
+        fnv1a64(json.to_string().as_bytes())
+    }
+
+    // Model matrix of the object: Data.transformation, applied after the PointCloud/LineCloud xform
+    pub fn model_matrix(&self) -> Matrix4<f32> {
+        let placement = column_major_matrix(self.data().transformation());
+        match *self {
+            GeometryObject::PointCloud(p) => placement * column_major_matrix(&p.xform.m),
+            GeometryObject::LineCloud(l) => placement * column_major_matrix(&l.xform.m),
+            _ => placement,
+        }
+    }
+}
+
+// Convert a column-major [f64; 16] (openmodel Data/Xform layout) to a cgmath matrix
+pub fn column_major_matrix(m: &[f64; 16]) -> Matrix4<f32> {
+    let c = |i: usize| [m[i * 4] as f32, m[i * 4 + 1] as f32, m[i * 4 + 2] as f32, m[i * 4 + 3] as f32];
+    Matrix4::from([c(0), c(1), c(2), c(3)])
+}
+
+// CPU-side geometry ready to be uploaded to the GPU
+#[derive(Default)]
+pub struct GeometryBuffers {
+    pub vertices: Vec<Vertex>, // Triangles (meshes and pipes)
+    pub indices: Vec<u32>,
+    pub points: Vec<PointInstance>, // Screen-space point sprites
+    pub edges: Vec<EdgeVertex>, // Mesh edges as line list, drawn by the edge/wireframe modes
+    // Picking keys: the element each triangle belongs to (mesh face key, polyline segment
+    // or line cloud line index) and the Mesh vertex key of each vertex
+    pub triangle_elements: Vec<usize>,
+    pub vertex_keys: Vec<usize>,
+    // Every triangle belongs to a closed mesh, so a section cut shows its inside (see clipping.wgsl)
+    pub closed: bool,
+}
+
+// Convert an openmodel Data color (0-255) to a float color, or None if left at the default
+fn data_color(color: [u8; 3]) -> Option<[f32; 3]> {
+    if color == [0, 0, 0] {
+        None
+    } else {
+        Some([color[0] as f32 / 255.0, color[1] as f32 / 255.0, color[2] as f32 / 255.0])
+    }
+}
+
+fn with_alpha([r, g, b]: [f32; 3], alpha: f32) -> [f32; 4] {
+    [r, g, b, alpha]
+}
+
+// Clamp an alpha to 0..1 and snap almost opaque values to 1.0, see OPAQUE_ALPHA
+pub fn snap_alpha(alpha: f32) -> f32 {
+    let alpha = if alpha.is_nan() { 1.0 } else { alpha.clamp(0.0, 1.0) };
+    if alpha >= OPAQUE_ALPHA { 1.0 } else { alpha }
+}
+
+// A mesh without boundary edges: every face edge has a face on its other side too
+pub fn is_closed(mesh: &Mesh) -> bool {
+    let face_of = |u: usize, v: usize| mesh.halfedge.get(&u).and_then(|n| n.get(&v).copied().flatten());
+    let mut faces = mesh.get_face_data().peekable();
+    faces.peek().is_some()
+        && faces.all(|(_, vertices)| (0..vertices.len()).all(|i| face_of(vertices[(i + 1) % vertices.len()], vertices[i]).is_some()))
+}
+
+impl GeometryBuffers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Hash of the buffer contents, for geometry that does not come from an openmodel object
+    pub fn content_hash(&self) -> u64 {
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.vertices));
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.indices));
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.points));
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.edges));
+        fnv1a64(&bytes)
+    }
+
+    // Build the buffers of a single object
+    pub fn from_object(object: GeometryObject) -> Self {
+        Self::from_object_colored_by(object, None)
+    }
+
+    // Build the buffers of a single object, with the values of a mesh attribute in Vertex.value
+    pub fn from_object_colored_by(object: GeometryObject, attribute: Option<(AttributeSource, &str)>) -> Self {
+        let mut buffers = Self::new();
+        buffers.append_object_colored_by(object, attribute);
+        buffers
+    }
+
+    // Append every drawable object of a JSON file
+    pub fn append_geometry(&mut self, geometry: &AllGeometryData) {
+        for object in GeometryObject::all(geometry) {
+            self.append_object(object);
+        }
+    }
+
+    pub fn append_object(&mut self, object: GeometryObject) {
+        self.append_object_colored_by(object, None);
+    }
+
+    // Only meshes carry attributes; curves, points and planes keep their colors
+    pub fn append_object_colored_by(&mut self, object: GeometryObject, attribute: Option<(AttributeSource, &str)>) {
+        match object {
+            GeometryObject::Mesh(mesh, default_color) => {
+                self.closed = (self.closed || self.indices.is_empty()) && is_closed(mesh);
+                self.append_mesh_colored_by(mesh, default_color, attribute);
+                self.append_mesh_edges(mesh);
+            }
+            GeometryObject::Point(point) => self.append_point(point),
+            GeometryObject::PointCloud(point_cloud) => self.append_point_cloud(point_cloud),
+            GeometryObject::Line(line) => self.append_line(line),
+            GeometryObject::Pline(pline) => self.append_pline(pline),
+            GeometryObject::LineCloud(line_cloud) => self.append_line_cloud(line_cloud),
+            GeometryObject::Plane(plane) => self.append_plane(plane),
+        }
+    }
+
+    // Push a mesh as shared vertices (one per Mesh vertex key) and fan-triangulated indices.
+    // Same key-to-index mapping as ModelMesh::from_halfedge_mesh, offset by the vertices already stored.
+    // Vertices carry area-weighted smooth normals; flat shading derives face normals in the shader,
+    // so no vertex has to be split per face. The vertex attribute "a" (0-1) sets the alpha.
+    pub fn append_mesh(&mut self, mesh: &Mesh, default_color: [f32; 3]) {
+        self.append_mesh_colored_by(mesh, default_color, None);
+    }
+
+    // append_mesh with the values of a vertex or face attribute in Vertex.value (see colormap.rs).
+    // Face values have to stay flat, so then every face gets its own vertices.
+    pub fn append_mesh_colored_by(&mut self, mesh: &Mesh, default_color: [f32; 3], attribute: Option<(AttributeSource, &str)>) {
+        let default_color = data_color(mesh.data.get_color()).unwrap_or(default_color);
+        let normals = mesh.vertex_normals_weighted(NormalWeighting::Area);
+        let split = |face_key: usize| matches!(attribute, Some((AttributeSource::Face, _))).then_some(face_key);
+        let mut vertex_key_to_index: HashMap<(usize, Option<usize>), u32> = HashMap::new();
+
+        for (&face_key, face_vertices) in mesh.get_face_data() {
+            for &vk in face_vertices {
+                if vertex_key_to_index.contains_key(&(vk, split(face_key))) { continue; }
+                if let Some(pos) = mesh.vertex_position(vk) {
+                    // Per-vertex color only when all r/g/b attributes are present
+                    let vertex_data = mesh.vertex.get(&vk);
+                    let [r, g, b] = match vertex_data {
+                        Some(vd) if ["r", "g", "b"].iter().all(|k| vd.attributes.contains_key(*k)) => {
+                            let c = vd.color();
+                            [c[0] as f32, c[1] as f32, c[2] as f32]
+                        }
+                        _ => default_color,
+                    };
+                    let alpha = vertex_data.and_then(|vd| vd.get_attribute("a")).map_or(1.0, |a| snap_alpha(a as f32));
+                    let color = [r, g, b, alpha];
+                    let normal = normals.get(&vk).map_or([0.0; 3], |n| [n.x as f32, n.y as f32, n.z as f32]);
+                    let value = match attribute {
+                        Some((AttributeSource::Vertex, name)) => vertex_value(mesh, vk, name),
+                        Some((AttributeSource::Face, name)) => face_value(mesh, face_key, name),
+                        None => None,
+                    };
+                    let value = value.filter(|v| v.is_finite()).map_or([0.0; 2], |v| [v as f32, 1.0]);
+                    vertex_key_to_index.insert((vk, split(face_key)), self.vertices.len() as u32);
+                    self.vertex_keys.push(vk);
+                    self.vertices.push(Vertex { position: [pos.x as f32, pos.y as f32, pos.z as f32], color, normal, value });
+                }
+            }
+        }
+
+        for (&face_key, face_vertices) in mesh.get_face_data() {
+            if face_vertices.len() < 3 { continue; }
+            for i in 1..(face_vertices.len() - 1) {
+                if let (Some(&i0), Some(&i1), Some(&i2)) = (
+                    vertex_key_to_index.get(&(face_vertices[0], split(face_key))),
+                    vertex_key_to_index.get(&(face_vertices[i], split(face_key))),
+                    vertex_key_to_index.get(&(face_vertices[i + 1], split(face_key))),
+                ) {
+                    self.indices.extend_from_slice(&[i0, i1, i2]);
+                    self.triangle_elements.push(face_key);
+                }
+            }
+        }
+    }
+
+    // Push every unique halfedge edge once, with what the edge filters need: whether it is a
+    // boundary and the angle between its two faces. Only meshes get edges; pipes of curves
+    // are built with append_mesh and would only add clutter.
+    pub fn append_mesh_edges(&mut self, mesh: &Mesh) {
+        let face_normals = mesh.face_normals();
+        let mut edges: BTreeSet<(usize, usize)> = BTreeSet::new();
+        for (_face_key, face_vertices) in mesh.get_face_data() {
+            for (i, &u) in face_vertices.iter().enumerate() {
+                let v = face_vertices[(i + 1) % face_vertices.len()];
+                edges.insert((u.min(v), u.max(v)));
+            }
+        }
+
+        // halfedge[u][v] is the face on the left of u -> v, None on the open side of a boundary
+        let face_of = |u: usize, v: usize| mesh.halfedge.get(&u).and_then(|n| n.get(&v).copied().flatten());
+        for (u, v) in edges {
+            let (Some(a), Some(b)) = (mesh.vertex_position(u), mesh.vertex_position(v)) else { continue };
+            let (boundary, angle) = match (face_of(u, v), face_of(v, u)) {
+                (Some(f0), Some(f1)) => {
+                    let angle = match (face_normals.get(&f0), face_normals.get(&f1)) {
+                        (Some(n0), Some(n1)) => {
+                            let cos = n0.dot(n1) / (n0.length() * n1.length());
+                            if cos.is_finite() { cos.clamp(-1.0, 1.0).acos().to_degrees() as f32 } else { 0.0 }
+                        }
+                        _ => 0.0,
+                    };
+                    (0.0, angle)
+                }
+                _ => (1.0, 0.0),
+            };
+            for p in [a, b] {
+                self.edges.push(EdgeVertex { position: [p.x as f32, p.y as f32, p.z as f32], boundary, angle });
+            }
+        }
+    }
+
+    // Points are drawn as screen-space sprites sized by Data thickness
+    pub fn append_point(&mut self, point: &Point) {
+        self.points.push(PointInstance {
+            position: [point.x as f32, point.y as f32, point.z as f32],
+            color: with_alpha(data_color(point.data.get_color()).unwrap_or(DEFAULT_POINT_COLOR), 1.0),
+            size: POINT_SIZE_PX * point.data.get_thickness() as f32,
+        });
+    }
+
+    // Point clouds use their per-point colors (with alpha), falling back to the cloud's Data color
+    pub fn append_point_cloud(&mut self, point_cloud: &PointCloud) {
+        let cloud_color = data_color(point_cloud.data.get_color()).unwrap_or(DEFAULT_POINT_COLOR);
+        let size = POINT_SIZE_PX * point_cloud.data.get_thickness() as f32;
+        for (i, point) in point_cloud.points.iter().enumerate() {
+            let color = point_cloud.colors.get(i).map_or(with_alpha(cloud_color, 1.0), |c| {
+                let (r, g, b, a) = c.to_float();
+                [r, g, b, snap_alpha(a)]
+            });
+            self.points.push(PointInstance {
+                position: [point.x as f32, point.y as f32, point.z as f32],
+                color,
+                size,
+            });
+        }
+    }
+
+    // Lines are drawn as pipes with Data thickness as radius
+    pub fn append_line(&mut self, line: &Line) {
+        let mut line = line.clone();
+        if let Some(mesh) = line.get_mesh() {
+            self.append_mesh(mesh, DEFAULT_CURVE_COLOR);
+        }
+    }
+
+    // Polylines are drawn as one pipe per segment
+    pub fn append_pline(&mut self, pline: &Pline) {
+        for (segment, mesh) in pline.to_pipe_meshes(None, None).iter().enumerate() {
+            self.append_mesh_as_element(mesh, DEFAULT_CURVE_COLOR, segment);
+        }
+    }
+
+    // Line clouds are drawn as pipes colored per line
+    pub fn append_line_cloud(&mut self, line_cloud: &LineCloud) {
+        let mut line_cloud = line_cloud.clone();
+        let default_color = data_color(line_cloud.data.get_color()).unwrap_or(DEFAULT_CURVE_COLOR);
+        for (line, mesh) in line_cloud.get_meshes().iter().enumerate() {
+            self.append_mesh_as_element(mesh, default_color, line);
+        }
+    }
+
+    // Append a mesh whose triangles all pick as one element (e.g. the pipe of a segment)
+    fn append_mesh_as_element(&mut self, mesh: &Mesh, default_color: [f32; 3], element: usize) {
+        let start = self.triangle_elements.len();
+        self.append_mesh(mesh, default_color);
+        self.triangle_elements[start..].fill(element);
+    }
+
+    // Planes are drawn as a small framed square with red/green/blue x/y/z axes
+    pub fn append_plane(&mut self, plane: &Plane) {
+        let radius = PLANE_PIPE_RADIUS * plane.data.get_thickness();
+        let s = PLANE_DISPLAY_HALF_SIZE;
+        let o = &plane.origin;
+        let x = plane.xaxis.normalize();
+        let y = plane.yaxis.normalize();
+        let z = plane.zaxis.normalize();
+        let at = |v: &Vector, t: f64, w: &Vector, u: f64| {
+            Point::new(o.x + v.x * t + w.x * u, o.y + v.y * t + w.y * u, o.z + v.z * t + w.z * u)
+        };
+
+        let frame_color = data_color(plane.data.get_color()).unwrap_or(DEFAULT_CURVE_COLOR);
+        let corners = [at(&x, -s, &y, -s), at(&x, s, &y, -s), at(&x, s, &y, s), at(&x, -s, &y, s)];
+        for i in 0..corners.len() {
+            let pipe = Mesh::create_pipe(corners[i].clone(), corners[(i + 1) % corners.len()].clone(), radius);
+            self.append_mesh(&pipe, frame_color);
+        }
+
+        let origin = at(&x, 0.0, &y, 0.0);
+        let axes = [(&x, [1.0, 0.0, 0.0]), (&y, [0.0, 1.0, 0.0]), (&z, [0.0, 0.0, 1.0])];
+        for (axis, color) in axes {
+            let pipe = Mesh::create_pipe(origin.clone(), at(axis, s, &y, 0.0), radius);
+            self.append_mesh(&pipe, color);
+        }
+    }
+}
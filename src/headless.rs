@@ -0,0 +1,227 @@
+// Headless rendering (native only).
+// Renders geometry without a window into an offscreen texture and reads it back,
+// e.g. for regression images in CI where only a software or GL adapter is available.
+use std::path::Path;
+use anyhow::{anyhow, Context};
+use openmodel::AllGeometryData;
+use crate::camera::Camera;
+use crate::renderer::Renderer;
+use crate::texture::Texture;
+
+// Same sRGB color format the window surface uses, so images match what the viewer shows
+pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// Tightly packed 8-bit RGBA pixels, rows from top to bottom
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let file = std::fs::File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+// Offscreen counterpart of State: owns a device, a color target and the shared Renderer
+pub struct HeadlessRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    adapter_info: wgpu::AdapterInfo,
+    renderer: Renderer,
+    target: wgpu::Texture,
+    target_view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+}
+
+impl HeadlessRenderer {
+    // Blocking constructor, like the native window path that awaits State::new with pollster.
+    // Anti-aliased with the viewer's default MSAA level.
+    pub fn new(width: u32, height: u32) -> anyhow::Result<Self> {
+        Self::with_sample_count(width, height, Texture::DEFAULT_SAMPLE_COUNT)
+    }
+
+    // Request a MSAA level, lowered to what the adapter supports; 1 disables anti-aliasing
+    pub fn with_sample_count(width: u32, height: u32, sample_count: u32) -> anyhow::Result<Self> {
+        pollster::block_on(Self::new_async(width, height, sample_count))
+    }
+
+    pub async fn new_async(width: u32, height: u32, sample_count: u32) -> anyhow::Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(anyhow!("headless image size must be non-zero, got {}x{}", width, height));
+        }
+
+        // All backends, so machines without Vulkan/Metal/DX12 can still use GL (e.g. Mesa llvmpipe)
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        // No surface to be compatible with; prefer a real adapter and fall back to a software one
+        let adapter = match instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }).await {
+            Ok(adapter) => adapter,
+            Err(_) => instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: true,
+            }).await.context("no GPU or fallback adapter available for headless rendering")?,
+        };
+        let adapter_info = adapter.get_info();
+        log::info!("Headless adapter: {} ({:?}, {:?})", adapter_info.name, adapter_info.backend, adapter_info.device_type);
+
+        // Software and GL adapters often only reach the downlevel limits
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("Headless Device"),
+                required_features: adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                required_limits: wgpu::Limits {
+                    max_buffer_size: adapter.limits().max_buffer_size,
+                    ..wgpu::Limits::downlevel_defaults()
+                }
+                .using_resolution(adapter.limits()),
+                memory_hints: Default::default(),
+                trace: wgpu::Trace::Off,
+            })
+            .await?;
+
+        let sample_count = Texture::supported_sample_count(&adapter, &device, &[HEADLESS_FORMAT, Texture::DEPTH_FORMAT], sample_count);
+        let renderer = Renderer::new(&adapter, &device, HEADLESS_FORMAT, width, height, sample_count).await;
+        let (target, target_view) = create_target(&device, width, height);
+
+        Ok(Self { device, queue, adapter_info, renderer, target, target_view, width, height })
+    }
+
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // Access to shading mode and pipeline choice, same as the keyboard toggles of the viewer
+    pub fn renderer_mut(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 && (width, height) != (self.width, self.height) {
+            (self.width, self.height) = (width, height);
+            (self.target, self.target_view) = create_target(&self.device, width, height);
+            self.renderer.resize(&self.device, width, height);
+        }
+    }
+
+    // Fit the camera to the bounds of the sources, like the viewer does on startup
+    pub fn frame_all(&mut self, sources: &[AllGeometryData], camera: &mut Camera) {
+        self.renderer.set_geometry(&self.device, sources);
+        if let Some((min, max)) = self.renderer.scene().bounds() {
+            camera.set_scene_bounds(min, max);
+            camera.frame_bounds(min, max);
+        }
+    }
+
+    // Render the sources (plus the overlay) as seen by the camera and read the image back.
+    // The camera aspect is not changed; create it with Camera::new(width, height) to avoid stretching.
+    pub fn render(&mut self, sources: &[AllGeometryData], camera: &Camera) -> anyhow::Result<RgbaImage> {
+        self.renderer.set_geometry(&self.device, sources);
+        self.renderer.update_camera(&self.queue, camera);
+        self.renderer.render(&self.device, &self.queue, &self.target_view);
+        self.read_target()
+    }
+
+    // Copy the color target into a mappable buffer; rows are padded to 256 bytes for the copy
+    fn read_target(&self) -> anyhow::Result<RgbaImage> {
+        let unpadded_row = self.width * 4;
+        let padded_row = unpadded_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Readback Buffer"),
+            size: (padded_row * self.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Headless Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            self.target.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 },
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        // Map the buffer and wait for the GPU to finish
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::PollType::Wait)?;
+        receiver.recv()?.context("mapping the headless readback buffer")?;
+
+        let mut pixels = Vec::with_capacity((unpadded_row * self.height) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks_exact(padded_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_row as usize]);
+            }
+        }
+        buffer.unmap();
+
+        Ok(RgbaImage { width: self.width, height: self.height, pixels })
+    }
+}
+
+// Render once and return the image; creates a device per call, use HeadlessRenderer for many images
+pub fn render_to_rgba(sources: &[AllGeometryData], camera: &Camera, width: u32, height: u32) -> anyhow::Result<RgbaImage> {
+    HeadlessRenderer::new(width, height)?.render(sources, camera)
+}
+
+// Render once and write the image to a PNG file
+pub fn render_to_png(sources: &[AllGeometryData], camera: &Camera, width: u32, height: u32, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    render_to_rgba(sources, camera, width, height)?.save_png(path)
+}
+
+fn create_target(device: &wgpu::Device, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Headless Color Target"),
+        size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HEADLESS_FORMAT,
+        // Rendered into, then copied to a buffer for readback
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
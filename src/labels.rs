@@ -0,0 +1,669 @@
+// Text labels drawn with the model: object names (points next to their sprite), mesh vertex keys,
+// attribute values, free annotations and measurements. Labels keep their size in pixels at any
+// distance: every frame they are projected and decluttered on the CPU, then drawn as screen-space quads
+// textured from the glyph atlas of a built-in 5x7 pixel font.
+use std::collections::{BTreeMap, HashMap};
+use bytemuck::Zeroable;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3, Zero};
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+use crate::camera::CameraUniform;
+use crate::colormap::{format_value, AttributeSource};
+use crate::geometry::GeometryBuffers;
+use crate::scene::Scene;
+use crate::section::keeps;
+use crate::texture::Texture;
+use crate::vertex::GlyphInstance;
+
+// Printable ASCII from ' ' to '~' and the degree sign after them, other characters are drawn as '?'
+pub const FIRST_CHAR: u32 = 32;
+const GLYPH_COUNT: u32 = 96;
+// In font pixels: glyphs are 5x7, one column and two rows apart.
+// Atlas cells add a one pixel halo on every side (shader_label.wgsl has the same layout).
+const GLYPH_SIZE: [u32; 2] = [5, 7];
+const ADVANCE: u32 = GLYPH_SIZE[0] + 1;
+const LINE_HEIGHT: u32 = GLYPH_SIZE[1] + 2;
+const CELL: [u32; 2] = [GLYPH_SIZE[0] + 2, GLYPH_SIZE[1] + 2];
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_SIZE: [u32; 2] = [ATLAS_COLUMNS * CELL[0], GLYPH_COUNT.div_ceil(ATLAS_COLUMNS) * CELL[1]];
+
+// Screen pixels per font pixel
+pub const MAX_LABEL_SCALE: u32 = 4;
+// Pixels between a label and its anchor, and kept free around decluttered labels
+pub const LABEL_GAP: f32 = 4.0;
+const DECLUTTER_MARGIN: f32 = 2.0;
+// Anchors move this fraction of their distance towards the eye before the depth test,
+// so a label on a surface is not hidden by that surface
+const LABEL_LIFT: f32 = 0.02;
+// Glyphs drawn per frame at most
+const MAX_GLYPHS: usize = 1 << 16;
+
+// Linear text colors
+const NAME_COLOR: [f32; 3] = [0.02, 0.02, 0.02];
+const KEY_COLOR: [f32; 3] = [0.02, 0.08, 0.5];
+const VALUE_COLOR: [f32; 3] = [0.02, 0.25, 0.04];
+const ANNOTATION_COLOR: [f32; 3] = [0.6, 0.02, 0.02];
+
+// 5x7 glyphs of FIRST_CHAR onwards, five columns each, bit 0 is the top row
+const FONT: [[u8; 5]; GLYPH_COUNT as usize] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+    [0x00, 0x06, 0x09, 0x09, 0x06], // °
+];
+
+// Atlas cell of a character
+fn glyph_index(c: char) -> u32 {
+    let code = c as u32;
+    if c == '°' {
+        return GLYPH_COUNT - 1;
+    }
+    if (FIRST_CHAR..FIRST_CHAR + GLYPH_COUNT).contains(&code) { code - FIRST_CHAR } else { '?' as u32 - FIRST_CHAR }
+}
+
+// Two channel atlas, rows from the top: red is the glyph, green the glyph grown by one pixel (its halo)
+pub fn atlas_texels() -> Vec<[u8; 2]> {
+    let [width, height] = ATLAS_SIZE;
+    let mut texels = vec![[0u8; 2]; (width * height) as usize];
+    for (index, columns) in FONT.iter().enumerate() {
+        let index = index as u32;
+        let (cell_x, cell_y) = ((index % ATLAS_COLUMNS) * CELL[0], (index / ATLAS_COLUMNS) * CELL[1]);
+        for (column, bits) in columns.iter().enumerate() {
+            for row in (0..GLYPH_SIZE[1]).filter(|row| bits & (1 << row) != 0) {
+                let (x, y) = (cell_x + 1 + column as u32, cell_y + 1 + row);
+                for (hx, hy) in (x - 1..=x + 1).flat_map(|hx| (y - 1..=y + 1).map(move |hy| (hx, hy))) {
+                    texels[(hy * width + hx) as usize][1] = 255;
+                }
+                texels[(y * width + x) as usize][0] = 255;
+            }
+        }
+    }
+    texels
+}
+
+// Size of a text block on screen in pixels; lines are split at '\n'
+pub fn text_size(text: &str, scale: u32) -> [f32; 2] {
+    let columns = text.lines().map(|line| line.chars().count() as u32).max().unwrap_or(0);
+    let rows = text.lines().count().max(1) as u32;
+    [((columns.max(1) - 1) * ADVANCE + CELL[0]) as f32 * scale as f32, (rows * LINE_HEIGHT) as f32 * scale as f32]
+}
+
+// What happens to labels whose anchor is behind the model
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelOcclusion {
+    Always, // Drawn over the model
+    Hidden,
+    #[default]
+    Faded, // Drawn faint
+}
+
+// What the labels show; set with Renderer::set_labels, the N key toggles `names`
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LabelSettings {
+    pub visible: bool, // All labels
+    pub names: bool, // Data::name() of the visible objects
+    pub vertex_keys: bool, // Mesh vertex keys
+    pub values: bool, // Values of the colored attribute (see colormap.rs) at the vertices or face centers
+    pub annotations: bool,
+    pub occlusion: LabelOcclusion,
+    pub declutter: bool, // Drop labels overlapping one of higher priority or closer to the camera
+    pub scale: u32, // Screen pixels per font pixel, 1 to MAX_LABEL_SCALE
+}
+
+impl Default for LabelSettings {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            names: false,
+            vertex_keys: false,
+            values: false,
+            annotations: true,
+            occlusion: LabelOcclusion::default(),
+            declutter: true,
+            scale: 2,
+        }
+    }
+}
+
+// Free text at a world position, e.g. loaded from JSON:
+// [{"text": "Column C3", "position": [1, 2, 0], "color": [0, 0, 1]}]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub text: String,
+    pub position: [f32; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[f32; 3]>, // 0-1 linear, dark red when not set
+}
+
+// Annotations from a JSON array or from an object with an "annotations" array
+pub fn parse_annotations(json: &str) -> serde_json::Result<Vec<Annotation>> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Document {
+        List(Vec<Annotation>),
+        Object { annotations: Vec<Annotation> },
+    }
+    Ok(match serde_json::from_str(json)? {
+        Document::List(annotations) | Document::Object { annotations } => annotations,
+    })
+}
+
+// Kinds of labels in decluttering priority, highest first
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LabelKind {
+    Measurement, // Results of the measuring tools (see measure.rs), never hidden by the model
+    Annotation,
+    Name,
+    Value,
+    VertexKey,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub kind: LabelKind,
+    pub text: String,
+    pub position: Point3<f32>, // World-space anchor
+    pub color: [f32; 3],
+    // The point of the text block at `align` (0-1 of its size from the top left)
+    // is placed `offset` pixels from the anchor on screen
+    pub align: [f32; 2],
+    pub offset: [f32; 2],
+}
+
+// Label anchors of one uploaded geometry, in object space
+#[derive(Debug, Default)]
+pub struct GeometryLabels {
+    pub vertices: Vec<(usize, Point3<f32>, Option<f32>)>, // Mesh vertex key, position and attribute value
+    pub faces: Vec<(Point3<f32>, Option<f32>)>, // Face centers and attribute values
+    pub point: Option<(Point3<f32>, f32)>, // Position and sprite diameter of a lone point, its name goes next to the sprite
+}
+
+impl GeometryLabels {
+    pub fn new(geometry: &GeometryBuffers) -> Self {
+        let point = match geometry.points.as_slice() {
+            [point] if geometry.vertices.is_empty() => Some((Point3::from(point.position), point.size)),
+            _ => None,
+        };
+        // Only mesh vertices have keys
+        if geometry.vertices.is_empty() || geometry.vertex_keys.len() != geometry.vertices.len() {
+            return Self { point, ..Default::default() };
+        }
+        let value = |index: usize| {
+            let [value, has_value] = geometry.vertices[index].value;
+            (has_value > 0.0).then_some(value)
+        };
+        let position = |index: usize| Point3::from(geometry.vertices[index].position);
+
+        // Face colored meshes repeat a vertex per face, the first one stands for all
+        let mut vertices: BTreeMap<usize, (Point3<f32>, Option<f32>)> = BTreeMap::new();
+        for (index, key) in geometry.vertex_keys.iter().enumerate() {
+            vertices.entry(*key).or_insert_with(|| (position(index), value(index)));
+        }
+
+        // Area weighted center of the triangles of each face
+        let mut faces: BTreeMap<usize, (Vector3<f32>, f32, Option<f32>)> = BTreeMap::new();
+        for (corners, face) in geometry.indices.chunks_exact(3).zip(&geometry.triangle_elements) {
+            let [a, b, c] = [corners[0], corners[1], corners[2]].map(|i| position(i as usize).to_vec());
+            let area = (b - a).cross(c - a).magnitude().max(f32::MIN_POSITIVE);
+            let entry = faces.entry(*face).or_insert_with(|| (Vector3::zero(), 0.0, value(corners[0] as usize)));
+            entry.0 += (a + b + c) / 3.0 * area;
+            entry.1 += area;
+        }
+
+        Self {
+            vertices: vertices.into_iter().map(|(key, (position, value))| (key, position, value)).collect(),
+            faces: faces.into_values().map(|(sum, area, value)| (Point3::from_vec(sum / area), value)).collect(),
+            point,
+        }
+    }
+}
+
+// Labels the settings ask for, with world-space anchors; anchors cut away by the clip planes are dropped
+pub fn collect_labels(scene: &Scene, settings: &LabelSettings, annotations: &[Annotation], clip_planes: &[[f32; 4]]) -> Vec<Label> {
+    let mut labels = Vec::new();
+    if !settings.visible {
+        return labels;
+    }
+    let label = |kind, text: String, position, color, align, offset| Label { kind, text, position, color, align, offset };
+    if settings.annotations {
+        for annotation in annotations {
+            let color = annotation.color.unwrap_or(ANNOTATION_COLOR);
+            labels.push(label(LabelKind::Annotation, annotation.text.clone(), Point3::from(annotation.position), color, [0.5, 0.5], [0.0; 2]));
+        }
+    }
+
+    let value_source = scene.attribute().map(|(source, _)| source).filter(|_| settings.values);
+    for (key, object, anchors) in scene.labeled_objects() {
+        let world = |p: Point3<f32>| Point3::from_homogeneous(object.model * p.to_homogeneous());
+        if settings.names && !object.name.is_empty() {
+            if let Some((position, size)) = anchors.point {
+                // Right of the sprite
+                labels.push(label(LabelKind::Name, object.name.clone(), world(position), NAME_COLOR, [0.0, 0.5], [size / 2.0 + LABEL_GAP, 0.0]));
+            } else if let Some((min, max)) = scene.object_bounds(key) {
+                // Above the middle of the top of the bounding box
+                let position = Point3::new((min.x + max.x) / 2.0, (min.y + max.y) / 2.0, max.z);
+                labels.push(label(LabelKind::Name, object.name.clone(), position, NAME_COLOR, [0.5, 1.0], [0.0, -LABEL_GAP]));
+            }
+        }
+        // Keys to the upper right of their vertex and values to the lower right, so both fit
+        if settings.vertex_keys {
+            for (vertex_key, position, _) in &anchors.vertices {
+                labels.push(label(LabelKind::VertexKey, vertex_key.to_string(), world(*position), KEY_COLOR, [0.0, 1.0], [LABEL_GAP, -LABEL_GAP]));
+            }
+        }
+        match value_source {
+            Some(AttributeSource::Vertex) => {
+                for (_, position, value) in anchors.vertices.iter().filter(|(_, _, value)| value.is_some()) {
+                    let text = format_value(value.unwrap_or_default());
+                    labels.push(label(LabelKind::Value, text, world(*position), VALUE_COLOR, [0.0, 0.0], [LABEL_GAP, LABEL_GAP]));
+                }
+            }
+            Some(AttributeSource::Face) => {
+                for (position, value) in anchors.faces.iter().filter(|(_, value)| value.is_some()) {
+                    let text = format_value(value.unwrap_or_default());
+                    labels.push(label(LabelKind::Value, text, world(*position), VALUE_COLOR, [0.5, 0.5], [0.0; 2]));
+                }
+            }
+            None => {}
+        }
+    }
+    labels.retain(|label| !label.text.trim().is_empty() && keeps(clip_planes, label.position));
+    labels
+}
+
+// A label that is drawn: its index in the collected labels and where it goes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PlacedLabel {
+    pub label: usize,
+    pub rect: [f32; 4], // Left, top, width and height in pixels from the top left of the viewport
+    pub depth: f32, // Of the lifted anchor, 0 near to 1 far
+}
+
+// Project the labels, drop those behind the camera or off screen and, when decluttering,
+// those overlapping a label of a higher priority kind or a closer one of the same kind.
+// Returned by priority, highest first.
+pub fn layout_labels(labels: &[Label], view_proj: Matrix4<f32>, eye: Point3<f32>, viewport: [f32; 2], settings: &LabelSettings) -> Vec<PlacedLabel> {
+    let scale = settings.scale.clamp(1, MAX_LABEL_SCALE);
+    let mut placed: Vec<PlacedLabel> = labels
+        .iter()
+        .enumerate()
+        .filter_map(|(index, label)| {
+            let anchor = label.position + (eye - label.position) * LABEL_LIFT;
+            let clip = view_proj * anchor.to_homogeneous();
+            if clip.w <= 0.0 {
+                return None;
+            }
+            let depth = clip.z / clip.w;
+            if !(0.0..=1.0).contains(&depth) {
+                return None;
+            }
+            let x = (clip.x / clip.w + 1.0) * 0.5 * viewport[0];
+            let y = (1.0 - clip.y / clip.w) * 0.5 * viewport[1];
+            let [width, height] = text_size(&label.text, scale);
+            // Whole pixels keep the font sharp
+            let left = (x + label.offset[0] - label.align[0] * width).round();
+            let top = (y + label.offset[1] - label.align[1] * height).round();
+            if left >= viewport[0] || top >= viewport[1] || left + width <= 0.0 || top + height <= 0.0 {
+                return None;
+            }
+            Some(PlacedLabel { label: index, rect: [left, top, width, height], depth })
+        })
+        .collect();
+    placed.sort_by(|a, b| labels[a.label].kind.cmp(&labels[b.label].kind).then(a.depth.total_cmp(&b.depth)));
+    if settings.declutter {
+        declutter(placed)
+    } else {
+        placed
+    }
+}
+
+fn overlaps(a: [f32; 4], b: [f32; 4], margin: f32) -> bool {
+    a[0] < b[0] + b[2] + margin && b[0] < a[0] + a[2] + margin && a[1] < b[1] + b[3] + margin && b[1] < a[1] + a[3] + margin
+}
+
+// Keep each label unless it overlaps one kept before it; a grid of screen cells limits the tests
+fn declutter(placed: Vec<PlacedLabel>) -> Vec<PlacedLabel> {
+    const GRID_CELL: f32 = 64.0;
+    let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    let mut kept: Vec<PlacedLabel> = Vec::new();
+    for label in placed {
+        let [left, top, width, height] = label.rect;
+        let cell = |v: f32| (v / GRID_CELL).floor() as i32;
+        let (x0, x1) = (cell(left - DECLUTTER_MARGIN), cell(left + width + DECLUTTER_MARGIN));
+        let (y0, y1) = (cell(top - DECLUTTER_MARGIN), cell(top + height + DECLUTTER_MARGIN));
+        let cells = || (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)));
+        let blocked = cells().any(|c| grid.get(&c).is_some_and(|ks| ks.iter().any(|&k| overlaps(kept[k].rect, label.rect, DECLUTTER_MARGIN))));
+        if blocked {
+            continue;
+        }
+        for c in cells() {
+            grid.entry(c).or_default().push(kept.len());
+        }
+        kept.push(label);
+    }
+    kept
+}
+
+// One quad per visible character; the highest priority labels come last, so they are drawn on top
+fn glyph_instances(labels: &[Label], placed: &[PlacedLabel], scale: u32) -> Vec<GlyphInstance> {
+    let scale = scale as f32;
+    let mut glyphs = Vec::new();
+    for placed in placed.iter().rev() {
+        let label = &labels[placed.label];
+        let [r, g, b] = label.color;
+        for (row, line) in label.text.lines().enumerate() {
+            for (column, c) in line.chars().enumerate().filter(|(_, c)| *c != ' ') {
+                if glyphs.len() == MAX_GLYPHS {
+                    return glyphs;
+                }
+                let left = placed.rect[0] + (column as u32 * ADVANCE) as f32 * scale;
+                let top = placed.rect[1] + (row as u32 * LINE_HEIGHT) as f32 * scale;
+                glyphs.push(GlyphInstance {
+                    rect: [left, top, CELL[0] as f32 * scale, CELL[1] as f32 * scale],
+                    color: [r, g, b, 1.0],
+                    glyph: glyph_index(c),
+                    // In front of everything, so the depth test never hides them
+                    depth: if label.kind == LabelKind::Measurement { 0.0 } else { placed.depth },
+                });
+            }
+        }
+    }
+    glyphs
+}
+
+pub struct Labels {
+    settings: LabelSettings,
+    annotations: Vec<Annotation>,
+    atlas: Option<Texture>, // Until uploaded
+    bind_group: wgpu::BindGroup,
+    scene_pipeline: wgpu::RenderPipeline, // Depth tested in the opaque pass
+    top_pipeline: wgpu::RenderPipeline, // Over everything in the composite pass
+    faded_pipeline: wgpu::RenderPipeline, // Like top_pipeline, at low alpha
+    instance_buffer: wgpu::Buffer, // Grows to the most glyphs drawn so far
+    glyph_count: u32,
+}
+
+impl Labels {
+    pub fn new(device: &wgpu::Device, camera_bind_group_layout: &wgpu::BindGroupLayout, color_format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Label Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader_label.wgsl").into()),
+        });
+
+        // Filled by the first prepare()
+        let [width, height] = ATLAS_SIZE;
+        let atlas = Texture::create_image_texture(device, wgpu::TextureFormat::Rg8Unorm, width, height, "label_atlas_texture");
+        // Glyph quads cover whole multiples of the atlas pixels
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("label_atlas_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("label_bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&atlas.view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+            label: Some("label_bind_group"),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Label Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = |label: &str, fragment_entry: &str, in_scene: bool| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[GlyphInstance::desc()],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(fragment_entry),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: in_scene.then(Texture::depth_read_only_state),
+                multisample: wgpu::MultisampleState {
+                    count: if in_scene { sample_count } else { 1 },
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+        let scene_pipeline = pipeline("Label Pipeline", "fs_main", true);
+        let top_pipeline = pipeline("Label Top Pipeline", "fs_main", false);
+        let faded_pipeline = pipeline("Label Faded Pipeline", "fs_faded", false);
+
+        Self {
+            settings: LabelSettings::default(),
+            annotations: Vec::new(),
+            atlas: Some(atlas),
+            bind_group,
+            scene_pipeline,
+            top_pipeline,
+            faded_pipeline,
+            instance_buffer: Self::create_instance_buffer(device, &[GlyphInstance::zeroed(); 256]),
+            glyph_count: 0,
+        }
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, glyphs: &[GlyphInstance]) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Glyph Instance Buffer"),
+            contents: bytemuck::cast_slice(glyphs),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        })
+    }
+
+    pub fn settings(&self) -> LabelSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: LabelSettings) {
+        self.settings = LabelSettings { scale: settings.scale.clamp(1, MAX_LABEL_SCALE), ..settings };
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    pub fn set_annotations(&mut self, annotations: Vec<Annotation>) {
+        self.annotations = annotations;
+    }
+
+    // Lay the labels out for the camera of the coming frame and upload their glyphs.
+    // Measurement labels are part of the measuring tools: shown even when the labels are not.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, scene: &Scene, camera: &CameraUniform, clip_planes: &[[f32; 4]], measurements: &[Label]) {
+        if let Some(atlas) = self.atlas.take() {
+            let [width, height] = ATLAS_SIZE;
+            queue.write_texture(
+                atlas.texture.as_image_copy(),
+                bytemuck::cast_slice(&atlas_texels()),
+                wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(2 * width), rows_per_image: Some(height) },
+                wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            );
+        }
+        self.glyph_count = 0;
+        let mut labels = collect_labels(scene, &self.settings, &self.annotations, clip_planes);
+        labels.extend_from_slice(measurements);
+        if labels.is_empty() {
+            return;
+        }
+        let [x, y, z, _] = camera.eye;
+        let viewport = [camera.viewport[0], camera.viewport[1]];
+        let placed = layout_labels(&labels, Matrix4::from(camera.view_proj), Point3::new(x, y, z), viewport, &self.settings);
+        let glyphs = glyph_instances(&labels, &placed, self.settings.scale);
+        if glyphs.is_empty() {
+            return;
+        }
+        let size = std::mem::size_of_val(glyphs.as_slice()) as wgpu::BufferAddress;
+        if size > self.instance_buffer.size() {
+            let mut grown = glyphs.clone();
+            grown.resize(glyphs.len().next_power_of_two(), GlyphInstance::zeroed());
+            self.instance_buffer = Self::create_instance_buffer(device, &grown);
+        } else {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&glyphs));
+        }
+        self.glyph_count = glyphs.len() as u32;
+    }
+
+    fn draw(&self, render_pass: &mut wgpu::RenderPass, pipeline: &wgpu::RenderPipeline, camera_bind_group: &wgpu::BindGroup) {
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.glyph_count);
+    }
+
+    // Depth tested labels, after the opaque model in the same pass
+    pub fn draw_scene(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if self.glyph_count > 0 && self.settings.occlusion != LabelOcclusion::Always {
+            self.draw(render_pass, &self.scene_pipeline, camera_bind_group);
+        }
+    }
+
+    pub fn draws_top(&self) -> bool {
+        self.glyph_count > 0 && self.settings.occlusion != LabelOcclusion::Hidden
+    }
+
+    // Labels over everything (faded ones over their depth tested copy), in a pass on the resolved target
+    pub fn draw_top(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if !self.draws_top() {
+            return;
+        }
+        let pipeline = if self.settings.occlusion == LabelOcclusion::Faded { &self.faded_pipeline } else { &self.top_pipeline };
+        self.draw(render_pass, pipeline, camera_bind_group);
+    }
+}
@@ -1,22 +1,55 @@
-use std::{iter, sync::Arc}; // Arc is a thread-safe reference-counted pointer
+use std::sync::Arc; // Arc is a thread-safe reference-counted pointer
 use anyhow::Result;
 use winit::{
     application::ApplicationHandler, 
+    dpi::PhysicalPosition,
     event::{WindowEvent, KeyEvent, MouseButton, ElementState}, //* - import everythingi is skipped due to warnings
-    event_loop::{ActiveEventLoop, EventLoop}, 
+    event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy}, 
     keyboard::{KeyCode, PhysicalKey}, 
     window::Window
 };
 pub mod vertex;
 pub mod camera;
 pub mod timing;
-use vertex::Vertex;
-use camera::{Camera, CameraUniform, CameraController};
+pub mod texture;
+pub mod geometry;
+pub mod shading;
+pub mod scene;
+pub mod picking;
+pub mod section;
+pub mod overlay;
+pub mod colormap;
+pub mod labels;
+pub mod measure;
+pub mod shadow;
+pub mod post;
+pub mod renderer;
+pub mod sources;
+pub mod stream;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
+#[cfg(not(target_arch = "wasm32"))]
+mod watcher;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+use picking::{PickCallback, PickResult};
+use geometry::GeometryObject;
+use renderer::Renderer;
+use texture::Texture;
+use sources::Config;
+use stream::StreamMessage;
+use camera::{Camera, CameraController, NamedView, Projection};
+use overlay::OverlaySettings;
+use colormap::{attribute_names, AttributeColoring, Colormap};
+use labels::{Annotation, LabelSettings};
+use measure::{MeasureCallback, MeasureTarget, MeasureTool};
+use shadow::SunSettings;
+use post::PostSettings;
 use timing::Instant;
-use wgpu::util::DeviceExt;
 // OpenModel: JSON geometry + mesh utilities
 use openmodel::AllGeometryData;
-use openmodel::geometry::{Mesh, Point};
+use openmodel::geometry::Plane;
+use cgmath::Point3;
 
 // Shared remote geometry URL used by both native and WASM builds
 // Use standard raw path: https://raw.githubusercontent.com/<user>/<repo>/<branch>/<path>
@@ -24,21 +57,24 @@ const REMOTE_GEOMETRY_URL: &str = "https://raw.githubusercontent.com/petrasvesta
 #[cfg(target_arch = "wasm32")]
 const LOCAL_GEOMETRY_HTTP_PATH: &str = "/geometry/all_geometry.json"; // served by docs dev server
 
-// Native-only: absolute path to local JSON for fast runtime reloads (fallbacks to include_str! if not found)
+// Native-only: absolute path to the local JSON loaded by the default config (fallbacks to include_str! if not found)
 #[cfg(not(target_arch = "wasm32"))]
 const LOCAL_GEOMETRY_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/openmodel/all_geometry.json");
 
-// Polling interval for change detection (ms)
+// Default polling interval for change detection (ms), see Source::poll_ms
 const GEOMETRY_POLL_INTERVAL_MS: u64 = 1000;
 
+// A left press and release closer than this (in pixels) counts as a click that picks
+const PICK_CLICK_TOLERANCE_PX: f64 = 4.0;
+
 #[cfg(target_arch = "wasm32")]
 use std::cell::{Cell, RefCell};
 
 #[cfg(target_arch = "wasm32")]
 thread_local! {
-    static PENDING_GEOMETRY: RefCell<Option<(Vec<Vertex>, Vec<u16>)>> = RefCell::new(None);
-    static REMOTE_HASH: RefCell<Option<u64>> = RefCell::new(None);
-    static LOCAL_HASH: RefCell<Option<u64>> = RefCell::new(None);
+    static PENDING_GEOMETRY: RefCell<Option<Vec<AllGeometryData>>> = RefCell::new(None);
+    // Last fetched (source, text) pairs, to detect changes and keep unwatched sources
+    static SOURCE_TEXTS: RefCell<Vec<(String, String)>> = RefCell::new(Vec::new());
     static REMOTE_FETCHING: Cell<bool> = Cell::new(false);
 }
 
@@ -76,56 +112,59 @@ fn fnv1a64(bytes: &[u8]) -> u64 {
     }
     hash
 }
-// Helper: push mesh faces as triangles (fan) with per-vertex or default color
-fn append_mesh_as_triangles(
-    mesh: &Mesh,
-    default_color: [f32; 3],
-    vertices: &mut Vec<Vertex>,
-    indices: &mut Vec<u16>,
-) {
-    for (_face_key, face_vertices) in mesh.get_face_data() {
-        if face_vertices.len() < 3 { continue; }
-        for i in 1..(face_vertices.len() - 1) {
-            let tri = [face_vertices[0], face_vertices[i], face_vertices[i + 1]];
-            for &vk in &tri {
-                if let Some(pos) = mesh.vertex_position(vk) {
-                    let use_default = if let Some(vd) = mesh.vertex.get(&vk) {
-                        !(vd.attributes.contains_key("r") && vd.attributes.contains_key("g") && vd.attributes.contains_key("b"))
-                    } else { true };
-                    let color = if use_default {
-                        default_color
-                    } else if let Some(vd) = mesh.vertex.get(&vk) {
-                        let c = vd.color();
-                        [c[0] as f32, c[1] as f32, c[2] as f32]
-                    } else { default_color };
-
-                    if vertices.len() >= u16::MAX as usize { break; }
-                    vertices.push(Vertex { position: [pos.x as f32, pos.y as f32, pos.z as f32], color });
-                    indices.push((vertices.len() - 1) as u16);
-                }
-            }
-        }
-    }
+// Events sent to the event loop from other threads or async tasks through an EventLoopProxy
+pub enum UserEvent {
+    // Web: the State is created asynchronously and handed over when ready
+    // (native creates it in resumed(), and keeping State out lets the proxy move to other threads)
+    #[cfg(target_arch = "wasm32")]
+    StateReady(Box<State>),
+    // New geometry sources parsed off the render thread
+    GeometryLoaded(Vec<AllGeometryData>),
+    // Command received from the geometry stream
+    Stream(Box<StreamMessage>),
+    // Request from the embedding application, e.g. the JavaScript API
+    Command(ViewerCommand),
 }
 
-// Helper: 10x10 grid (11 lines per direction) + 1-unit Z axis as pipes
-fn make_grid_and_axis_meshes() -> Vec<(Mesh, [f32; 3])> {
-    let mut out = Vec::new();
-    let size: i32 = 5; // -5..=5 => 11 lines => 10x10 cells
-    let radius: f64 = 0.02;
-    let grid_color: [f32; 3] = [0.3, 0.3, 0.3];
-    let axis_color: [f32; 3] = [0.0, 0.0, 1.0];
-
-    for i in -size..=size {
-        let y = i as f64;
-        out.push((Mesh::create_pipe(Point::new(-(size as f64), y, 0.0), Point::new(size as f64, y, 0.0), radius), grid_color));
-    }
-    for i in -size..=size {
-        let x = i as f64;
-        out.push((Mesh::create_pipe(Point::new(x, -(size as f64), 0.0), Point::new(x, size as f64, 0.0), radius), grid_color));
-    }
-    out.push((Mesh::create_pipe(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 0.0, 1.0), 0.03), axis_color));
-    out
+// Viewer controls that do not come from the window, applied on the event loop
+pub enum ViewerCommand {
+    // Remove the loaded sources and all streamed objects
+    Clear,
+    SetView(NamedView),
+    SetProjection(Projection),
+    FrameAll,
+    // Show or hide an object; duplicates of the guid are affected too
+    SetVisible { guid: String, visible: bool },
+    // Opacity from 0 (invisible) to 1 (opaque) of an object and its duplicates
+    SetOpacity { guid: String, opacity: f32 },
+    SetXray(bool),
+    // Replace the clip planes set through the API; section planes of the sources stay
+    SetClipPlanes(Vec<Plane>),
+    // Keep only what is inside the box (min and max corners), None turns it off
+    SetSectionBox(Option<(Point3<f32>, Point3<f32>)>),
+    // Cap color of cut solids, None leaves them open
+    SetSectionFill(Option<[f32; 3]>),
+    // Grid, axes and view gizmo
+    SetOverlay(OverlaySettings),
+    // Color meshes by a vertex or face attribute, None shows their own colors
+    SetAttributeColoring(Option<AttributeColoring>),
+    // Which labels are shown and how they are occluded and decluttered
+    SetLabels(LabelSettings),
+    // Replace the free text annotations
+    SetAnnotations(Vec<Annotation>),
+    // Measuring tool taking the left clicks instead of selection, None selects again
+    SetMeasureTool(Option<MeasureTool>),
+    ClearMeasurements,
+    // Sun position, shadows and ground plane
+    SetSun(SunSettings),
+    // Ambient occlusion and silhouette outlines
+    SetPost(PostSettings),
+    // Web: replace the pick callback (JS functions cannot move to other threads)
+    #[cfg(target_arch = "wasm32")]
+    SetPickCallback(PickCallback),
+    // Web: replace the callback receiving the measurements whenever they change
+    #[cfg(target_arch = "wasm32")]
+    SetMeasureCallback(MeasureCallback),
 }
 
 pub struct State{
@@ -134,34 +173,33 @@ pub struct State{
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     is_surface_configured: bool,
-    // Shader pipelines
-    render_pipeline_solid: wgpu::RenderPipeline, // First pipeline (one color)
-    render_pipeline_color: wgpu::RenderPipeline, // Second pipeline (vertex colors)
-    use_color_pipeline: bool,                    // Whether to use the second pipeline
-    vertex_buffer: wgpu::Buffer, // We will store data of vertex.rs in this buffer
-    index_buffer: wgpu::Buffer, // We will store data of vertex.rs in this buffer
+    renderer: Renderer, // Pipelines, depth buffer, camera uniform and scene buffers
     // Camera system - testing step by step
     camera: Camera,
-    camera_uniform: CameraUniform,
-    camera_buffer: wgpu::Buffer,
-    camera_bind_group: wgpu::BindGroup,
     camera_controller: CameraController,
     last_render_time: Instant,
-    // Change detection throttle timestamp
+    // Web-only: change detection throttle timestamp (native reloads come from the watcher thread)
+    #[cfg(target_arch = "wasm32")]
     last_poll_time: Instant,
+    // Web-only: sources to poll, set by the App once the State exists
+    #[cfg(target_arch = "wasm32")]
+    source_config: Config,
     mouse_pressed: bool,
+    // Picking: left click without dragging selects the object under the cursor
+    cursor_position: PhysicalPosition<f64>,
+    left_press_position: Option<PhysicalPosition<f64>>,
+    pick_callback: Option<PickCallback>,
+    measure_callback: Option<MeasureCallback>,
+    sources: Vec<AllGeometryData>, // Loaded geometry, kept to report attributes of picked objects
+    streamed: AllGeometryData, // Objects received from the geometry stream, drawn after the sources
     // default pointer to the window
     window: Arc<Window>,
-    // Native-only: track local file mtime and remote ETag/Last-Modified between polls
-    #[cfg(not(target_arch = "wasm32"))]
-    last_local_mtime: Option<std::time::SystemTime>,
-    #[cfg(not(target_arch = "wasm32"))]
-    last_remote_tag: Option<String>,
 }
 
 impl State{
-    // We don't need to be async right now, will implement later
-    pub async fn new(window: Arc<Window>, vertices: &[Vertex], indices: &[u16]) -> anyhow::Result<Self> {
+    // We don't need to be async right now, will implement later.
+    // sample_count is the requested MSAA level, lowered to what the adapter supports.
+    pub async fn new(window: Arc<Window>, sources: Vec<AllGeometryData>, sample_count: u32) -> anyhow::Result<Self> {
 
         let size = window.inner_size();
 
@@ -189,7 +227,7 @@ impl State{
         }).await?;
 
         // Use adapter to create device and queue
-        // This current example doesn't use any features.
+        // The only optional feature is the one allowing MSAA sample counts other than 4.
         // Full list of features: https://docs.rs/wgpu/latest/wgpu/struct.Features.html
         // Full list of limits: https://docs.rs/wgpu/latest/wgpu/struct.Limits.html
         // The mmemory_hints field provides the adapter with a preferred memory allocation strategy.
@@ -197,13 +235,21 @@ impl State{
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::empty(),
+                required_features: adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
                 // WebGL doesn't support all of wgpu's features, so if
                 // we're building for the web we'll have to disable some.
+                // Large building models need big vertex/index buffers, so we ask for
+                // the biggest buffer the adapter supports instead of the 256MB default.
                 required_limits: if cfg!(target_arch = "wasm32") {
-                    wgpu::Limits::downlevel_webgl2_defaults()
+                    wgpu::Limits {
+                        max_buffer_size: adapter.limits().max_buffer_size,
+                        ..wgpu::Limits::downlevel_webgl2_defaults()
+                    }
                 } else {
-                    wgpu::Limits::default()
+                    wgpu::Limits {
+                        max_buffer_size: adapter.limits().max_buffer_size,
+                        ..wgpu::Limits::default()
+                    }
                 },
                 memory_hints: Default::default(),
                 trace: wgpu::Trace::Off,
@@ -244,222 +290,23 @@ impl State{
             desired_maximum_frame_latency: 2,
         };
 
-        ////////////////////////////////////////////////////////////////////////////////////////////////////////////
-        // SHADERS
-        ////////////////////////////////////////////////////////////////////////////////////////////////////////////
-        // Debug: capture validation errors during pipeline/build steps (especially useful on Web)
-        // Push one scope before creating pipelines; we'll pop it after both are created.
-        device.push_error_scope(wgpu::ErrorFilter::Validation);
-
-        // Pipeline. We will have to load shaders, as the render pipeline require them.
-        let shader_solid = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Solid Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader_solid.wgsl").into()),
-        });
-
-        let shader_color = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Color Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader_color.wgsl").into()),
-        });
-
-        // Pipeline layout - testing camera bind group step by step
-        let camera_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("camera_bind_group_layout"),
-            });
-
-        let render_pipeline_layout =
-        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&camera_bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        // Pipeline for rendering
-        let render_pipeline_solid = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Solid Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            
-            vertex: wgpu::VertexState {
-                module: &shader_solid, // <-- Change the shader
-                entry_point: Some("vs_main"), // 1. vertex entry point
-                buffers: &[
-                    Vertex::desc(), // The implementation of the vertex struct
-                ], // 2. tells wgpu that type of vetices we want to pass to vertex shader
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-
-            fragment: Some(wgpu::FragmentState { // 3. This is optional so we wrap to Some(), we need it for colors
-                module: &shader_solid,  // <-- Change the shader
-                entry_point: Some("fs_main"), // 1. fragment entry point
-                targets: &[Some(wgpu::ColorTargetState { // 4. tells wgpu what color outputs it should set up
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-
-            // The primitive field describes how to interpret our vertices when converting them into triangles.
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList, // Means that every three verties will correspond to one triangle.
-                strip_index_format: None,
-                // Face orientation and culling debug:
-                // - With DEBUG_FACE_COLORING = true in shaders:
-                //     front faces (CCW) = vertex colors, back faces = red
-                // - To see BOTH sides: keep cull_mode = None (debug view)
-                // - To see ONLY front faces: set cull_mode = Some(wgpu::Face::Back)
-                // - To see ONLY back faces:  set cull_mode = Some(wgpu::Face::Front)
-                // - If colors appear flipped, change front_face between Ccw/Cw to match your mesh winding
-                front_face: wgpu::FrontFace::Ccw, // CounterClockWise is facing forward, cw are culled
-                cull_mode: None, // Debug default: no culling to view both front/back coloring
-                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-                // or Features::POLYGON_MODE_POINT
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-
-            // We are not using a depth/stencil buffer currently
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1, // Determines how many samples the pipeline will use
-                mask: !0, // Specifies which samples should be active, here we use all
-                alpha_to_coverage_enabled: false, // related to multisampling which is not used here
-            },
-            // If the pipeline will be used with a multiview render pass, this
-            // indicates how many array layers the attachments will have.
-            multiview: None,
-            // Useful for optimizing shader compilation on Android
-            cache: None,
-        });
-
-
-         // Pipeline for rendering
-         let render_pipeline_color = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Color Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            
-            vertex: wgpu::VertexState {
-                module: &shader_color, // <-- Change the shader
-                entry_point: Some("vs_main"), // 1. vertex entry point
-                buffers: &[
-                    Vertex::desc(),
-                ], // 2. tells wgpu that type of vetices we want to pass to vertex shader
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-
-            fragment: Some(wgpu::FragmentState { // 3. This is optional so we wrap to Some(), we need it for colors
-                module: &shader_color, // <-- Change the shader
-                entry_point: Some("fs_main"), // 1. fragment entry point
-                targets: &[Some(wgpu::ColorTargetState { // 4. tells wgpu what color outputs it should set up
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-
-            // The primitive field describes how to interpret our vertices when converting them into triangles.
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList, // Means that every three verties will correspond to one triangle.
-                strip_index_format: None,
-                // Face orientation and culling debug:
-                // - With DEBUG_FACE_COLORING = true in shaders:
-                //     front faces (CCW) = vertex colors, back faces = red
-                // - To see BOTH sides: keep cull_mode = None (debug view)
-                // - To see ONLY front faces: set cull_mode = Some(wgpu::Face::Back)
-                // - To see ONLY back faces:  set cull_mode = Some(wgpu::Face::Front)
-                // - If colors appear flipped, change front_face between Ccw/Cw to match your mesh winding
-                front_face: wgpu::FrontFace::Ccw, // CounterClockWise is facing forward, cw are culled
-                cull_mode: None, // Debug default: no culling to view both front/back coloring
-                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-                // or Features::POLYGON_MODE_POINT
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-
-            // We are not using a depth/stencil buffer currently
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1, // Determines how many samples the pipeline will use
-                mask: !0, // Specifies which samples should be active, here we use all
-                alpha_to_coverage_enabled: false, // related to multisampling which is not used here
-            },
-            // If the pipeline will be used with a multiview render pass, this
-            // indicates how many array layers the attachments will have.
-            multiview: None,
-            // Useful for optimizing shader compilation on Android
-            cache: None,
-        });
-
-        // Pop and log any validation errors that might have occurred during pipeline creation
-        #[cfg(target_arch = "wasm32")]
-        if let Some(err) = device.pop_error_scope().await {
-            web_sys::console::error_1(&format!("WGPU validation (pipeline): {:?}", err).into());
-        }
-        #[cfg(not(target_arch = "wasm32"))]
-        if let Some(err) = device.pop_error_scope().await {
-            eprintln!("WGPU validation (pipeline): {:?}", err);
+        // Pipelines, depth buffer and camera uniform live in the surface independent renderer
+        let sample_count = Texture::supported_sample_count(&adapter, &device, &[config.format, Texture::DEPTH_FORMAT], sample_count);
+        log::info!("MSAA: {}x", sample_count);
+        let mut renderer = Renderer::new(&adapter, &device, config.format, config.width, config.height, sample_count).await;
+
+        // Create GPU buffers from provided geometry, one entry per object
+        let stats = renderer.set_geometry(&device, &sources);
+        log::info!("Scene loaded: {} objects", stats.added);
+
+        // Initialize camera system, looking at the whole model
+        let mut camera = Camera::new(size.width as f32, size.height as f32);
+        if let Some((min, max)) = renderer.scene().bounds() {
+            camera.set_scene_bounds(min, max);
+            camera.frame_bounds(min, max);
+            camera.set_initial_to_current();
         }
-
-        // Create GPU buffers from provided geometry
-        let vertex_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
-        );
-
-        let index_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(indices),
-                usage: wgpu::BufferUsages::INDEX,
-            }
-        );
-
-        // Initialize camera system
-        let camera = Camera::new(size.width as f32, size.height as f32);
-        let mut camera_uniform = CameraUniform::new();
-        camera_uniform.update_view_proj(&camera);
-
-        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"),
-            contents: bytemuck::cast_slice(&[camera_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        // Debug: log the first row of the view-proj on Web to ensure it isn't zeros/NaNs
-        #[cfg(target_arch = "wasm32")]
-        {
-            web_sys::console::log_1(&format!("Camera VP row0: {:?}", camera_uniform.view_proj[0]).into());
-        }
-
-        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
-            label: Some("camera_bind_group"),
-        });
+        renderer.update_camera(&queue, &camera);
 
         let camera_controller = CameraController::new(4.0, 0.4);
 
@@ -471,192 +318,163 @@ impl State{
             queue,
             config,
             is_surface_configured: false,
-            // Pipeline for rendering solid color
-            render_pipeline_solid,
-            render_pipeline_color,
-            use_color_pipeline: true,  
-            vertex_buffer,
-            index_buffer,
+            renderer,
             // Camera system - testing step by step
             camera,
-            camera_uniform,
-            camera_buffer,
-            camera_bind_group,
             camera_controller,
             last_render_time: Instant::now(),
+            #[cfg(target_arch = "wasm32")]
             last_poll_time: Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            source_config: Config::default(),
             mouse_pressed: false,
+            cursor_position: PhysicalPosition::new(0.0, 0.0),
+            left_press_position: None,
+            pick_callback: None,
+            measure_callback: None,
+            sources,
+            streamed: AllGeometryData::default(),
             window,
-            #[cfg(not(target_arch = "wasm32"))]
-            last_local_mtime: None,
-            #[cfg(not(target_arch = "wasm32"))]
-            last_remote_tag: None,
         })
     }
 
-    // Replace GPU buffers with new geometry
-    fn replace_geometry(&mut self, vertices: &[Vertex], indices: &[u16]) {
-        let new_vertex_buffer = self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            }
+    // Update the scene from reloaded sources; only new or changed objects are re-uploaded
+    pub fn replace_geometry(&mut self, sources: Vec<AllGeometryData>) {
+        self.sources = sources;
+        let stats = self.sync_scene();
+        let message = format!(
+            "Geometry reloaded: {} added, {} updated, {} removed, {} unchanged",
+            stats.added, stats.updated, stats.removed, stats.unchanged
         );
-        let new_index_buffer = self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(indices),
-                usage: wgpu::BufferUsages::INDEX,
-            }
-        );
-        self.vertex_buffer = new_vertex_buffer;
-        self.index_buffer = new_index_buffer;
         #[cfg(target_arch = "wasm32")]
         {
-            web_sys::console::log_1(&"Geometry buffers reloaded".into());
+            web_sys::console::log_1(&message.into());
         }
         #[cfg(not(target_arch = "wasm32"))]
         {
-            log::info!("Geometry buffers reloaded");
+            log::info!("{}", message);
         }
     }
 
-    // Check for geometry changes and reload if needed (throttled)
-    fn poll_geometry_changes(&mut self) {
-        let now = Instant::now();
-        if (now - self.last_poll_time).as_millis() < (GEOMETRY_POLL_INTERVAL_MS as u128) {
+    // Apply a stream command; like reloads, only the objects it touches are re-uploaded
+    pub fn apply_stream(&mut self, message: StreamMessage) {
+        if let StreamMessage::Annotations { annotations } = message {
+            self.renderer.set_annotations(annotations);
             return;
         }
-        self.last_poll_time = now;
+        message.apply(&mut self.streamed);
+        let stats = self.sync_scene();
+        log::debug!(
+            "Stream update: {} added, {} updated, {} removed, {} unchanged",
+            stats.added, stats.updated, stats.removed, stats.unchanged
+        );
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            use std::time::{Duration as StdDuration};
-            let mut changed = false;
-
-            // Local file mtime check
-            if let Ok(meta) = std::fs::metadata(LOCAL_GEOMETRY_PATH) {
-                if let Ok(mtime) = meta.modified() {
-                    if self.last_local_mtime.map_or(true, |prev| prev != mtime) {
-                        self.last_local_mtime = Some(mtime);
-                        changed = true;
-                    }
+    pub fn apply_command(&mut self, command: ViewerCommand) {
+        match command {
+            ViewerCommand::Clear => {
+                self.streamed = AllGeometryData::default();
+                self.replace_geometry(Vec::new());
+            }
+            ViewerCommand::SetView(view) => self.camera.set_view(view),
+            ViewerCommand::SetProjection(projection) => self.camera.projection = projection,
+            ViewerCommand::FrameAll => self.frame_all(),
+            ViewerCommand::SetVisible { guid, visible } => {
+                for key in self.scene_keys(&guid) {
+                    self.renderer.scene_mut().set_visible(&key, visible);
                 }
             }
-
-            // Remote HEAD ETag/Last-Modified check (fast, small timeout)
-            if let Ok(client) = reqwest::blocking::Client::builder()
-                .timeout(StdDuration::from_millis(400))
-                .build()
-            {
-                if let Ok(resp) = client.head(REMOTE_GEOMETRY_URL).send() {
-                    if resp.status().is_success() {
-                        let headers = resp.headers();
-                        let tag = headers
-                            .get(reqwest::header::ETAG)
-                            .and_then(|v| v.to_str().ok())
-                            .map(|s| format!("etag:{}", s))
-                            .or_else(|| headers
-                                .get(reqwest::header::LAST_MODIFIED)
-                                .and_then(|v| v.to_str().ok())
-                                .map(|s| format!("lm:{}", s)))
-                            .or_else(|| headers
-                                .get(reqwest::header::CONTENT_LENGTH)
-                                .and_then(|v| v.to_str().ok())
-                                .map(|s| format!("len:{}", s)));
-                        if let Some(tag) = tag {
-                            if self.last_remote_tag.as_deref() != Some(tag.as_str()) {
-                                self.last_remote_tag = Some(tag);
-                                changed = true;
-                            }
-                        }
-                    }
+            ViewerCommand::SetOpacity { guid, opacity } => {
+                for key in self.scene_keys(&guid) {
+                    self.renderer.scene_mut().set_opacity(&key, opacity);
                 }
             }
-
-            if changed {
-                let (vertices, indices) = get_geometry();
-                self.replace_geometry(&vertices, &indices);
+            ViewerCommand::SetXray(xray) => self.renderer.set_xray(xray),
+            ViewerCommand::SetClipPlanes(planes) => self.renderer.set_clip_planes(&planes),
+            ViewerCommand::SetSectionBox(bounds) => self.renderer.set_section_box(bounds),
+            ViewerCommand::SetSectionFill(fill) => self.renderer.set_section_fill(fill),
+            ViewerCommand::SetOverlay(settings) => self.renderer.set_overlay(settings),
+            ViewerCommand::SetAttributeColoring(coloring) => self.set_attribute_coloring(coloring),
+            ViewerCommand::SetLabels(settings) => self.renderer.set_labels(settings),
+            ViewerCommand::SetAnnotations(annotations) => self.renderer.set_annotations(annotations),
+            ViewerCommand::SetMeasureTool(tool) => self.renderer.measurements_mut().set_tool(tool),
+            ViewerCommand::ClearMeasurements => {
+                self.renderer.measurements_mut().clear();
+                self.report_measurements();
             }
+            ViewerCommand::SetSun(settings) => self.renderer.set_sun(settings),
+            ViewerCommand::SetPost(settings) => self.renderer.set_post(settings),
+            #[cfg(target_arch = "wasm32")]
+            ViewerCommand::SetPickCallback(callback) => self.pick_callback = Some(callback),
+            #[cfg(target_arch = "wasm32")]
+            ViewerCommand::SetMeasureCallback(callback) => self.measure_callback = Some(callback),
+        }
+    }
+
+    // Scene keys of an object guid; duplicate guids get a "#n" suffix in the scene
+    fn scene_keys(&self, guid: &str) -> Vec<String> {
+        let keys: Vec<String> = self.renderer.scene().keys()
+            .filter(|key| key.split('#').next() == Some(guid))
+            .cloned()
+            .collect();
+        if keys.is_empty() {
+            log::warn!("No object with guid {}", guid);
         }
+        keys
+    }
+
+    // Upload the sources and the streamed objects to the scene
+    fn sync_scene(&mut self) -> scene::SyncStats {
+        self.sources.push(std::mem::take(&mut self.streamed));
+        let stats = self.renderer.set_geometry(&self.device, &self.sources);
+        self.streamed = self.sources.pop().unwrap_or_default();
+        // Keep zoom limits and clip planes matched to the model, without moving the view
+        if let Some((min, max)) = self.renderer.scene().bounds() {
+            self.camera.set_scene_bounds(min, max);
+        }
+        stats
+    }
+
+    // Web: check for geometry changes and reload if needed (throttled).
+    // All watched sources are refetched at the shortest poll interval among them.
+    // Native reloads are pushed by the watcher thread as UserEvent::GeometryLoaded.
+    #[cfg(target_arch = "wasm32")]
+    fn poll_geometry_changes(&mut self) {
+        let interval = self.source_config.active_sources().filter(|s| s.watch).map(|s| s.poll_interval_ms()).min();
+        let Some(interval) = interval else {
+            return;
+        };
+        let now = Instant::now();
+        if (now - self.last_poll_time).as_millis() < (interval as u128) {
+            return;
+        }
+        self.last_poll_time = now;
 
-        #[cfg(target_arch = "wasm32")]
         {
             // If a fetch is already running, just try to apply pending result
             let already_fetching = REMOTE_FETCHING.with(|f| f.get());
             if !already_fetching {
                 REMOTE_FETCHING.with(|f| f.set(true));
-                // Spawn async poll for remote JSON; only apply if content hash changed
+                let config = self.source_config.clone();
+                // Spawn async poll of the watched sources; only apply if any content changed
                 spawn_local(async move {
-                    // Fetch local-served JSON
-                    let local_text = fetch_text(LOCAL_GEOMETRY_HTTP_PATH).await;
-                    let local_changed = if let Some(ref t) = local_text {
-                        let new_hash = fnv1a64(t.as_bytes());
-                        LOCAL_HASH.with(|h| {
-                            let mut hb = h.borrow_mut();
-                            if hb.map_or(true, |old| old != new_hash) { *hb = Some(new_hash); true } else { false }
-                        })
-                    } else { false };
-
-                    // Fetch remote RAW JSON
-                    let remote_text = fetch_text(REMOTE_GEOMETRY_URL).await;
-                    let remote_changed = if let Some(ref t) = remote_text {
-                        let new_hash = fnv1a64(t.as_bytes());
-                        REMOTE_HASH.with(|h| {
-                            let mut hb = h.borrow_mut();
-                            if hb.map_or(true, |old| old != new_hash) { *hb = Some(new_hash); true } else { false }
-                        })
-                    } else { false };
-
-                    if local_changed || remote_changed {
-                        // Build geometry from both sources (remote + local) while avoiding duplicates by content hash.
-                        // If neither is available, fall back to embedded. Always add grid/axis once.
-                        let mut vertices: Vec<Vertex> = Vec::new();
-                        let mut indices: Vec<u16> = Vec::new();
-
-                        let mut used_sources: Vec<&str> = Vec::new();
-                        let mut remote_h: Option<u64> = None;
-
-                        if let Some(t) = &remote_text {
-                            if let Ok(all_geom_remote) = serde_json::from_str::<AllGeometryData>(t) {
-                                for m in &all_geom_remote.meshes { append_mesh_as_triangles(m, [0.8,0.8,0.8], &mut vertices, &mut indices); }
-                                remote_h = Some(fnv1a64(t.as_bytes()));
-                                used_sources.push("remote");
-                            } else {
-                                web_sys::console::warn_1(&"Poll: failed to parse remote JSON".into());
-                            }
+                    let previous = SOURCE_TEXTS.with(|t| t.borrow().clone());
+                    let mut texts: Vec<(String, String)> = Vec::new();
+                    for source in config.active_sources() {
+                        let fetched = if source.watch { fetch_text(&source.path).await } else { None };
+                        // Keep the last content of unwatched sources and of failed fetches
+                        let text = fetched.or_else(|| previous.iter().find(|(path, _)| *path == source.path).map(|(_, text)| text.clone()));
+                        if let Some(text) = text {
+                            texts.push((source.path.clone(), text));
                         }
+                    }
 
-                        if let Some(t) = &local_text {
-                            let h = fnv1a64(t.as_bytes());
-                            if remote_h != Some(h) {
-                                if let Ok(all_geom_local) = serde_json::from_str::<AllGeometryData>(t) {
-                                    for m in &all_geom_local.meshes { append_mesh_as_triangles(m, [0.8,0.8,0.8], &mut vertices, &mut indices); }
-                                    used_sources.push("local");
-                                } else {
-                                    web_sys::console::warn_1(&"Poll: failed to parse local JSON".into());
-                                }
-                            } else {
-                                web_sys::console::log_1(&"Poll: skipping local (duplicate of remote)".into());
-                            }
-                        }
-
-                        if used_sources.is_empty() {
-                            let json_str = include_str!("openmodel/all_geometry.json");
-                            let all_geom: AllGeometryData = serde_json::from_str(json_str).unwrap_or(AllGeometryData {
-                                points: vec![], vectors: vec![], lines: vec![], planes: vec![], colors: vec![],
-                                point_clouds: vec![], line_clouds: vec![], plines: vec![], xforms: vec![], meshes: vec![],
-                            });
-                            for m in &all_geom.meshes { append_mesh_as_triangles(m, [0.8,0.8,0.8], &mut vertices, &mut indices); }
-                            used_sources.push("embedded");
-                        }
-
-                        // Always add procedural grid and axis once
-                        for (m, color) in make_grid_and_axis_meshes() { append_mesh_as_triangles(&m, color, &mut vertices, &mut indices); }
-
-                        PENDING_GEOMETRY.with(|p| *p.borrow_mut() = Some((vertices, indices)));
-                        web_sys::console::log_1(&format!("Geometry changed; sources: {}", used_sources.join("+")).into());
+                    if texts != previous {
+                        let sources = sources::parse_sources(&texts, config.uses_embedded_fallback());
+                        let names: Vec<&str> = texts.iter().map(|(path, _)| path.as_str()).collect();
+                        web_sys::console::log_1(&format!("Geometry changed; sources: {}", names.join(" + ")).into());
+                        SOURCE_TEXTS.with(|t| *t.borrow_mut() = texts);
+                        PENDING_GEOMETRY.with(|p| *p.borrow_mut() = Some(sources));
                     }
                     REMOTE_FETCHING.with(|f| f.set(false));
                 });
@@ -664,8 +482,8 @@ impl State{
 
             // Apply any pending geometry prepared by the async task
             let pending = PENDING_GEOMETRY.with(|p| p.borrow_mut().take());
-            if let Some((vertices, indices)) = pending {
-                self.replace_geometry(&vertices, &indices);
+            if let Some(sources) = pending {
+                self.replace_geometry(sources);
             }
         }
     }
@@ -678,6 +496,8 @@ impl State{
             self.config.width = width;
             self.config.height = height;
             self.surface.configure(&self.device, &self.config);
+            // The depth attachment must always match the surface size
+            self.renderer.resize(&self.device, width, height);
             // Keep camera projection in sync with the surface size (important on Web)
             self.camera.aspect = self.config.width as f32 / self.config.height as f32;
             self.is_surface_configured = true;
@@ -699,6 +519,25 @@ impl State{
                 self.camera_controller.process_scroll(delta);
                 true
             }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = *position;
+                false
+            }
+            // Left click picks, unless the mouse moved noticeably between press and release
+            WindowEvent::MouseInput { button: MouseButton::Left, state, .. } => {
+                match state {
+                    ElementState::Pressed => self.left_press_position = Some(self.cursor_position),
+                    ElementState::Released => {
+                        if let Some(pressed) = self.left_press_position.take() {
+                            let (dx, dy) = (self.cursor_position.x - pressed.x, self.cursor_position.y - pressed.y);
+                            if dx * dx + dy * dy <= PICK_CLICK_TOLERANCE_PX * PICK_CLICK_TOLERANCE_PX {
+                                self.pick_at(self.cursor_position);
+                            }
+                        }
+                    }
+                }
+                true
+            }
             WindowEvent::MouseInput { button, state, .. } => {
                 // Handle right mouse for general mouse_pressed tracking
                 if *button == MouseButton::Right {
@@ -711,19 +550,165 @@ impl State{
         }
     }
 
+    // Select the object under a window position and report it to the pick callback.
+    // Clicks on the view gizmo turn the camera to that view instead, and with a measuring tool
+    // the click measures
+    fn pick_at(&mut self, position: PhysicalPosition<f64>) {
+        let (width, height) = (self.config.width as f32, self.config.height as f32);
+        let cursor = [position.x as f32, position.y as f32];
+        if let Some(view) = self.renderer.gizmo_view_at(&self.camera, cursor) {
+            self.camera.set_view(view);
+            log::info!("View: {:?}", view);
+            return;
+        }
+        let ndc_x = cursor[0] / width * 2.0 - 1.0;
+        let ndc_y = 1.0 - cursor[1] / height * 2.0;
+        let ray = self.camera.ray_through(ndc_x, ndc_y);
+        let view_proj = self.camera.build_view_projection_matrix();
+        let clip_planes = self.renderer.clip_planes();
+        let hit = self.renderer.scene().pick(&ray, &view_proj, [width, height], cursor, &clip_planes);
+        if self.renderer.measurements().tool().is_some() {
+            let target = hit.as_ref().and_then(|hit| {
+                let model = self.renderer.scene().get(&hit.key)?.model;
+                let object = self.source_object(&hit.key)?;
+                Some(MeasureTarget::new(hit, object, model, view_proj, [width, height], cursor, &clip_planes))
+            });
+            self.measure(target);
+            return;
+        }
+        self.renderer.scene_mut().set_selected(hit.as_ref().map(|h| h.key.as_str()));
+
+        let result = hit.as_ref().and_then(|hit| self.source_object(&hit.key).map(|object| PickResult::new(hit, object)));
+
+        match &mut self.pick_callback {
+            Some(callback) => callback(result.as_ref()),
+            None => match &result {
+                Some(r) => log::info!("Picked {} '{}' ({}) face {:?} vertex {:?} element {:?}", r.dtype, r.name, r.guid, r.face_key, r.vertex_key, r.element_index),
+                None => log::info!("Picked nothing"),
+            },
+        }
+    }
+
+    // Resolve a scene key back to its openmodel object, by the position the scene recorded at sync time.
+    // sync_scene() passes the streamed objects as the source after the loaded ones.
+    fn source_object(&self, key: &str) -> Option<GeometryObject<'_>> {
+        let [source, index] = self.renderer.scene().get(key)?.source?;
+        let geometry = match self.sources.get(source) {
+            Some(geometry) => geometry,
+            None if source == self.sources.len() => &self.streamed,
+            None => return None,
+        };
+        GeometryObject::all(geometry).into_iter().nth(index)
+    }
+
+    // Take a click with the measuring tool; None is a click on empty space
+    fn measure(&mut self, target: Option<MeasureTarget>) {
+        if let Some(target) = &target {
+            log::debug!("Snapped to {:?} at {:?}", target.snap.kind, target.snap.position);
+        }
+        let count = self.renderer.measurements().measurements().len();
+        let changed = match self.renderer.measurements_mut().click(target) {
+            Some(measurement) => {
+                log::info!("{}", measurement.name());
+                true
+            }
+            // Taking away the last face of an area removes it
+            None => self.renderer.measurements().measurements().len() != count,
+        };
+        if changed {
+            self.report_measurements();
+        }
+    }
+
+    // Hand the measurements to the measure callback
+    fn report_measurements(&mut self) {
+        if let Some(callback) = &mut self.measure_callback {
+            callback(self.renderer.measurements());
+        }
+    }
+
+    // Save the measurements as openmodel lines and points, named after their values
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_measurements(&self) {
+        const PATH: &str = "measurements.json";
+        let geometry = self.renderer.measurements().to_geometry();
+        let written = serde_json::to_string_pretty(&geometry).map_err(anyhow::Error::from).and_then(|json| Ok(std::fs::write(PATH, json)?));
+        match written {
+            Ok(()) => log::info!("Measurements saved to {}", PATH),
+            Err(err) => log::error!("Unable to save the measurements: {:#}", err),
+        }
+    }
+
+    // Fit the camera to all loaded geometry
+    fn frame_all(&mut self) {
+        if let Some((min, max)) = self.renderer.scene().bounds() {
+            self.camera.frame_bounds(min, max);
+        }
+    }
+
+    // Fit the camera to the selected object, or to everything when nothing is selected
+    fn frame_selected(&mut self) {
+        let scene = self.renderer.scene();
+        match scene.selected().and_then(|key| scene.object_bounds(key)) {
+            Some((min, max)) => self.camera.frame_bounds(min, max),
+            None => self.frame_all(),
+        }
+    }
+
+    // Toggle a section box around the selected object, slightly larger so its own faces are kept
+    fn toggle_section_box(&mut self) {
+        if self.renderer.section_box().is_some() {
+            self.renderer.set_section_box(None);
+            log::info!("Section box: off");
+            return;
+        }
+        let scene = self.renderer.scene();
+        match scene.selected().and_then(|key| scene.object_bounds(key)) {
+            Some((min, max)) => {
+                let margin = (max - min).map(|d| d * 0.01 + 1e-3);
+                self.renderer.set_section_box(Some((min - margin, max + margin)));
+                log::info!("Section box: on");
+            }
+            None => log::info!("Select an object to put a section box around it"),
+        }
+    }
+
+    // Changing the attribute rebuilds the meshes that carry it
+    fn set_attribute_coloring(&mut self, coloring: Option<AttributeColoring>) {
+        self.renderer.set_attribute_coloring(coloring);
+        self.sync_scene();
+    }
+
+    // Color by the next attribute of the loaded meshes; after the last one, back to their own colors
+    fn cycle_attribute_coloring(&mut self) {
+        let mut names = attribute_names(&self.sources);
+        names.extend(attribute_names(std::slice::from_ref(&self.streamed)));
+        names.sort();
+        names.dedup();
+        let current = self.renderer.attribute_coloring().cloned();
+        let next = match current.as_ref().and_then(|c| names.iter().position(|(source, name)| *source == c.source && *name == c.attribute)) {
+            Some(i) => names.get(i + 1),
+            None => names.first(),
+        };
+        let colormap = current.map_or_else(Colormap::default, |c| c.colormap);
+        let coloring = next.map(|(source, name)| AttributeColoring { colormap, ..AttributeColoring::new(*source, name) });
+        match &coloring {
+            Some(c) => log::info!("Color by {:?} attribute '{}'", c.source, c.attribute),
+            None if names.is_empty() => log::info!("No mesh attributes to color by"),
+            None => log::info!("Color by attribute: off"),
+        }
+        self.set_attribute_coloring(coloring);
+    }
+
     fn update(&mut self) {
-        // Poll for geometry changes periodically and hot-reload buffers if needed
+        // Web: poll for geometry changes periodically and hot-reload buffers if needed
+        #[cfg(target_arch = "wasm32")]
         self.poll_geometry_changes();
         let now = Instant::now();
         let dt = now - self.last_render_time;
         self.last_render_time = now;
         self.camera_controller.update_camera(&mut self.camera, dt);
-        self.camera_uniform.update_view_proj(&self.camera);
-        self.queue.write_buffer(
-            &self.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[self.camera_uniform]),
-        );
+        self.renderer.update_camera(&self.queue, &self.camera);
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -742,65 +727,8 @@ impl State{
         // We need to do this because we want to control how the rende code interacts with the texture.
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        // We also need a CommandEncoder to create the actual commands to send to GPU.
-        // Most modern graphics frameworks expect commands to to be stored in a command buffer before sending to GPU.
-        // The encoder builds a command buffer that we can then send to the GPU.
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
-        });
-
-        // Clearing the screen.
-        // We need to use the encoder to create a RenderPass.
-        // The RenderPass has all the methods for the actual drawing.
-        // The render method via shaders will draw the geometry.
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.9,
-                            g: 0.9,
-                            b: 0.9,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-        
-        
-            // We set the the pipeline on the render_pass using the one we created for shader.
-            if self.use_color_pipeline {
-                render_pass.set_pipeline(&self.render_pipeline_color);
-            } else {
-                render_pass.set_pipeline(&self.render_pipeline_solid);
-            }
-
-            // Set the camera bind group (pipeline expects it even if shaders don't use it)
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-
-            // Set the vertex buffer otherwise the app will crash.
-            // First arguement is the buffer slot index
-            // Second argument allows us to specifiy which portion of buffer to use, .. is entire buffer.
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-
-            // You can only have one index buffer set at a time.
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16); // 1.
-
-            // When using an index buffer, we need to use draw_indexed instead of draw.
-            // First argument is the range of indices to draw.
-            // Second argument is the base vertex.
-            // Third argument is the instance count.
-            render_pass.draw_indexed(0..(self.index_buffer.size() / std::mem::size_of::<u16>() as u64) as u32, 0, 0..1);
-        
-        }
-        self.queue.submit(iter::once(encoder.finish()));
+        // Draw the scene into the surface texture
+        self.renderer.render(&self.device, &self.queue, &view);
         output.present();
     
         Ok(())
@@ -811,10 +739,88 @@ impl State{
     // Handle key events.
     // Escape - to exit the app
     // Space - to change the shader in the render pipeline
+    // L - to cycle the shading mode (flat, smooth, unlit)
+    // Z - to cycle the display mode (shaded, shaded with edges, wireframe)
+    // B - to cycle the edge filter (all, boundary, feature edges)
+    // X - to toggle x-ray (ghosted objects with hidden edges, the selection stays solid)
+    // K - to toggle a section box around the selected object
+    // N - to toggle the object name labels
+    // M - to cycle the measuring tool (distance, angle, area, off); Backspace - to undo the last
+    // measurement, Delete - to remove all, Enter - to save them to measurements.json
+    // G - to toggle the overlay (grid, axes and view gizmo)
+    // H - to toggle the sun with its shadows
+    // P - to cycle the post-processing (ambient occlusion, silhouettes, both, off)
+    // T - to cycle the mesh attribute the model is colored by (with a legend), then off
+    // Home - to frame all geometry, F - to frame the selected object
     fn handle_key(&mut self, event_loop: &ActiveEventLoop, code: KeyCode, is_pressed: bool) {
         match (code, is_pressed) {
             (KeyCode::Escape, true) => event_loop.exit(),
-            (KeyCode::Space, true) => self.use_color_pipeline = !self.use_color_pipeline,
+            (KeyCode::Space, true) => self.renderer.set_use_color_pipeline(!self.renderer.use_color_pipeline()),
+            (KeyCode::KeyL, true) => {
+                let mode = self.renderer.shading_mode().next();
+                self.renderer.set_shading_mode(mode);
+                log::info!("Shading mode: {:?}", mode);
+            }
+            (KeyCode::KeyZ, true) => {
+                let mode = self.renderer.display_mode().next();
+                self.renderer.set_display_mode(mode);
+                log::info!("Display mode: {:?}", mode);
+            }
+            (KeyCode::KeyX, true) => {
+                let xray = !self.renderer.xray();
+                self.renderer.set_xray(xray);
+                log::info!("X-ray: {}", if xray { "on" } else { "off" });
+            }
+            (KeyCode::KeyB, true) => {
+                let filter = self.renderer.edge_filter().next();
+                self.renderer.set_edge_filter(filter);
+                log::info!("Edge filter: {:?}", filter);
+            }
+            (KeyCode::KeyK, true) => self.toggle_section_box(),
+            (KeyCode::KeyT, true) => self.cycle_attribute_coloring(),
+            (KeyCode::KeyG, true) => {
+                let mut overlay = self.renderer.overlay();
+                overlay.visible = !overlay.visible;
+                self.renderer.set_overlay(overlay);
+                log::info!("Overlay: {}", if overlay.visible { "on" } else { "off" });
+            }
+            (KeyCode::KeyH, true) => {
+                let mut sun = self.renderer.sun();
+                sun.enabled = !sun.enabled;
+                self.renderer.set_sun(sun);
+                log::info!("Sun: {}", if sun.enabled { "on" } else { "off" });
+            }
+            (KeyCode::KeyP, true) => {
+                let post = self.renderer.post().next();
+                self.renderer.set_post(post);
+                log::info!("Ambient occlusion: {}, silhouettes: {}", if post.ambient_occlusion { "on" } else { "off" }, if post.silhouettes { "on" } else { "off" });
+            }
+            (KeyCode::KeyN, true) => {
+                let mut labels = self.renderer.labels();
+                labels.names = !labels.names;
+                self.renderer.set_labels(labels);
+                log::info!("Object names: {}", if labels.names { "on" } else { "off" });
+            }
+            (KeyCode::KeyM, true) => {
+                let tool = MeasureTool::next(self.renderer.measurements().tool());
+                self.renderer.measurements_mut().set_tool(tool);
+                match tool {
+                    Some(tool) => log::info!("Measure: {:?}", tool),
+                    None => log::info!("Measure: off"),
+                }
+            }
+            (KeyCode::Backspace, true) => {
+                self.renderer.measurements_mut().undo();
+                self.report_measurements();
+            }
+            (KeyCode::Delete, true) => {
+                self.renderer.measurements_mut().clear();
+                self.report_measurements();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            (KeyCode::Enter, true) => self.export_measurements(),
+            (KeyCode::Home, true) => self.frame_all(),
+            (KeyCode::KeyF, true) => self.frame_selected(),
             _ => {}
         }
     }
@@ -824,33 +830,58 @@ impl State{
 // We need to tell winit how to use it, for this an App is created
 ////////////////////////////////////////////////////////////////////////////////////////////
 pub struct App {
-    #[cfg(target_arch = "wasm32")]
-    proxy: Option<winit::event_loop::EventLoopProxy<State>>,
+    proxy: Option<EventLoopProxy<UserEvent>>,
     state: Option<State>,
-    vertices: Vec<Vertex>, // User geometry
-    indices: Vec<u16>, // User geometry
+    sources: Vec<AllGeometryData>, // User geometry
+    config: Config, // Geometry sources to watch (native) or to load and poll (web)
+    #[cfg(not(target_arch = "wasm32"))]
+    loader: Option<sources::Loader>, // Loader of the initial sources, handed to the watcher
+    pick_callback: Option<PickCallback>, // Handed to the State once it exists
+    pending_events: Vec<UserEvent>, // Stream and API events received before the State exists
 }
 
 impl App {
     pub fn new(
-        #[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>,
-        vertices: Vec<Vertex>, // User geometry
-        indices: Vec<u16>, // User geometry
+        event_loop: &EventLoop<UserEvent>,
+        sources: Vec<AllGeometryData>, // User geometry
     ) -> Self {
-        #[cfg(target_arch = "wasm32")]
         let proxy = Some(event_loop.create_proxy());
         Self {
             state: None,
-            vertices, // User geometry
-            indices, // User geometry
-            #[cfg(target_arch = "wasm32")]
+            sources, // User geometry
+            config: Config::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            loader: None,
+            pick_callback: None,
+            pending_events: Vec::new(),
             proxy,
         }
     }
+
+    // Receive the picked object (or None when a click hits nothing) instead of logging it
+    pub fn with_pick_callback(mut self, callback: PickCallback) -> Self {
+        self.pick_callback = Some(callback);
+        self
+    }
+
+    // Sources to reload from; on web they are also loaded once the canvas exists
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    // The loader that produced the initial sources. The watcher keeps its URL contents,
+    // so it does not fetch and reload everything again right after startup.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_loader(mut self, loader: sources::Loader) -> Self {
+        self.config = loader.config().clone();
+        self.loader = Some(loader);
+        self
+    }
 }
 
 // This gives a variety of functions: key press, mouse movements, lifecycle events.
-impl ApplicationHandler<State> for App {
+impl ApplicationHandler<UserEvent> for App {
 
     // Define attributes about the window including web attributes
     // We use those attributes to create the window
@@ -881,7 +912,22 @@ impl ApplicationHandler<State> for App {
         {
             // If we are not on web we can use pollster to
             // await the 
-            self.state = Some(pollster::block_on(State::new(window, &self.vertices, &self.indices)).unwrap());
+            let mut state = pollster::block_on(State::new(window, std::mem::take(&mut self.sources), self.config.msaa)).unwrap();
+            state.pick_callback = self.pick_callback.take();
+            self.state = Some(state);
+
+            // Reload geometry on file edits and remote changes, without blocking frames
+            if let Some(proxy) = self.proxy.take() {
+                if let Some(url) = &self.config.stream {
+                    if let Err(err) = stream::spawn_stream_client(proxy.clone(), url.clone()) {
+                        log::error!("Unable to start the stream client: {:#}", err);
+                    }
+                }
+                let loader = self.loader.take().unwrap_or_else(|| sources::Loader::new(self.config.clone()));
+                if let Err(err) = watcher::spawn_geometry_watcher(proxy, loader) {
+                    log::error!("Unable to start the geometry watcher: {:#}", err);
+                }
+            }
         }
 
         #[cfg(target_arch = "wasm32")]
@@ -889,15 +935,20 @@ impl ApplicationHandler<State> for App {
             // Run the future asynchronously and use the
             // proxy to send the results to the event loop
             if let Some(proxy) = self.proxy.take() {
+                let config = self.config.clone();
                 wasm_bindgen_futures::spawn_local(async move {
-                    // Build geometry on WASM (embedded + grid/axis + remote RAW JSON)
-                    let (vertices, indices) = get_geometry().await;
+                    // Build geometry on WASM from the configured sources (grid and axes are drawn by the overlay)
+                    let sources = load_geometry(&config).await;
+                    let mut state = State::new(window, sources, config.msaa)
+                        .await
+                        .expect("Unable to create canvas!!!");
+                    // Connect once the State exists, so no stream command arrives before it
+                    if let Some(url) = config.stream.clone() {
+                        stream::connect_web(proxy.clone(), url);
+                    }
+                    state.source_config = config;
                     assert!(proxy
-                        .send_event(
-                            State::new(window, &vertices, &indices)
-                                .await
-                                .expect("Unable to create canvas!!!")
-                        )
+                        .send_event(UserEvent::StateReady(Box::new(state)))
                         .is_ok())
                 });
             }
@@ -906,18 +957,39 @@ impl ApplicationHandler<State> for App {
 
     // This servers as a landing point four our State future. 
     // Resumed isnt aync so we need to offload the future and send the results somewhere
-    #[allow(unused_mut)]
-    fn user_event(&mut self, _event_loop: &ActiveEventLoop, mut event: State) {
+    // Reloaded geometry from the watcher thread arrives here too.
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: UserEvent) {
         // This is where proxy.send_event() ends up
-        #[cfg(target_arch = "wasm32")]
-        {
-            event.window.request_redraw();
-            event.resize(
-                event.window.inner_size().width,
-                event.window.inner_size().height,
-            );
+        match event {
+            #[cfg(target_arch = "wasm32")]
+            UserEvent::StateReady(mut state) => {
+                state.window.request_redraw();
+                state.resize(
+                    state.window.inner_size().width,
+                    state.window.inner_size().height,
+                );
+                if self.pick_callback.is_some() {
+                    state.pick_callback = self.pick_callback.take();
+                }
+                self.state = Some(*state);
+                for event in std::mem::take(&mut self.pending_events) {
+                    self.user_event(_event_loop, event);
+                }
+            }
+            UserEvent::GeometryLoaded(sources) => {
+                if let Some(state) = &mut self.state {
+                    state.replace_geometry(sources);
+                }
+            }
+            UserEvent::Stream(message) => match &mut self.state {
+                Some(state) => state.apply_stream(*message),
+                None => self.pending_events.push(UserEvent::Stream(message)),
+            },
+            UserEvent::Command(command) => match &mut self.state {
+                Some(state) => state.apply_command(command),
+                None => self.pending_events.push(UserEvent::Command(command)),
+            },
         }
-        self.state = Some(event);
     }
 
     // Windows event
@@ -977,13 +1049,10 @@ impl ApplicationHandler<State> for App {
         event: winit::event::DeviceEvent,
     ) {
         if let Some(state) = &mut self.state {
-            match event {
-                winit::event::DeviceEvent::MouseMotion { delta } => {
-                    if state.mouse_pressed {
-                        state.camera_controller.process_mouse(delta.0, delta.1);
-                    }
+            if let winit::event::DeviceEvent::MouseMotion { delta } = event {
+                if state.mouse_pressed {
+                    state.camera_controller.process_mouse(delta.0, delta.1);
                 }
-                _ => {}
             }
         }
     }
@@ -994,6 +1063,20 @@ impl ApplicationHandler<State> for App {
 // This function sets up the logger as well as creates the event loop and our app
 // THen runs our app to completeion
 pub fn run() -> anyhow::Result<()> {
+    run_app(Config::default(), None)
+}
+
+// Same as run(), but loads and watches the given sources (see sources::Config::from_args)
+pub fn run_with_config(config: Config) -> anyhow::Result<()> {
+    run_app(config, None)
+}
+
+// Same as run(), but picked objects are reported to the callback
+pub fn run_with_pick_callback(callback: PickCallback) -> anyhow::Result<()> {
+    run_app(Config::default(), Some(callback))
+}
+
+fn run_app(config: Config, pick_callback: Option<PickCallback>) -> anyhow::Result<()> {
 
     #[cfg(not(target_arch = "wasm32"))]
     {
@@ -1006,23 +1089,30 @@ pub fn run() -> anyhow::Result<()> {
 
     let event_loop = EventLoop::with_user_event().build()?;
 
+    // The JavaScript API sends its commands through this proxy
+    #[cfg(target_arch = "wasm32")]
+    web::set_proxy(event_loop.create_proxy());
+
 
 
     #[cfg(not(target_arch = "wasm32"))]
-    let (vertices, indices) = get_geometry();
+    let mut loader = sources::Loader::new(config);
 
     #[cfg(not(target_arch = "wasm32"))]
     let mut app = App::new(
-        vertices,
-        indices,
-    );
+        &event_loop,
+        loader.load(),
+    ).with_loader(loader);
 
     #[cfg(target_arch = "wasm32")]
     let mut app = App::new(
         &event_loop,
         Vec::new(),
-        Vec::new(),
-    );
+    ).with_config(config);
+
+    if let Some(callback) = pick_callback {
+        app = app.with_pick_callback(callback);
+    }
     event_loop.run_app(&mut app)?;
 
     Ok(())
@@ -1035,132 +1125,33 @@ pub fn run() -> anyhow::Result<()> {
 #[wasm_bindgen(start)]
 pub fn run_web() -> Result<(), wasm_bindgen::JsValue> {
     console_error_panic_hook::set_once();
-    run().unwrap_throw();
+    // Sources from the page URL, e.g. ?source=/geometry/a.json&source=https://...&poll=500
+    let search = web_sys::window().and_then(|w| w.location().search().ok()).unwrap_or_default();
+    run_with_config(Config::from_query(&search)).unwrap_throw();
 
     Ok(())
 }
 
 
-// Geometry: load JSON meshes, add grid + Z-axis pipes, convert to buffers
+// Geometry: load the default sources (local or embedded, then remote)
 #[cfg(not(target_arch = "wasm32"))]
-pub fn get_geometry() -> (Vec<Vertex>, Vec<u16>) {
-    // 1) Load JSON geometry from disk if available (fast mtime check), fallback to embedded
-    let json_str = std::fs::read_to_string(LOCAL_GEOMETRY_PATH)
-        .unwrap_or_else(|_| include_str!("openmodel/all_geometry.json").to_string());
-    let local_or_embedded_hash = fnv1a64(json_str.as_bytes());
-    let all_geom: AllGeometryData = serde_json::from_str(&json_str).unwrap_or(AllGeometryData {
-        points: vec![],
-        vectors: vec![],
-        lines: vec![],
-        planes: vec![],
-        colors: vec![],
-        point_clouds: vec![],
-        line_clouds: vec![],
-        plines: vec![],
-        xforms: vec![],
-        meshes: vec![],
-    });
-
-    // 2) Aggregate meshes: loaded + procedural grid/axis
-    let mut vertices: Vec<Vertex> = Vec::new();
-    let mut indices: Vec<u16> = Vec::new();
-
-    for m in &all_geom.meshes {
-        append_mesh_as_triangles(m, [0.8, 0.8, 0.8], &mut vertices, &mut indices);
-    }
-    for (m, color) in make_grid_and_axis_meshes() {
-        append_mesh_as_triangles(&m, color, &mut vertices, &mut indices);
-    }
-
-    // 3) Try to fetch and merge remote JSON (native build)
-    match reqwest::blocking::get(REMOTE_GEOMETRY_URL) {
-        Ok(resp) if resp.status().is_success() => {
-            match resp.text() {
-                Ok(text) => {
-                    let remote_hash = fnv1a64(text.as_bytes());
-                    if remote_hash != local_or_embedded_hash {
-                        match serde_json::from_str::<AllGeometryData>(&text) {
-                            Ok(all_geom_remote) => {
-                                for m in &all_geom_remote.meshes {
-                                    append_mesh_as_triangles(m, [0.8, 0.8, 0.8], &mut vertices, &mut indices);
-                                }
-                                log::info!(
-                                    "Merged remote JSON meshes (native): {}",
-                                    all_geom_remote.meshes.len()
-                                );
-                            }
-                            Err(err) => log::warn!("Failed to parse remote JSON (native): {}", err),
-                        }
-                    } else {
-                        log::info!("Skipping remote (duplicate of local/embedded) (native)");
-                    }
-                },
-                Err(err) => log::warn!("Failed reading remote response (native): {}", err),
-            }
-        }
-        Ok(resp) => {
-            log::warn!("Remote fetch returned status {} (native)", resp.status());
-        }
-        Err(err) => {
-            log::warn!("Remote JSON fetch failed (native): {}", err);
-        }
-    }
-
-    (vertices, indices)
+pub fn get_geometry() -> Vec<AllGeometryData> {
+    sources::Loader::new(Config::default()).load()
 }
 
-// WASM: fetch remote RAW JSON and merge with embedded + procedural meshes
+// WASM: fetch the default sources (local-served and remote RAW JSON), fallback to embedded
 #[cfg(target_arch = "wasm32")]
-pub async fn get_geometry() -> (Vec<Vertex>, Vec<u16>) {
-    // Build geometry by merging remote and local, avoiding duplicates via content hash; fallback to embedded.
-    let mut vertices: Vec<Vertex> = Vec::new();
-    let mut indices: Vec<u16> = Vec::new();
-
-    let local_text = fetch_text(LOCAL_GEOMETRY_HTTP_PATH).await;
-    let remote_text = fetch_text(REMOTE_GEOMETRY_URL).await;
-
-    let mut used_sources: Vec<&str> = Vec::new();
-    let mut remote_h: Option<u64> = None;
-
-    if let Some(t) = &remote_text {
-        match serde_json::from_str::<AllGeometryData>(t) {
-            Ok(g) => {
-                for m in &g.meshes { append_mesh_as_triangles(m, [0.8,0.8,0.8], &mut vertices, &mut indices); }
-                remote_h = Some(fnv1a64(t.as_bytes()));
-                used_sources.push("remote");
-            }
-            Err(err) => web_sys::console::warn_1(&format!("Initial: failed to parse remote JSON: {}", err).into()),
-        }
-    }
-
-    if let Some(t) = &local_text {
-        let h = fnv1a64(t.as_bytes());
-        if remote_h != Some(h) {
-            match serde_json::from_str::<AllGeometryData>(t) {
-                Ok(g) => {
-                    for m in &g.meshes { append_mesh_as_triangles(m, [0.8,0.8,0.8], &mut vertices, &mut indices); }
-                    used_sources.push("local");
-                }
-                Err(err) => web_sys::console::warn_1(&format!("Initial: failed to parse local JSON: {}", err).into()),
-            }
-        } else {
-            web_sys::console::log_1(&format!("Initial: skipping local (duplicate of remote)").into());
-        }
-    }
-
-    if used_sources.is_empty() {
-        let json_str = include_str!("openmodel/all_geometry.json");
-        let all_geom: AllGeometryData = serde_json::from_str(json_str).unwrap_or(AllGeometryData {
-            points: vec![], vectors: vec![], lines: vec![], planes: vec![], colors: vec![],
-            point_clouds: vec![], line_clouds: vec![], plines: vec![], xforms: vec![], meshes: vec![],
-        });
-        for m in &all_geom.meshes { append_mesh_as_triangles(m, [0.8,0.8,0.8], &mut vertices, &mut indices); }
-        used_sources.push("embedded");
-    }
-
-    for (m, color) in make_grid_and_axis_meshes() { append_mesh_as_triangles(&m, color, &mut vertices, &mut indices); }
-
-    web_sys::console::log_1(&format!("Initial geometry sources: {}", used_sources.join("+")).into());
+pub async fn get_geometry() -> Vec<AllGeometryData> {
+    load_geometry(&Config::default()).await
+}
 
-    (vertices, indices)
+// WASM: fetch every configured source and remember the texts for change polling
+#[cfg(target_arch = "wasm32")]
+pub async fn load_geometry(config: &Config) -> Vec<AllGeometryData> {
+    let texts = sources::fetch_source_texts(config).await;
+    let names: Vec<&str> = texts.iter().map(|(path, _)| path.as_str()).collect();
+    web_sys::console::log_1(&format!("Initial geometry sources: {}", names.join(" + ")).into());
+    let sources = sources::parse_sources(&texts, config.uses_embedded_fallback());
+    SOURCE_TEXTS.with(|t| *t.borrow_mut() = texts);
+    sources
 }
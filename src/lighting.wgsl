@@ -0,0 +1,105 @@
+// Lighting shared by the triangle pipelines.
+// Prepended to shader_solid.wgsl, shader_color.wgsl, shader_point.wgsl and shader_edge.wgsl, so `camera` comes from those files.
+
+// Shading mode, edge filter and feature angle, see ShadingUniform in shading.rs
+struct ShadingUniform {
+    modes: vec4<u32>, // x: ShadingMode, y: EdgeFilter
+    angles: vec4<f32>, // x: feature angle in degrees
+}
+@group(0) @binding(8)
+var<uniform> shading: ShadingUniform;
+
+const SHADING_FLAT: u32 = 0u;
+const SHADING_SMOOTH: u32 = 1u;
+const SHADING_UNLIT: u32 = 2u;
+
+// Hemispheric ambient: sky color from +Z, ground color from -Z
+const SKY_COLOR: vec3<f32> = vec3<f32>(0.55, 0.58, 0.62);
+const GROUND_COLOR: vec3<f32> = vec3<f32>(0.25, 0.23, 0.21);
+// Headlight: a directional light located at the eye
+const HEADLIGHT_STRENGTH: f32 = 0.7;
+// With the sun on the headlight only fills in the sides the sun leaves dark
+const HEADLIGHT_FILL: f32 = 0.15;
+
+// The sun, its shadow map and the ground plane, see SunUniform in shadow.rs
+struct SunUniform {
+    view_proj: mat4x4<f32>, // World to shadow map
+    direction: vec4<f32>, // Towards the sun, w its strength (0 off)
+    shadow: vec4<f32>, // x: 1 when the shadow map is valid
+    ground: vec4<f32>, // Ground plane center xy, height and half size (w: 0 off)
+}
+@group(0) @binding(7)
+var<uniform> sun: SunUniform;
+
+// Sun shadow map and its comparison sampler
+@group(0) @binding(3)
+var shadow_map: texture_depth_2d;
+@group(0) @binding(4)
+var shadow_sampler: sampler_comparison;
+
+// Shadow map depth offsets against self shadowing, larger on surfaces grazed by the sun
+const SHADOW_BIAS: f32 = 0.0015;
+const SHADOW_SLOPE_BIAS: f32 = 0.004;
+
+// Fraction of the sun reaching a point: 3x3 percentage closer filtering of the shadow map.
+// n_dot_l is the cosine between the surface normal and the direction towards the sun.
+fn sun_visibility(world_position: vec3<f32>, n_dot_l: f32) -> f32 {
+    if (sun.shadow.x < 0.5) {
+        return 1.0;
+    }
+    // Orthographic: no perspective divide
+    let light = (sun.view_proj * vec4<f32>(world_position, 1.0)).xyz;
+    let uv = vec2<f32>(light.x * 0.5 + 0.5, 0.5 - light.y * 0.5);
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+        return 1.0;
+    }
+    // The map is fitted to the model: receivers beyond its far side are behind every caster
+    let depth = min(light.z, 1.0) - SHADOW_BIAS - SHADOW_SLOPE_BIAS * (1.0 - clamp(n_dot_l, 0.0, 1.0));
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            // Explicit level: no derivatives, so this is fine after branching
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + vec2<f32>(f32(x), f32(y)) * texel, depth);
+        }
+    }
+    return lit / 9.0;
+}
+
+fn shade(color: vec3<f32>, world_position: vec3<f32>, vertex_normal: vec3<f32>) -> vec3<f32> {
+    let mode = shading.modes.x;
+    if (mode == SHADING_UNLIT) {
+        return color;
+    }
+
+    // Face normal from screen-space derivatives; also used when a vertex has no normal
+    let face_normal = normalize(cross(dpdx(world_position), dpdy(world_position)));
+    var n = face_normal;
+    if (mode == SHADING_SMOOTH && length(vertex_normal) > 1e-6) {
+        n = normalize(vertex_normal);
+    }
+
+    // Two-sided lighting: always light the side facing the viewer
+    let to_eye = normalize(camera.eye.xyz - world_position);
+    if (dot(n, to_eye) < 0.0) {
+        n = -n;
+    }
+
+    let ambient = mix(GROUND_COLOR, SKY_COLOR, 0.5 + 0.5 * n.z);
+    let sun_on = sun.direction.w > 0.0;
+    var diffuse = select(HEADLIGHT_STRENGTH, HEADLIGHT_FILL, sun_on) * max(dot(n, to_eye), 0.0);
+    if (sun_on) {
+        let n_dot_l = dot(n, normalize(sun.direction.xyz));
+        if (n_dot_l > 0.0) {
+            diffuse += sun.direction.w * n_dot_l * sun_visibility(world_position, n_dot_l);
+        }
+    }
+    return color * (ambient + vec3<f32>(diffuse));
+}
+
+// Selection highlight: tint towards orange
+const HIGHLIGHT_COLOR: vec3<f32> = vec3<f32>(1.0, 0.55, 0.0);
+
+fn apply_highlight(color: vec3<f32>, highlight: f32) -> vec3<f32> {
+    return mix(color, HIGHLIGHT_COLOR, 0.6 * highlight);
+}
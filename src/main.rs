@@ -1,6 +1,17 @@
-use wink::run;
+use wink::{run_with_config, sources::{Config, USAGE}};
 
 fn main() {
-
-    run().unwrap();
-}
\ No newline at end of file
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let config = match Config::from_args(args) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {:#}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    run_with_config(config).unwrap();
+}
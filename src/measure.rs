@@ -0,0 +1,549 @@
+// Measuring tools: the distance between two points, the angle at the middle one of three points
+// and the area of mesh faces. Clicks snap to the mesh vertices, edge midpoints and face centroids
+// near the cursor. Results are drawn over the model as dimension lines with labels, and can be
+// exported as openmodel lines and points named after their value.
+use bytemuck::Zeroable;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix3, Matrix4, Point3, SquareMatrix, Vector3};
+use openmodel::AllGeometryData;
+use openmodel::geometry::{Line, Mesh, Point};
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+use crate::colormap::format_value;
+use crate::geometry::GeometryObject;
+use crate::labels::{Label, LabelKind, LABEL_GAP};
+use crate::picking::PickHit;
+use crate::section::keeps;
+use crate::vertex::{PointInstance, Vertex};
+
+// Pixels from the cursor within which a click snaps to a vertex, edge midpoint or face centroid
+pub const SNAP_RADIUS: f32 = 12.0;
+// Side of the square markers at the measured points, in pixels
+const MARKER_SIZE: f32 = 7.0;
+// The angle arc: segments, and radius as a fraction of the shorter leg
+const ARC_SEGMENTS: usize = 24;
+const ARC_RADIUS: f32 = 0.25;
+
+// Linear colors of the lines and markers, and of the label text
+const LINE_COLOR: [f32; 4] = [1.0, 0.3, 0.0, 1.0];
+const LABEL_COLOR: [f32; 3] = [0.35, 0.08, 0.0];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MeasureTool {
+    Distance, // Two clicks
+    Angle, // Three clicks, the angle is at the second point
+    Area, // Clicks add mesh faces to the area, or take them away again
+}
+
+impl std::str::FromStr for MeasureTool {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "distance" => Ok(MeasureTool::Distance),
+            "angle" => Ok(MeasureTool::Angle),
+            "area" => Ok(MeasureTool::Area),
+            _ => Err(format!("unknown measure tool '{}', expected distance, angle or area", name)),
+        }
+    }
+}
+
+impl MeasureTool {
+    // The M key cycles distance, angle, area, then off
+    pub fn next(tool: Option<MeasureTool>) -> Option<MeasureTool> {
+        match tool {
+            None => Some(MeasureTool::Distance),
+            Some(MeasureTool::Distance) => Some(MeasureTool::Angle),
+            Some(MeasureTool::Angle) => Some(MeasureTool::Area),
+            Some(MeasureTool::Area) => None,
+        }
+    }
+}
+
+// What a click snapped to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapKind {
+    Vertex, // Mesh vertex
+    Midpoint, // Middle of a mesh edge or a line
+    Centroid, // Mesh face centroid
+    Point, // Point object, point cloud point or line end
+    Surface, // Nothing near the cursor: the hit itself
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Snap {
+    pub position: Point3<f32>, // World space
+    pub kind: SnapKind,
+}
+
+fn to_point3(p: &Point) -> Point3<f32> {
+    Point3::new(p.x as f32, p.y as f32, p.z as f32)
+}
+
+fn transform(model: &Matrix4<f32>, p: Point3<f32>) -> Point3<f32> {
+    Point3::from_homogeneous(model * p.to_homogeneous())
+}
+
+// Corners of a mesh face in mesh coordinates
+fn face_points(mesh: &Mesh, face: usize) -> Vec<Point3<f32>> {
+    mesh.face_vertices(face).into_iter().flatten().filter_map(|&v| mesh.vertex_position(v)).map(|p| to_point3(&p)).collect()
+}
+
+// Snap candidates of the element under the cursor, in world space
+fn snap_candidates(hit: &PickHit, object: &GeometryObject, model: &Matrix4<f32>) -> Vec<(SnapKind, Point3<f32>)> {
+    let ends = |a: Point3<f32>, b: Point3<f32>| vec![(SnapKind::Point, a), (SnapKind::Point, b), (SnapKind::Midpoint, a.midpoint(b))];
+    let line = |l: &Line| ends(Point3::new(l.x0 as f32, l.y0 as f32, l.z0 as f32), Point3::new(l.x1 as f32, l.y1 as f32, l.z1 as f32));
+    let local = match (object, hit.element, hit.point_index) {
+        (GeometryObject::Mesh(mesh, _), Some(face), _) => {
+            let corners = face_points(mesh, face);
+            let mut candidates: Vec<(SnapKind, Point3<f32>)> = corners.iter().map(|p| (SnapKind::Vertex, *p)).collect();
+            for (i, p) in corners.iter().enumerate() {
+                candidates.push((SnapKind::Midpoint, p.midpoint(corners[(i + 1) % corners.len()])));
+            }
+            if !corners.is_empty() {
+                candidates.push((SnapKind::Centroid, Point3::centroid(&corners)));
+            }
+            candidates
+        }
+        (GeometryObject::Point(point), ..) => vec![(SnapKind::Point, to_point3(point))],
+        (GeometryObject::PointCloud(cloud), _, Some(index)) => cloud.points.get(index).map(|p| vec![(SnapKind::Point, to_point3(p))]).unwrap_or_default(),
+        (GeometryObject::Line(l), ..) => line(l),
+        (GeometryObject::Pline(pline), Some(segment), _) => match pline.points.get(segment..segment + 2) {
+            Some([a, b]) => ends(to_point3(a), to_point3(b)),
+            _ => Vec::new(),
+        },
+        (GeometryObject::LineCloud(cloud), Some(index), _) => cloud.lines.get(index).map(line).unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    local.into_iter().map(|(kind, p)| (kind, transform(model, p))).collect()
+}
+
+// A mesh face taking part in an area measurement
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FaceArea {
+    pub key: String, // Scene key of the mesh, its guid (see Scene::sync)
+    pub face: usize,
+    pub area: f32, // In world units
+    pub outline: Vec<[f32; 3]>, // World-space corners
+}
+
+impl FaceArea {
+    // Mesh::face_area is in mesh coordinates: the linear part L of the model matrix scales it
+    // by |det L| |L^-T n| for the unit face normal n
+    pub fn new(key: &str, mesh: &Mesh, face: usize, model: &Matrix4<f32>) -> Option<Self> {
+        let area = mesh.face_area(face)? as f32;
+        let normal = mesh.face_normal(face)?;
+        let normal = Vector3::new(normal.x as f32, normal.y as f32, normal.z as f32);
+        let linear = Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate());
+        let scale = linear.invert().map_or(0.0, |inverse| linear.determinant().abs() * (inverse.transpose() * normal).magnitude());
+        let outline = face_points(mesh, face).into_iter().map(|p| transform(model, p).into()).collect();
+        Some(Self { key: key.to_string(), face, area: area * scale, outline })
+    }
+
+    pub fn centroid(&self) -> Point3<f32> {
+        let corners: Vec<Point3<f32>> = self.outline.iter().map(|p| Point3::from(*p)).collect();
+        Point3::centroid(&corners)
+    }
+}
+
+// What a click measures: the snapped point and, on a mesh, the face under the cursor
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeasureTarget {
+    pub snap: Snap,
+    pub face: Option<FaceArea>,
+}
+
+impl MeasureTarget {
+    // Snap to the candidate closest to the cursor on screen, within SNAP_RADIUS; point sprites
+    // always snap to their point. Candidates cut away by the clip planes are skipped.
+    pub fn new(hit: &PickHit, object: GeometryObject, model: Matrix4<f32>, view_proj: Matrix4<f32>, viewport: [f32; 2], cursor: [f32; 2], clip_planes: &[[f32; 4]]) -> Self {
+        let radius = if hit.point_index.is_some() { f32::INFINITY } else { SNAP_RADIUS };
+        let screen_distance = |p: Point3<f32>| {
+            let clip = view_proj * p.to_homogeneous();
+            let x = (clip.x / clip.w + 1.0) * 0.5 * viewport[0];
+            let y = (1.0 - clip.y / clip.w) * 0.5 * viewport[1];
+            (clip.w > 0.0).then(|| (x - cursor[0]).hypot(y - cursor[1]))
+        };
+        let nearest = snap_candidates(hit, &object, &model)
+            .into_iter()
+            .filter(|(_, p)| keeps(clip_planes, *p))
+            .filter_map(|(kind, p)| screen_distance(p).filter(|d| *d <= radius).map(|d| (d, kind, p)))
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        let snap = match nearest {
+            Some((_, kind, position)) => Snap { position, kind },
+            None => Snap { position: Point3::from(hit.position), kind: SnapKind::Surface },
+        };
+        let face = match object {
+            GeometryObject::Mesh(mesh, _) => hit.element.and_then(|face| FaceArea::new(&hit.key, mesh, face, &model)),
+            _ => None,
+        };
+        Self { snap, face }
+    }
+}
+
+// A finished measurement, as reported and exported
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Measurement {
+    Distance { points: [[f32; 3]; 2], distance: f32 },
+    Angle { points: [[f32; 3]; 3], degrees: f32 }, // At the middle point
+    Area { faces: Vec<FaceArea>, area: f32 },
+}
+
+impl Measurement {
+    pub fn distance(a: Point3<f32>, b: Point3<f32>) -> Self {
+        Measurement::Distance { points: [a.into(), b.into()], distance: (b - a).magnitude() }
+    }
+
+    // Zero when a leg has no length
+    pub fn angle(a: Point3<f32>, vertex: Point3<f32>, b: Point3<f32>) -> Self {
+        let (u, v) = (a - vertex, b - vertex);
+        let degrees = if u.magnitude2() > 0.0 && v.magnitude2() > 0.0 { u.angle(v).0.to_degrees() } else { 0.0 };
+        Measurement::Angle { points: [a.into(), vertex.into(), b.into()], degrees }
+    }
+
+    pub fn area(faces: Vec<FaceArea>) -> Self {
+        let area = faces.iter().map(|face| face.area).sum();
+        Measurement::Area { faces, area }
+    }
+
+    // Distance, degrees or area
+    pub fn value(&self) -> f32 {
+        match self {
+            Measurement::Distance { distance, .. } => *distance,
+            Measurement::Angle { degrees, .. } => *degrees,
+            Measurement::Area { area, .. } => *area,
+        }
+    }
+
+    // Label text
+    pub fn text(&self) -> String {
+        match self {
+            Measurement::Distance { distance, .. } => format_value(*distance),
+            Measurement::Angle { degrees, .. } => format!("{:.1}°", degrees),
+            Measurement::Area { area, .. } => format!("A = {}", format_value(*area)),
+        }
+    }
+
+    // Name of the exported objects, e.g. "Distance 2.5"
+    pub fn name(&self) -> String {
+        match self {
+            Measurement::Distance { distance, .. } => format!("Distance {}", format_value(*distance)),
+            Measurement::Angle { degrees, .. } => format!("Angle {:.1}°", degrees),
+            Measurement::Area { area, .. } => format!("Area {}", format_value(*area)),
+        }
+    }
+
+    // The measured lines: the distance, both legs of the angle, the outlines of the faces
+    pub fn segments(&self) -> Vec<[Point3<f32>; 2]> {
+        match self {
+            Measurement::Distance { points: [a, b], .. } => vec![[Point3::from(*a), Point3::from(*b)]],
+            Measurement::Angle { points: [a, vertex, b], .. } => vec![[Point3::from(*vertex), Point3::from(*a)], [Point3::from(*vertex), Point3::from(*b)]],
+            Measurement::Area { faces, .. } => faces
+                .iter()
+                .flat_map(|face| (0..face.outline.len()).map(|i| [Point3::from(face.outline[i]), Point3::from(face.outline[(i + 1) % face.outline.len()])]))
+                .collect(),
+        }
+    }
+
+    // The measured points, the centroid of the faces for areas
+    pub fn points(&self) -> Vec<Point3<f32>> {
+        match self {
+            Measurement::Distance { points, .. } => points.iter().map(|p| Point3::from(*p)).collect(),
+            Measurement::Angle { points, .. } => points.iter().map(|p| Point3::from(*p)).collect(),
+            Measurement::Area { .. } => vec![self.area_centroid()],
+        }
+    }
+
+    // Area weighted centroid of the faces
+    fn area_centroid(&self) -> Point3<f32> {
+        let Measurement::Area { faces, area } = self else { return Point3::origin() };
+        if *area <= 0.0 {
+            let centroids: Vec<Point3<f32>> = faces.iter().map(FaceArea::centroid).collect();
+            return Point3::centroid(&centroids);
+        }
+        Point3::from_vec(faces.iter().map(|face| face.centroid().to_vec() * face.area).sum::<Vector3<f32>>() / *area)
+    }
+
+    // Arc between the legs of an angle, None when the legs are degenerate
+    fn arc(&self) -> Option<Vec<Point3<f32>>> {
+        let Measurement::Angle { points: [a, vertex, b], .. } = self else { return None };
+        let (vertex, u, v) = (Point3::from(*vertex), Point3::from(*a) - Point3::from(*vertex), Point3::from(*b) - Point3::from(*vertex));
+        let radius = u.magnitude().min(v.magnitude()) * ARC_RADIUS;
+        let theta = u.angle(v).0;
+        if radius <= 0.0 || theta.sin() < 1e-4 {
+            return None;
+        }
+        let (u, v) = (u.normalize(), v.normalize());
+        // Spherical interpolation from one leg to the other
+        Some((0..=ARC_SEGMENTS).map(|i| {
+            let s = i as f32 / ARC_SEGMENTS as f32;
+            vertex + (u * ((1.0 - s) * theta).sin() + v * (s * theta).sin()) / theta.sin() * radius
+        }).collect())
+    }
+
+    // Distances above the middle of the line, angles at the middle of the arc, areas at their centroid
+    pub fn label(&self) -> Label {
+        let label = |position, align, offset| Label { kind: LabelKind::Measurement, text: self.text(), position, color: LABEL_COLOR, align, offset };
+        match self {
+            Measurement::Distance { points: [a, b], .. } => label(Point3::from(*a).midpoint(Point3::from(*b)), [0.5, 1.0], [0.0, -LABEL_GAP]),
+            Measurement::Angle { points: [_, vertex, _], .. } => {
+                let position = self.arc().map_or(Point3::from(*vertex), |arc| arc[ARC_SEGMENTS / 2]);
+                label(position, [0.0, 1.0], [LABEL_GAP, -LABEL_GAP])
+            }
+            Measurement::Area { .. } => label(self.area_centroid(), [0.5, 0.5], [0.0; 2]),
+        }
+    }
+}
+
+// Called with the measurements whenever they change
+pub type MeasureCallback = Box<dyn FnMut(&Measurements)>;
+
+// The measuring state of the viewer: the active tool, the points picked so far and the results
+#[derive(Clone, Debug, Default)]
+pub struct Measurements {
+    tool: Option<MeasureTool>,
+    picks: Vec<Snap>, // Points of the distance or angle being measured
+    open_area: bool, // The last measurement is an area still taking faces
+    measurements: Vec<Measurement>,
+}
+
+impl Measurements {
+    pub fn tool(&self) -> Option<MeasureTool> {
+        self.tool
+    }
+
+    // Switching tools drops the points of an unfinished measurement
+    pub fn set_tool(&mut self, tool: Option<MeasureTool>) {
+        self.tool = tool;
+        self.finish();
+    }
+
+    pub fn picks(&self) -> &[Snap] {
+        &self.picks
+    }
+
+    pub fn measurements(&self) -> &[Measurement] {
+        &self.measurements
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.measurements.is_empty() && self.picks.is_empty()
+    }
+
+    // Drop the picked points and end the area being measured
+    fn finish(&mut self) {
+        self.picks.clear();
+        self.open_area = false;
+    }
+
+    pub fn clear(&mut self) {
+        self.finish();
+        self.measurements.clear();
+    }
+
+    // Undo the points of the unfinished measurement or, without any, the last measurement
+    pub fn undo(&mut self) {
+        if self.picks.is_empty() {
+            self.measurements.pop();
+        }
+        self.finish();
+    }
+
+    // Measure with a click; None is a click on empty space, which ends the measurement in progress.
+    // Returns the measurement the click finished, or the area it changed.
+    pub fn click(&mut self, target: Option<MeasureTarget>) -> Option<&Measurement> {
+        let tool = self.tool?;
+        let Some(target) = target else {
+            self.finish();
+            return None;
+        };
+        match tool {
+            MeasureTool::Distance | MeasureTool::Angle => {
+                self.picks.push(target.snap);
+                let needed = if tool == MeasureTool::Distance { 2 } else { 3 };
+                if self.picks.len() < needed {
+                    return None;
+                }
+                let points: Vec<Point3<f32>> = self.picks.drain(..).map(|snap| snap.position).collect();
+                self.measurements.push(match points[..] {
+                    [a, b] => Measurement::distance(a, b),
+                    [a, vertex, b, ..] => Measurement::angle(a, vertex, b),
+                    _ => return None,
+                });
+            }
+            MeasureTool::Area => {
+                let face = target.face?;
+                let mut faces = match self.open_area.then(|| self.measurements.pop()).flatten() {
+                    Some(Measurement::Area { faces, .. }) => faces,
+                    _ => Vec::new(),
+                };
+                // A face picked again is taken away
+                match faces.iter().position(|f| f.key == face.key && f.face == face.face) {
+                    Some(index) => { faces.remove(index); }
+                    None => faces.push(face),
+                }
+                self.open_area = !faces.is_empty();
+                if faces.is_empty() {
+                    return None;
+                }
+                self.measurements.push(Measurement::area(faces));
+            }
+        }
+        self.measurements.last()
+    }
+
+    // One label per measurement, drawn by the label renderer
+    pub fn labels(&self) -> Vec<Label> {
+        self.measurements.iter().map(Measurement::label).collect()
+    }
+
+    // The measurements as openmodel objects named after their value: lines for the distances,
+    // the legs of the angles and the outlines of the areas, points for the measured points
+    pub fn to_geometry(&self) -> AllGeometryData {
+        let mut geometry = AllGeometryData::default();
+        for measurement in &self.measurements {
+            let name = measurement.name();
+            for [a, b] in measurement.segments() {
+                let (a, b) = (a.cast::<f64>().unwrap_or(Point3::origin()), b.cast::<f64>().unwrap_or(Point3::origin()));
+                geometry.lines.push(Line::with_name(name.clone(), a.x, a.y, a.z, b.x, b.y, b.z));
+            }
+            for p in measurement.points() {
+                geometry.points.push(Point::with_name(name.clone(), p.x as f64, p.y as f64, p.z as f64));
+            }
+        }
+        geometry
+    }
+}
+
+// Draws the measurements and the points picked so far: dimension lines and angle arcs, with a
+// square marker at every point. They go over everything, in the pass on the resolved target.
+pub struct MeasureLines {
+    line_pipeline: wgpu::RenderPipeline,
+    marker_pipeline: wgpu::RenderPipeline,
+    line_buffer: wgpu::Buffer, // Grows to the most vertices drawn so far
+    marker_buffer: wgpu::Buffer,
+    line_vertex_count: u32,
+    marker_count: u32,
+}
+
+// Write to the buffer, or replace it with a larger one
+fn upload<T: bytemuck::Pod + Zeroable>(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &mut wgpu::Buffer, items: &[T], label: &str) {
+    let size = std::mem::size_of_val(items) as wgpu::BufferAddress;
+    if size > buffer.size() {
+        let mut grown = items.to_vec();
+        grown.resize(items.len().next_power_of_two(), T::zeroed());
+        *buffer = create_buffer(device, &grown, label);
+    } else if size > 0 {
+        queue.write_buffer(buffer, 0, bytemuck::cast_slice(items));
+    }
+}
+
+fn create_buffer<T: bytemuck::Pod>(device: &wgpu::Device, items: &[T], label: &str) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(items),
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+impl MeasureLines {
+    pub fn new(device: &wgpu::Device, camera_bind_group_layout: &wgpu::BindGroupLayout, color_format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Measure Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader_measure.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Measure Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = |label: &str, entry: &str, buffers: &[wgpu::VertexBufferLayout], topology: wgpu::PrimitiveTopology| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(&format!("vs_{}", entry)),
+                    buffers,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(&format!("fs_{}", entry)),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState { topology, ..Default::default() },
+                // Not depth tested, so dimensions stay readable behind the model
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let line_pipeline = pipeline("Measure Line Pipeline", "line", &[Vertex::desc()], wgpu::PrimitiveTopology::LineList);
+        let marker_pipeline = pipeline("Measure Marker Pipeline", "marker", &[PointInstance::desc()], wgpu::PrimitiveTopology::TriangleList);
+
+        Self {
+            line_pipeline,
+            marker_pipeline,
+            line_buffer: create_buffer(device, &[Vertex::zeroed(); 64], "Measure Line Buffer"),
+            marker_buffer: create_buffer(device, &[PointInstance::zeroed(); 16], "Measure Marker Buffer"),
+            line_vertex_count: 0,
+            marker_count: 0,
+        }
+    }
+
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, measurements: &Measurements) {
+        let vertex = |p: Point3<f32>| Vertex { position: p.into(), color: LINE_COLOR, normal: [0.0; 3], value: [0.0; 2] };
+        let mut lines: Vec<Vertex> = Vec::new();
+        for measurement in measurements.measurements() {
+            lines.extend(measurement.segments().into_iter().flat_map(|[a, b]| [vertex(a), vertex(b)]));
+            if let Some(arc) = measurement.arc() {
+                lines.extend(arc.windows(2).flat_map(|pair| [vertex(pair[0]), vertex(pair[1])]));
+            }
+        }
+        // The points picked so far, joined in order
+        let picks: Vec<Point3<f32>> = measurements.picks().iter().map(|snap| snap.position).collect();
+        lines.extend(picks.windows(2).flat_map(|pair| [vertex(pair[0]), vertex(pair[1])]));
+
+        let markers: Vec<PointInstance> = measurements
+            .measurements()
+            .iter()
+            .flat_map(Measurement::points)
+            .chain(picks)
+            .map(|p| PointInstance { position: p.into(), color: LINE_COLOR, size: MARKER_SIZE })
+            .collect();
+
+        upload(device, queue, &mut self.line_buffer, &lines, "Measure Line Buffer");
+        upload(device, queue, &mut self.marker_buffer, &markers, "Measure Marker Buffer");
+        self.line_vertex_count = lines.len() as u32;
+        self.marker_count = markers.len() as u32;
+    }
+
+    pub fn draws(&self) -> bool {
+        self.line_vertex_count > 0 || self.marker_count > 0
+    }
+
+    // Lines, then markers over them, in a pass on the resolved target
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        if self.line_vertex_count > 0 {
+            render_pass.set_pipeline(&self.line_pipeline);
+            render_pass.set_vertex_buffer(0, self.line_buffer.slice(..));
+            render_pass.draw(0..self.line_vertex_count, 0..1);
+        }
+        if self.marker_count > 0 {
+            render_pass.set_pipeline(&self.marker_pipeline);
+            render_pass.set_vertex_buffer(0, self.marker_buffer.slice(..));
+            render_pass.draw(0..6, 0..self.marker_count);
+        }
+    }
+}
@@ -246,7 +246,9 @@ impl LineCloud {
     /// assert_eq!(lc.meshes.len(), 1);
     /// ```
     pub fn update_meshes(&mut self) -> &mut Self {
-        if !self.dirty {
+        // Meshes are not serialized, so a deserialized cloud has no meshes
+        // even though `dirty` defaults to false - rebuild in that case too
+        if !self.dirty && self.meshes.len() == self.lines.len() {
             return self;
         }
         
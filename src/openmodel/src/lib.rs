@@ -13,7 +13,9 @@ use common::{JsonSerializable, FromJsonData};
 use serde::{Serialize, Deserialize};
 
 // Comprehensive geometry data structure with all geometry types
-#[derive(Serialize, Deserialize, Debug)]
+// Missing lists deserialize as empty, so partial files and messages are valid
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
 pub struct AllGeometryData {
     pub points: Vec<Point>,
     pub vectors: Vec<Vector>,
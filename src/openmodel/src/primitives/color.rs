@@ -1,10 +1,10 @@
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use crate::common::{FromJsonData, Data, HasJsonData};
 use serde_json::Value;
 
 /// A color in RGBA format
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color {
     /// Red component (0-255)
     pub r: u8,
@@ -284,6 +284,19 @@ impl Serialize for Color {
     }
 }
 
+// Custom Deserialize implementation accepting both the COMPAS-style format
+// written by Serialize and the direct format
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        Color::from_json_data(&value)
+            .ok_or_else(|| serde::de::Error::custom("invalid Color data"))
+    }
+}
+
 // COMPAS-style JSON serialization support
 impl HasJsonData for Color {
     fn to_json_data(&self, minimal: bool) -> Value {
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign};
 use std::fmt;
@@ -7,7 +7,7 @@ use crate::common::json_serialization::{HasJsonData, FromJsonData};
 use crate::common::Data;
 
 /// A vector in 3D space with x, y, z components
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct Vector {
     /// The x component of the vector.
     pub x: f64,
@@ -29,6 +29,19 @@ impl Serialize for Vector {
     }
 }
 
+// Custom Deserialize implementation accepting both the COMPAS-style format
+// written by Serialize and the direct format
+impl<'de> Deserialize<'de> for Vector {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        Vector::from_json_data(&value)
+            .ok_or_else(|| serde::de::Error::custom("invalid Vector data"))
+    }
+}
+
 // Implement JSON serialization for Vector
 impl HasJsonData for Vector {
     fn to_json_data(&self, minimal: bool) -> Value {
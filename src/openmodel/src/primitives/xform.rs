@@ -1,6 +1,6 @@
 use crate::primitives::vector::Vector;
 use crate::geometry::point::Point;
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::ops::{Index, IndexMut, Mul, MulAssign};
 use std::fmt;
 use crate::common::{HasJsonData, FromJsonData, Data};
@@ -8,7 +8,7 @@ use serde_json::Value;
 
 /// A 4x4 transformation matrix in 3D space
 /// Stored in column-major order (standard in graphics)
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct Xform {
     /// The matrix elements stored in column-major order as a flattened array
     pub m: [f64; 16],
@@ -593,6 +593,19 @@ impl Serialize for Xform {
     }
 }
 
+// Custom Deserialize implementation accepting both the COMPAS-style format
+// written by Serialize and the direct format
+impl<'de> Deserialize<'de> for Xform {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        Xform::from_json_data(&value)
+            .ok_or_else(|| serde::de::Error::custom("invalid Xform data"))
+    }
+}
+
 // COMPAS-style JSON serialization support
 impl HasJsonData for Xform {
     fn to_json_data(&self, minimal: bool) -> Value {
@@ -0,0 +1,319 @@
+// Overlay drawn with the model: a construction grid on the XY plane, the world axes and a
+// view gizmo in the top right corner that snaps the camera to the standard views when clicked.
+// None of it is part of the scene, so it is never picked, framed or cut by section planes.
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+use crate::camera::{Camera, NamedView, OPENGL_TO_WGPU_MATRIX};
+use crate::picking::Ray;
+use crate::texture::Texture;
+use crate::vertex::Vertex;
+
+// View gizmo square in pixels and its distance from the top right corner
+pub const GIZMO_SIZE: f32 = 96.0;
+pub const GIZMO_MARGIN: f32 = 12.0;
+// Half width of the gizmo's orthographic view; the diagonal of the unit cube (1.73) fits
+const GIZMO_EXTENT: f32 = 1.8;
+
+// What the overlay shows; set with Renderer::set_overlay, the G key toggles `visible`
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlaySettings {
+    pub visible: bool, // The whole overlay
+    pub grid: bool,
+    pub axes: bool,
+    pub gizmo: bool,
+    pub minor_spacing: f32, // Grid line spacing in model units
+    pub major_spacing: f32,
+    pub adaptive: bool, // Grow both spacings by 10x while minor cells are too small on screen
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self { visible: true, grid: true, axes: true, gizmo: true, minor_spacing: 1.0, major_spacing: 10.0, adaptive: true }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct OverlayUniform {
+    inv_view_proj: [[f32; 4]; 4],
+    gizmo_view_proj: [[f32; 4]; 4],
+    gizmo_eye: [f32; 4], // w unused
+    grid: [f32; 4], // x: minor spacing, y: major spacing, z: fade distance, w: 1 when adaptive
+}
+
+// Gizmo square (x, y, width, height) in pixels from the top left of a viewport
+pub fn gizmo_rect(viewport: [f32; 2]) -> [f32; 4] {
+    [viewport[0] - GIZMO_SIZE - GIZMO_MARGIN, GIZMO_MARGIN, GIZMO_SIZE, GIZMO_SIZE]
+}
+
+// The unit cube seen from the camera direction, without the camera position and zoom
+pub fn gizmo_view_proj(camera: &Camera) -> Matrix4<f32> {
+    let eye = Point3::origin() + (camera.position - camera.target).normalize() * 3.0;
+    let view = Matrix4::look_at_rh(eye, Point3::origin(), camera.up);
+    let e = GIZMO_EXTENT;
+    OPENGL_TO_WGPU_MATRIX * cgmath::ortho(-e, e, -e, e, 0.0, 6.0) * view
+}
+
+// Standard view of the gizmo face under the cursor (pixels from the top left), if any
+pub fn gizmo_view_at(camera: &Camera, viewport: [f32; 2], cursor: [f32; 2]) -> Option<NamedView> {
+    let [x, y, width, height] = gizmo_rect(viewport);
+    let ndc_x = (cursor[0] - x) / width * 2.0 - 1.0;
+    let ndc_y = 1.0 - (cursor[1] - y) / height * 2.0;
+    if ndc_x.abs() > 1.0 || ndc_y.abs() > 1.0 {
+        return None;
+    }
+    let inverse = gizmo_view_proj(camera).invert()?;
+    let unproject = |z: f32| Point3::from_homogeneous(inverse * Vector4::new(ndc_x, ndc_y, z, 1.0));
+    let near = unproject(0.0);
+    let ray = Ray { origin: near, direction: unproject(1.0) - near };
+
+    // Entry point into the cube [-1, 1]^3; its largest coordinate names the face
+    let (mut t_min, mut t_max) = (0.0f32, 1.0f32);
+    for axis in 0..3 {
+        let (o, d) = (ray.origin[axis], ray.direction[axis]);
+        if d.abs() < 1e-12 {
+            if o.abs() > 1.0 { return None; }
+            continue;
+        }
+        let (t0, t1) = ((-1.0 - o) / d, (1.0 - o) / d);
+        t_min = t_min.max(t0.min(t1));
+        t_max = t_max.min(t0.max(t1));
+    }
+    if t_min > t_max {
+        return None;
+    }
+    let hit = ray.at(t_min);
+    let axis = (0..3).max_by(|&a, &b| hit[a].abs().total_cmp(&hit[b].abs()))?;
+    Some(match (axis, hit[axis] > 0.0) {
+        (0, true) => NamedView::Right,
+        (0, false) => NamedView::Left,
+        (1, true) => NamedView::Back,
+        (1, false) => NamedView::Front,
+        (_, true) => NamedView::Top,
+        (_, false) => NamedView::Bottom,
+    })
+}
+
+// Axis colors, also used for the gizmo faces
+const X_COLOR: [f32; 3] = [0.85, 0.2, 0.2];
+const Y_COLOR: [f32; 3] = [0.2, 0.65, 0.2];
+const Z_COLOR: [f32; 3] = [0.2, 0.35, 0.9];
+
+// Unit segments through the origin along X, Y and Z, scaled in the shader
+fn axis_vertices() -> Vec<Vertex> {
+    [(Vector3::unit_x(), X_COLOR), (Vector3::unit_y(), Y_COLOR), (Vector3::unit_z(), Z_COLOR)]
+        .into_iter()
+        .flat_map(|(axis, [r, g, b])| {
+            [-axis, axis].map(|p| Vertex { position: p.into(), color: [r, g, b, 1.0], normal: [0.0; 3], value: [0.0; 2] })
+        })
+        .collect()
+}
+
+// Cube [-1, 1]^3 with counter-clockwise faces seen from outside, positive faces in the axis colors
+fn gizmo_vertices() -> Vec<Vertex> {
+    let (x, y, z) = (Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z());
+    let dark = |[r, g, b]: [f32; 3]| [r * 0.6, g * 0.6, b * 0.6];
+    // Normal and two tangents with u x v = normal
+    let faces = [
+        (x, y, z, X_COLOR), (-x, z, y, dark(X_COLOR)),
+        (y, z, x, Y_COLOR), (-y, x, z, dark(Y_COLOR)),
+        (z, x, y, Z_COLOR), (-z, y, x, dark(Z_COLOR)),
+    ];
+    faces
+        .into_iter()
+        .flat_map(|(n, u, v, [r, g, b])| {
+            let corners = [n - u - v, n + u - v, n + u + v, n - u + v];
+            [0, 1, 2, 0, 2, 3].map(|i| Vertex { position: corners[i].into(), color: [r, g, b, 1.0], normal: n.into(), value: [0.0; 2] })
+        })
+        .collect()
+}
+
+pub struct Overlay {
+    settings: OverlaySettings,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    grid_pipeline: wgpu::RenderPipeline,
+    axes_pipeline: wgpu::RenderPipeline,
+    gizmo_pipeline: wgpu::RenderPipeline, // Drawn into the resolved target, like the composite pass
+    axes_buffer: wgpu::Buffer,
+    gizmo_buffer: wgpu::Buffer,
+    gizmo_vertex_count: u32,
+}
+
+impl Overlay {
+    // The grid and the axes are drawn in the opaque pass (sample_count, depth tested),
+    // the gizmo afterwards into the single sampled target
+    pub fn new(device: &wgpu::Device, camera_bind_group_layout: &wgpu::BindGroupLayout, color_format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader_overlay.wgsl").into()),
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Overlay Buffer"),
+            size: std::mem::size_of::<OverlayUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("overlay_bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("overlay_bind_group"),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = |label: &str, entry: &str, buffers: &[wgpu::VertexBufferLayout], primitive: wgpu::PrimitiveState, in_scene: bool| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(&format!("vs_{}", entry)),
+                    buffers,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(&format!("fs_{}", entry)),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive,
+                // Hidden behind the model, without hiding anything drawn later
+                depth_stencil: in_scene.then(Texture::depth_read_only_state),
+                multisample: wgpu::MultisampleState {
+                    count: if in_scene { sample_count } else { 1 },
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+        let grid_pipeline = pipeline("Grid Pipeline", "grid", &[], wgpu::PrimitiveState::default(), true);
+        let axes_pipeline = pipeline(
+            "Axes Pipeline", "axes", &[Vertex::desc()],
+            wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::LineList, ..Default::default() },
+            true,
+        );
+        let gizmo_pipeline = pipeline(
+            "Gizmo Pipeline", "gizmo", &[Vertex::desc()],
+            // The cube is convex: culling the back faces replaces a depth buffer
+            wgpu::PrimitiveState { cull_mode: Some(wgpu::Face::Back), ..Default::default() },
+            false,
+        );
+
+        let axes_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Axes Vertex Buffer"),
+            contents: bytemuck::cast_slice(&axis_vertices()),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let gizmo = gizmo_vertices();
+        let gizmo_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Gizmo Vertex Buffer"),
+            contents: bytemuck::cast_slice(&gizmo),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        Self {
+            settings: OverlaySettings::default(),
+            uniform_buffer,
+            bind_group,
+            grid_pipeline,
+            axes_pipeline,
+            gizmo_pipeline,
+            axes_buffer,
+            gizmo_buffer,
+            gizmo_vertex_count: gizmo.len() as u32,
+        }
+    }
+
+    pub fn settings(&self) -> OverlaySettings {
+        self.settings
+    }
+
+    // Spacings are kept positive, a major spacing below the minor one is raised to it
+    pub fn set_settings(&mut self, settings: OverlaySettings) {
+        let minor_spacing = if settings.minor_spacing > 0.0 { settings.minor_spacing } else { 1.0 };
+        let major_spacing = settings.major_spacing.max(minor_spacing);
+        self.settings = OverlaySettings { minor_spacing, major_spacing, ..settings };
+    }
+
+    pub fn draws_gizmo(&self) -> bool {
+        self.settings.visible && self.settings.gizmo
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+        let s = &self.settings;
+        let uniform = OverlayUniform {
+            inv_view_proj: camera.build_view_projection_matrix().invert().unwrap_or(Matrix4::identity()).into(),
+            gizmo_view_proj: gizmo_view_proj(camera).into(),
+            gizmo_eye: (camera.position - camera.target).normalize().extend(0.0).into(),
+            // Faded out by the far plane distance, before the far plane cuts them off
+            grid: [s.minor_spacing, s.major_spacing, camera.zfar, if s.adaptive { 1.0 } else { 0.0 }],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // Grid and axes, after the opaque model in the same pass
+    pub fn draw_scene(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if !self.settings.visible {
+            return;
+        }
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        if self.settings.grid {
+            render_pass.set_pipeline(&self.grid_pipeline);
+            render_pass.draw(0..3, 0..1);
+        }
+        if self.settings.axes {
+            render_pass.set_pipeline(&self.axes_pipeline);
+            render_pass.set_vertex_buffer(0, self.axes_buffer.slice(..));
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+
+    // Gizmo in its corner of a viewport of the given size, in a pass on the resolved target
+    pub fn draw_gizmo(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup, viewport: [f32; 2]) {
+        if !self.draws_gizmo() {
+            return;
+        }
+        let [x, y, width, height] = gizmo_rect(viewport);
+        if x < 0.0 || y + height > viewport[1] {
+            return; // Viewport too small
+        }
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        render_pass.set_pipeline(&self.gizmo_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.gizmo_buffer.slice(..));
+        render_pass.draw(0..self.gizmo_vertex_count, 0..1);
+        render_pass.set_viewport(0.0, 0.0, viewport[0], viewport[1], 0.0, 1.0);
+    }
+}
@@ -0,0 +1,234 @@
+// CPU ray picking.
+// Every uploaded geometry keeps its positions and element keys next to the GPU buffers,
+// so a click can be resolved to an object guid and face/vertex key without reading back the GPU.
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3, Vector4};
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::geometry::{GeometryBuffers, GeometryObject};
+
+// Ray from the near to the far plane: points are origin + t * direction, t in [0, 1].
+// The direction is not normalized so t stays comparable after transforming the ray.
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    // Ray expressed in another space, e.g. object space with the inverse model matrix
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Ray {
+        Ray {
+            origin: Point3::from_homogeneous(matrix * self.origin.to_homogeneous()),
+            direction: (matrix * self.direction.extend(0.0)).truncate(),
+        }
+    }
+
+    pub fn at(&self, t: f32) -> Point3<f32> {
+        self.origin + self.direction * t
+    }
+
+    // Parameter of the point on the ray closest to p
+    pub fn closest_t(&self, p: Point3<f32>) -> f32 {
+        (p - self.origin).dot(self.direction) / self.direction.magnitude2()
+    }
+
+    // Range of t in [0, 1] kept by the clip planes (see section.rs), None when all of the ray is cut away
+    pub fn clip(&self, planes: &[[f32; 4]]) -> Option<(f32, f32)> {
+        let (mut t_min, mut t_max) = (0.0f32, 1.0f32);
+        for [a, b, c, d] in planes {
+            let normal = Vector3::new(*a, *b, *c);
+            // Signed distance along the ray: start + t * slope, kept where <= 0
+            let start = normal.dot(self.origin.to_vec()) + d;
+            let slope = normal.dot(self.direction);
+            if slope.abs() < 1e-12 {
+                if start > 0.0 { return None; }
+            } else if slope > 0.0 {
+                t_max = t_max.min(-start / slope);
+            } else {
+                t_min = t_min.max(-start / slope);
+            }
+        }
+        (t_min <= t_max).then_some((t_min, t_max))
+    }
+}
+
+// Axis aligned bounding box of a set of points, None when there are no points
+pub fn bounds_of(points: impl IntoIterator<Item = Point3<f32>>) -> Option<(Point3<f32>, Point3<f32>)> {
+    points.into_iter().fold(None, |bounds, p| {
+        Some(match bounds {
+            None => (p, p),
+            Some((min, max)) => (
+                Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+            ),
+        })
+    })
+}
+
+// Triangle hit in object space
+pub struct TriangleHit {
+    pub t: f32,
+    pub element: usize,    // Face key, segment or line index (see GeometryBuffers::triangle_elements)
+    pub vertex_key: usize, // Mesh vertex key of the triangle corner closest to the hit
+}
+
+// CPU copy of an uploaded geometry, used for picking and bounds
+pub struct PickGeometry {
+    positions: Vec<Point3<f32>>,
+    indices: Vec<u32>,
+    triangle_elements: Vec<usize>,
+    vertex_keys: Vec<usize>,
+    pub points: Vec<(Point3<f32>, f32)>, // Point sprite centers and diameters in pixels
+    pub bounds: Option<(Point3<f32>, Point3<f32>)>, // Object-space axis aligned bounding box
+}
+
+impl PickGeometry {
+    pub fn new(geometry: &GeometryBuffers) -> Self {
+        let positions: Vec<Point3<f32>> = geometry.vertices.iter().map(|v| Point3::from(v.position)).collect();
+        let points: Vec<(Point3<f32>, f32)> = geometry.points.iter().map(|p| (Point3::from(p.position), p.size)).collect();
+
+        let bounds = bounds_of(positions.iter().chain(points.iter().map(|(p, _)| p)).copied());
+
+        Self {
+            positions,
+            indices: geometry.indices.clone(),
+            triangle_elements: geometry.triangle_elements.clone(),
+            vertex_keys: geometry.vertex_keys.clone(),
+            points,
+            bounds,
+        }
+    }
+
+    // Slab test against the bounding box, so most objects are skipped without testing triangles
+    fn ray_hits_bounds(&self, ray: &Ray) -> bool {
+        let Some((min, max)) = self.bounds else { return false };
+        let (mut t_min, mut t_max) = (f32::NEG_INFINITY, f32::INFINITY);
+        for axis in 0..3 {
+            let (o, d) = (ray.origin[axis], ray.direction[axis]);
+            if d.abs() < 1e-12 {
+                if o < min[axis] || o > max[axis] { return false; }
+                continue;
+            }
+            let (t0, t1) = ((min[axis] - o) / d, (max[axis] - o) / d);
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+        }
+        t_max >= t_min.max(0.0)
+    }
+
+    // Closest triangle hit along an object-space ray (Moller-Trumbore)
+    pub fn intersect_triangles(&self, ray: &Ray) -> Option<TriangleHit> {
+        if self.indices.is_empty() || !self.ray_hits_bounds(ray) {
+            return None;
+        }
+        let mut best: Option<TriangleHit> = None;
+        for (triangle, corners) in self.indices.chunks_exact(3).enumerate() {
+            let [a, b, c] = [corners[0], corners[1], corners[2]].map(|i| self.positions[i as usize]);
+            let (e1, e2) = (b - a, c - a);
+            let p = ray.direction.cross(e2);
+            let det = e1.dot(p);
+            if det.abs() < 1e-12 { continue; }
+            let inv_det = 1.0 / det;
+            let s = ray.origin - a;
+            let u = s.dot(p) * inv_det;
+            if !(0.0..=1.0).contains(&u) { continue; }
+            let q = s.cross(e1);
+            let v = ray.direction.dot(q) * inv_det;
+            if v < 0.0 || u + v > 1.0 { continue; }
+            let t = e2.dot(q) * inv_det;
+            if t < 0.0 || best.as_ref().is_some_and(|hit| hit.t <= t) { continue; }
+
+            // Barycentric weights: the largest one belongs to the closest corner
+            let weights = [1.0 - u - v, u, v];
+            let corner = (0..3).max_by(|&i, &j| weights[i].total_cmp(&weights[j])).unwrap_or(0);
+            best = Some(TriangleHit {
+                t,
+                element: self.triangle_elements.get(triangle).copied().unwrap_or(0),
+                vertex_key: self.vertex_keys.get(corners[corner] as usize).copied().unwrap_or(0),
+            });
+        }
+        best
+    }
+
+    // Closest point sprite under the cursor; sprites have a fixed pixel size, so this is
+    // tested in screen space with the world-space model-view-projection of the object
+    pub fn intersect_points(&self, ray: &Ray, model: &Matrix4<f32>, view_proj: &Matrix4<f32>, viewport: [f32; 2], cursor: [f32; 2]) -> Option<(f32, usize)> {
+        let mut best: Option<(f32, usize)> = None;
+        for (index, (p, size)) in self.points.iter().enumerate() {
+            let world = model * p.to_homogeneous();
+            let clip: Vector4<f32> = view_proj * world;
+            if clip.w <= 0.0 { continue; }
+            let px = (clip.x / clip.w * 0.5 + 0.5) * viewport[0];
+            let py = (0.5 - clip.y / clip.w * 0.5) * viewport[1];
+            let radius = size * 0.5;
+            if (px - cursor[0]).powi(2) + (py - cursor[1]).powi(2) > radius * radius { continue; }
+            let t = ray.closest_t(Point3::from_homogeneous(world));
+            if best.is_none_or(|(best_t, _)| t < best_t) {
+                best = Some((t, index));
+            }
+        }
+        best
+    }
+}
+
+// Raw hit returned by Scene::pick
+#[derive(Clone, Debug)]
+pub struct PickHit {
+    pub key: String,
+    pub t: f32,
+    pub position: [f32; 3], // World-space hit position
+    pub element: Option<usize>,
+    pub vertex_key: Option<usize>,
+    pub point_index: Option<usize>,
+}
+
+// What the pick callback receives: the object under the cursor and the element that was hit
+#[derive(Clone, Debug, Serialize)]
+pub struct PickResult {
+    pub guid: String,
+    pub name: String,
+    pub dtype: String,
+    pub face_key: Option<usize>,      // Meshes: face under the cursor
+    pub vertex_key: Option<usize>,    // Meshes: closest vertex of the hit face
+    pub element_index: Option<usize>, // Point clouds: point, line clouds: line, polylines: segment
+    pub position: [f32; 3],
+    pub attributes: Value, // Data fields plus face/vertex attributes of meshes
+}
+
+// Called with Some(result) when an object is picked and None when the click hit nothing
+pub type PickCallback = Box<dyn FnMut(Option<&PickResult>)>;
+
+impl PickResult {
+    pub fn new(hit: &PickHit, object: GeometryObject) -> Self {
+        let data = object.data();
+        let mut attributes = json!({ "data": serde_json::to_value(data).unwrap_or_default() });
+        let (mut face_key, mut vertex_key, mut element_index) = (None, None, None);
+
+        match object {
+            GeometryObject::Mesh(mesh, _) => {
+                face_key = hit.element;
+                vertex_key = hit.vertex_key;
+                if let Some(face) = face_key.and_then(|f| mesh.facedata.get(&f)) {
+                    attributes["face"] = json!(face);
+                }
+                if let Some(vertex) = vertex_key.and_then(|v| mesh.vertex.get(&v)) {
+                    attributes["vertex"] = json!(vertex.attributes);
+                }
+            }
+            GeometryObject::PointCloud(_) => element_index = hit.point_index,
+            GeometryObject::Pline(_) | GeometryObject::LineCloud(_) => element_index = hit.element,
+            _ => {}
+        }
+
+        Self {
+            guid: data.guid().to_string(),
+            name: data.name().to_string(),
+            dtype: object.dtype().to_string(),
+            face_key,
+            vertex_key,
+            element_index,
+            position: hit.position,
+            attributes,
+        }
+    }
+}
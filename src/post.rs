@@ -0,0 +1,379 @@
+// Post-processing that brings back the depth cues flat colored models lose.
+// A prepass renders the depth and face normals of the opaque triangles into textures of their
+// own; screen-space ambient occlusion darkens creases and corners from them, and silhouette
+// outlines are drawn where the depth or the normals jump. The occlusion is rendered into an
+// intermediate texture and blurred, then both are composited over the resolved opaque image
+// before the transparent surfaces, see shader_post.wgsl.
+// Everything runs in fragment shaders on 8-bit and depth textures, so it works within the WebGL2
+// limits; devices with those limits get cheaper, half resolution ambient occlusion.
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix};
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+use crate::camera::Camera;
+use crate::scene::{DrawPass, Scene};
+use crate::shading::DEFAULT_FEATURE_ANGLE;
+use crate::texture::Texture;
+use crate::vertex::{InstanceRaw, Vertex};
+
+const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+// Ambient occlusion samples per pixel
+const FULL_SAMPLES: u32 = 16;
+const REDUCED_SAMPLES: u32 = 8;
+// Steps smaller than this fraction of their distance from the camera draw no outline
+const STEP_TOLERANCE: f32 = 0.004;
+
+// Which effects are on and how strong; set with Renderer::set_post, the P key cycles them
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostSettings {
+    pub ambient_occlusion: bool,
+    pub occlusion_radius: f32, // Reach of the occlusion as a fraction of the model size
+    pub occlusion_strength: f32, // 0 none, 1 black where fully enclosed
+    pub silhouettes: bool,
+    pub silhouette_color: [f32; 3],
+    pub crease_angle: f32, // Degrees between faces that draw an outline
+}
+
+impl Default for PostSettings {
+    fn default() -> Self {
+        Self {
+            ambient_occlusion: false,
+            occlusion_radius: 0.05,
+            occlusion_strength: 1.0,
+            silhouettes: false,
+            silhouette_color: [0.1, 0.1, 0.1],
+            crease_angle: DEFAULT_FEATURE_ANGLE,
+        }
+    }
+}
+
+impl PostSettings {
+    pub fn is_active(&self) -> bool {
+        self.ambient_occlusion || self.silhouettes
+    }
+
+    // Cycle off -> ambient occlusion -> silhouettes -> both -> off
+    pub fn next(self) -> Self {
+        let (ambient_occlusion, silhouettes) = match (self.ambient_occlusion, self.silhouettes) {
+            (false, false) => (true, false),
+            (true, false) => (false, true),
+            (false, true) => (true, true),
+            (true, true) => (false, false),
+        };
+        Self { ambient_occlusion, silhouettes, ..self }
+    }
+}
+
+// The WebGL2 limits (no compute shaders, requested by lib.rs on the web) mark the slower
+// devices: they get half resolution ambient occlusion with fewer samples
+pub fn reduced_quality(limits: &wgpu::Limits) -> bool {
+    limits.max_compute_workgroups_per_dimension == 0
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+    view_proj: [[f32; 4]; 4],
+    inv_view_proj: [[f32; 4]; 4],
+    eye: [f32; 4], // w unused
+    forward: [f32; 4], // Viewing direction (w unused)
+    size: [f32; 4], // xy: image size, zw: occlusion texture size
+    occlusion: [f32; 4], // x: radius in model units, y: strength, z: samples, w: 1 on
+    outline: [f32; 4], // rgb: color, w: 1 on
+    crease: [f32; 4], // x: cosine of the crease angle, y: step tolerance (zw unused)
+}
+
+// Intermediate textures, recreated on resize
+struct PostTargets {
+    depth: Texture,
+    normals: Texture,
+    occlusion: Texture,
+    blurred: Texture,
+    occlusion_size: [u32; 2],
+    read_blurred: wgpu::BindGroup, // Read by the occlusion and the composite pass
+    read_occlusion: wgpu::BindGroup, // Read by the blur
+}
+
+impl PostTargets {
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, uniform_buffer: &wgpu::Buffer, sampler: &wgpu::Sampler, width: u32, height: u32, reduced: bool) -> Self {
+        let depth = Texture::create_depth_texture(device, width, height, 1, "post_depth_texture");
+        let normals = Texture::create_color_texture(device, NORMAL_FORMAT, width, height, 1, "post_normal_texture");
+        let occlusion_size = if reduced { [width.div_ceil(2), height.div_ceil(2)] } else { [width, height] };
+        let [w, h] = occlusion_size;
+        let occlusion = Texture::create_color_texture(device, OCCLUSION_FORMAT, w, h, 1, "occlusion_texture");
+        let blurred = Texture::create_color_texture(device, OCCLUSION_FORMAT, w, h, 1, "blurred_occlusion_texture");
+        let bind_group = |occlusion: &Texture, label| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&depth.view) },
+                    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&normals.view) },
+                    wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&occlusion.view) },
+                    wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::Sampler(sampler) },
+                ],
+                label: Some(label),
+            })
+        };
+        let read_blurred = bind_group(&blurred, "post_bind_group");
+        let read_occlusion = bind_group(&occlusion, "blur_bind_group");
+        Self { depth, normals, occlusion, blurred, occlusion_size, read_blurred, read_occlusion }
+    }
+}
+
+pub struct PostProcess {
+    settings: PostSettings,
+    reduced: bool, // Half resolution occlusion with fewer samples
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    targets: PostTargets,
+    gbuffer_pipeline: wgpu::RenderPipeline, // Depth and normals prepass
+    occlusion_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline, // Onto the color target
+}
+
+impl PostProcess {
+    pub fn new(device: &wgpu::Device, camera_bind_group_layout: &wgpu::BindGroupLayout, color_format: wgpu::TextureFormat, width: u32, height: u32) -> Self {
+        let reduced = reduced_quality(&device.limits());
+        let uniform = PostUniform {
+            view_proj: Matrix4::identity().into(),
+            inv_view_proj: Matrix4::identity().into(),
+            eye: [0.0; 4],
+            forward: [0.0, 0.0, -1.0, 0.0],
+            size: [1.0; 4],
+            occlusion: [0.0; 4],
+            outline: [0.0; 4],
+            crease: [0.0; 4],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture { sample_type, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Depth read as plain floats: GLSL has no texel fetch from depth textures
+                texture_entry(1, wgpu::TextureSampleType::Float { filterable: false }),
+                texture_entry(2, wgpu::TextureSampleType::Float { filterable: false }),
+                texture_entry(3, wgpu::TextureSampleType::Float { filterable: true }),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("post_bind_group_layout"),
+        });
+        // Bilinear upsampling of the half resolution occlusion
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Occlusion Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let targets = PostTargets::new(device, &bind_group_layout, &uniform_buffer, &sampler, width, height, reduced);
+
+        let gbuffer_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("G-Buffer Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(include_str!("clipping.wgsl"), include_str!("transparency.wgsl"), include_str!("shader_gbuffer.wgsl")).into()),
+        });
+        let gbuffer_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("G-Buffer Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let gbuffer_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("G-Buffer Pipeline"),
+            layout: Some(&gbuffer_layout),
+            vertex: wgpu::VertexState {
+                module: &gbuffer_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &gbuffer_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: NORMAL_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(Texture::depth_stencil_state()),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader_post.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label: &str, entry: &str, format: wgpu::TextureFormat, blend: wgpu::BlendState| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry),
+                    targets: &[Some(wgpu::ColorTargetState { format, blend: Some(blend), write_mask: wgpu::ColorWrites::ALL })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let occlusion_pipeline = pipeline("Occlusion Pipeline", "fs_occlusion", OCCLUSION_FORMAT, wgpu::BlendState::REPLACE);
+        let blur_pipeline = pipeline("Occlusion Blur Pipeline", "fs_blur", OCCLUSION_FORMAT, wgpu::BlendState::REPLACE);
+        // color = outline * coverage + target * occlusion * (1 - coverage), the target alpha stays
+        let composite_blend = wgpu::BlendState {
+            color: wgpu::BlendComponent { src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::SrcAlpha, operation: wgpu::BlendOperation::Add },
+            alpha: wgpu::BlendComponent { src_factor: wgpu::BlendFactor::Zero, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add },
+        };
+        let composite_pipeline = pipeline("Post Composite Pipeline", "fs_composite", color_format, composite_blend);
+
+        Self {
+            settings: PostSettings::default(),
+            reduced,
+            uniform_buffer,
+            bind_group_layout,
+            sampler,
+            targets,
+            gbuffer_pipeline,
+            occlusion_pipeline,
+            blur_pipeline,
+            composite_pipeline,
+        }
+    }
+
+    pub fn settings(&self) -> PostSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: PostSettings) {
+        self.settings = settings;
+    }
+
+    // Whether the ambient occlusion runs at half resolution with fewer samples
+    pub fn reduced(&self) -> bool {
+        self.reduced
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = PostTargets::new(device, &self.bind_group_layout, &self.uniform_buffer, &self.sampler, width, height, self.reduced);
+    }
+
+    // Camera matrices, sizes and settings for the next frame; the occlusion radius follows the model size
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera, bounds: Option<(Point3<f32>, Point3<f32>)>) {
+        let model_size = bounds.map_or(1.0, |(min, max)| (max - min).magnitude());
+        let view_proj = camera.build_view_projection_matrix();
+        let [width, height] = [self.targets.depth.texture.width(), self.targets.depth.texture.height()];
+        let [occlusion_width, occlusion_height] = self.targets.occlusion_size;
+        let forward = (camera.target - camera.position).normalize();
+        let settings = &self.settings;
+        let samples = if self.reduced { REDUCED_SAMPLES } else { FULL_SAMPLES };
+        let on = |on: bool| if on { 1.0 } else { 0.0 };
+        let [r, g, b] = settings.silhouette_color;
+        let uniform = PostUniform {
+            view_proj: view_proj.into(),
+            inv_view_proj: view_proj.invert().unwrap_or_else(Matrix4::identity).into(),
+            eye: [camera.position.x, camera.position.y, camera.position.z, 1.0],
+            forward: [forward.x, forward.y, forward.z, 0.0],
+            size: [width as f32, height as f32, occlusion_width as f32, occlusion_height as f32],
+            occlusion: [settings.occlusion_radius * model_size, settings.occlusion_strength, samples as f32, on(settings.ambient_occlusion)],
+            outline: [r, g, b, on(settings.silhouettes)],
+            crease: [settings.crease_angle.to_radians().cos(), STEP_TOLERANCE, 0.0, 0.0],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // Prepass, occlusion and blur into the intermediate textures, then the composite over the
+    // opaque image in `view`. Nothing is drawn while both effects are off.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, camera_bind_group: &wgpu::BindGroup, scene: &Scene, skip_edged: bool) {
+        if !self.settings.is_active() {
+            return;
+        }
+        let targets = &self.targets;
+        let color_attachment = |view, load| {
+            Some(wgpu::RenderPassColorAttachment { view, resolve_target: None, ops: wgpu::Operations { load, store: wgpu::StoreOp::Store } })
+        };
+        let full_screen_pass = |encoder: &mut wgpu::CommandEncoder, label, view, load, pipeline, bind_group| {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[color_attachment(view, load)],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        };
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("G-Buffer Pass"),
+                color_attachments: &[color_attachment(&targets.normals.view, wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT))],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.gbuffer_pipeline);
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            scene.draw_triangles(&mut render_pass, DrawPass::Opaque, skip_edged);
+        }
+        if self.settings.ambient_occlusion {
+            let clear = wgpu::LoadOp::Clear(wgpu::Color::WHITE);
+            full_screen_pass(encoder, "Occlusion Pass", &targets.occlusion.view, clear, &self.occlusion_pipeline, &targets.read_blurred);
+            full_screen_pass(encoder, "Occlusion Blur Pass", &targets.blurred.view, clear, &self.blur_pipeline, &targets.read_occlusion);
+        }
+        full_screen_pass(encoder, "Post Composite Pass", view, wgpu::LoadOp::Load, &self.composite_pipeline, &targets.read_blurred);
+    }
+}
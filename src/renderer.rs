@@ -0,0 +1,1159 @@
+// Surface independent renderer.
+// Owns everything needed to draw the scene into a color texture view: pipelines, depth buffer,
+// camera uniform and per-object GPU buffers. The window (State) and the headless renderer
+// only differ in where the color target comes from.
+// A frame is a shadow map pass while the sun casts shadows, an opaque pass, the post-processing
+// of the opaque image while an effect is on, then, if anything is translucent, a weighted blended
+// order-independent transparency pass and a composite pass over the opaque image.
+// Adapters that cannot draw or blend the transparency buffers at the scene's sample count
+// (e.g. WebGL2 without float blending) alpha blend translucent surfaces in the opaque pass instead.
+use std::iter;
+use cgmath::Point3;
+use openmodel::AllGeometryData;
+use openmodel::geometry::Plane;
+use wgpu::util::DeviceExt;
+use crate::camera::{Camera, CameraUniform, NamedView};
+use crate::colormap::{AttributeColoring, AttributeColors};
+use crate::labels::{Annotation, LabelSettings, Labels};
+use crate::measure::{MeasureLines, Measurements};
+use crate::overlay::{gizmo_view_at, Overlay, OverlaySettings};
+use crate::picking::bounds_of;
+use crate::post::{PostProcess, PostSettings};
+use crate::scene::{DrawPass, Scene, SyncStats};
+use crate::section::{plane_equation, ClipUniform, Clipping};
+use crate::shadow::{Shadows, SunSettings};
+use crate::shading::{DisplayMode, EdgeFilter, ShadingMode, ShadingUniform, DEFAULT_FEATURE_ANGLE};
+use crate::texture::Texture;
+use crate::vertex::{EdgeVertex, InstanceRaw, PointInstance, Vertex};
+
+// Background color of every frame
+pub const CLEAR_COLOR: wgpu::Color = wgpu::Color { r: 0.9, g: 0.9, b: 0.9, a: 1.0 };
+
+// Transparency buffers: weighted color sums need a float format, revealage fits in 8 bits
+const ACCUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+// Whether both transparency buffers can be drawn into and blended at the scene's sample count
+pub fn supports_order_independent_transparency(adapter: &wgpu::Adapter, device: &wgpu::Device, sample_count: u32) -> bool {
+    [ACCUM_FORMAT, REVEALAGE_FORMAT]
+        .into_iter()
+        .all(|format| Texture::supports_attachment(adapter, device, format, sample_count, true))
+}
+
+// Targets of the transparent pass and the bind group the composite pass reads them with.
+// With MSAA the pass draws into the multisampled textures and resolves into the readable ones.
+struct TransparencyTargets {
+    accum: Texture,
+    revealage: Texture,
+    accum_msaa: Option<Texture>,
+    revealage_msaa: Option<Texture>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TransparencyTargets {
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, width: u32, height: u32, sample_count: u32) -> Self {
+        let accum = Texture::create_color_texture(device, ACCUM_FORMAT, width, height, 1, "accum_texture");
+        let revealage = Texture::create_color_texture(device, REVEALAGE_FORMAT, width, height, 1, "revealage_texture");
+        let multisampled = |format, label| (sample_count > 1).then(|| Texture::create_color_texture(device, format, width, height, sample_count, label));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&accum.view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&revealage.view) },
+            ],
+            label: Some("composite_bind_group"),
+        });
+        Self {
+            accum_msaa: multisampled(ACCUM_FORMAT, "accum_msaa_texture"),
+            revealage_msaa: multisampled(REVEALAGE_FORMAT, "revealage_msaa_texture"),
+            accum,
+            revealage,
+            bind_group,
+        }
+    }
+
+    // Color attachment that draws into `texture` (through its multisampled twin with MSAA)
+    fn attachment<'a>(texture: &'a Texture, msaa: &'a Option<Texture>, clear: wgpu::Color) -> wgpu::RenderPassColorAttachment<'a> {
+        let (view, resolve_target, store) = match msaa {
+            Some(msaa) => (&msaa.view, Some(&texture.view), wgpu::StoreOp::Discard),
+            None => (&texture.view, None, wgpu::StoreOp::Store),
+        };
+        wgpu::RenderPassColorAttachment { view, resolve_target, ops: wgpu::Operations { load: wgpu::LoadOp::Clear(clear), store } }
+    }
+}
+
+// Pipeline of the transparent pass: additive accumulation and multiplicative revealage,
+// depth tested against the opaque pass without writing.
+// With `blended` set it is the fallback without the transparency buffers instead:
+// fs_blended alpha blends into the color target of the opaque pass, in draw order.
+fn create_transparent_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+    buffers: &[wgpu::VertexBufferLayout],
+    sample_count: u32,
+    blended: Option<wgpu::TextureFormat>,
+) -> wgpu::RenderPipeline {
+    let accumulate = wgpu::BlendComponent { src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add };
+    let reveal = wgpu::BlendComponent { src_factor: wgpu::BlendFactor::Zero, dst_factor: wgpu::BlendFactor::OneMinusSrc, operation: wgpu::BlendOperation::Add };
+    let oit_targets = [
+        Some(wgpu::ColorTargetState {
+            format: ACCUM_FORMAT,
+            blend: Some(wgpu::BlendState { color: accumulate, alpha: accumulate }),
+            write_mask: wgpu::ColorWrites::ALL,
+        }),
+        Some(wgpu::ColorTargetState {
+            format: REVEALAGE_FORMAT,
+            blend: Some(wgpu::BlendState { color: reveal, alpha: reveal }),
+            write_mask: wgpu::ColorWrites::ALL,
+        }),
+    ];
+    let blended_target = blended.map(|format| wgpu::ColorTargetState {
+        format,
+        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+        write_mask: wgpu::ColorWrites::ALL,
+    });
+    let (entry_point, targets): (&str, &[Option<wgpu::ColorTargetState>]) = match blended_target {
+        Some(_) => ("fs_blended", std::slice::from_ref(&blended_target)),
+        None => ("fs_transparent", &oit_targets),
+    };
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module,
+            entry_point: Some("vs_main"),
+            buffers,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module,
+            entry_point: Some(entry_point),
+            targets,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None, // Back faces show through the front ones
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(Texture::depth_read_only_state()),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+pub struct Renderer {
+    // Shader pipelines
+    render_pipeline_solid: wgpu::RenderPipeline, // First pipeline (one color)
+    render_pipeline_color: wgpu::RenderPipeline, // Second pipeline (vertex colors)
+    render_pipeline_point: wgpu::RenderPipeline, // Screen-space point sprites
+    render_pipeline_edge: wgpu::RenderPipeline,  // Mesh edges as lines
+    render_pipeline_edge_xray: wgpu::RenderPipeline, // Mesh edges without depth test, drawn over x-ray
+    // Transparent pass counterparts of the solid, color and point pipelines
+    render_pipeline_solid_transparent: wgpu::RenderPipeline,
+    render_pipeline_color_transparent: wgpu::RenderPipeline,
+    render_pipeline_point_transparent: wgpu::RenderPipeline,
+    render_pipeline_composite: wgpu::RenderPipeline, // Blends the transparent pass over the opaque image
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    transparency: Option<TransparencyTargets>, // Recreated on resize, None blends translucent surfaces in the opaque pass
+    overlay: Overlay, // Grid, axes and view gizmo
+    attribute_colors: AttributeColors, // Colormap and legend of the color by attribute display
+    labels: Labels, // Names, vertex keys, values and annotations
+    measurements: Measurements, // Measuring tool, picked points and results
+    measure_lines: MeasureLines, // Dimension lines and markers of the measurements
+    shadows: Shadows, // Sun, shadow map and ground plane
+    post: PostProcess, // Ambient occlusion and silhouettes, intermediate textures recreated on resize
+    use_color_pipeline: bool,                    // Whether to use the second pipeline
+    shading_mode: ShadingMode,                   // Flat, smooth or unlit triangles
+    display_mode: DisplayMode,                   // Faces, faces with edges or edges only
+    edge_filter: EdgeFilter,                     // All, boundary or feature edges
+    feature_angle: f32,                          // Crease angle of EdgeFilter::Feature in degrees
+    clipping: Clipping,                          // Clip planes, section box and cap fill
+    clip_uniform: ClipUniform,                   // Active planes and fill, written with the camera uniform
+    clip_buffer: wgpu::Buffer,
+    shading_buffer: wgpu::Buffer,                // ShadingUniform, written with the camera uniform
+    color_format: wgpu::TextureFormat, // Format of the target passed to render()
+    sample_count: u32, // MSAA samples per pixel, 1 draws straight into the target
+    msaa_texture: Option<Texture>, // Multisampled color attachment resolved into the target, recreated on resize
+    depth_texture: Texture, // Depth attachment, recreated on resize
+    scene: Scene, // One set of GPU buffers per object, keyed by Data guid
+    camera_uniform: CameraUniform,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+}
+
+impl Renderer {
+    // Create pipelines for a color target of the given format and size.
+    // sample_count must be supported by the adapter, see Texture::supported_sample_count().
+    // The scene starts empty, fill it with set_geometry().
+    pub async fn new(adapter: &wgpu::Adapter, device: &wgpu::Device, color_format: wgpu::TextureFormat, width: u32, height: u32, sample_count: u32) -> Self {
+        ////////////////////////////////////////////////////////////////////////////////////////////////////////////
+        // SHADERS
+        ////////////////////////////////////////////////////////////////////////////////////////////////////////////
+        // Debug: capture validation errors during pipeline/build steps (especially useful on Web)
+        // Push one scope before creating pipelines; we'll pop it after both are created.
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        // Pipeline. We will have to load shaders, as the render pipeline require them.
+        let shader_solid = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Solid Shader"),
+            // lighting.wgsl and transparency.wgsl are prepended so the shaders share shade() and transparent_output()
+            source: wgpu::ShaderSource::Wgsl(concat!(include_str!("lighting.wgsl"), include_str!("clipping.wgsl"), include_str!("colormap.wgsl"), include_str!("transparency.wgsl"), include_str!("shader_solid.wgsl")).into()),
+        });
+
+        let shader_color = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Color Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(include_str!("lighting.wgsl"), include_str!("clipping.wgsl"), include_str!("colormap.wgsl"), include_str!("transparency.wgsl"), include_str!("shader_color.wgsl")).into()),
+        });
+
+        let shader_point = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Point Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(include_str!("lighting.wgsl"), include_str!("clipping.wgsl"), include_str!("transparency.wgsl"), include_str!("shader_point.wgsl")).into()),
+        });
+
+        let shader_edge = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Edge Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(include_str!("lighting.wgsl"), include_str!("clipping.wgsl"), include_str!("shader_edge.wgsl")).into()),
+        });
+
+        let shader_composite = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Composite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader_composite.wgsl").into()),
+        });
+
+        // Pipeline layout - testing camera bind group step by step
+        let camera_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        // The fragment stage reads the eye position and shading mode for lighting
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Colormap of the attribute coloring and its sampler, see colormap.wgsl
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    // Shadow map of the sun and its comparison sampler, see lighting.wgsl
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
+                    // Clip planes and section fill, see section.rs and clipping.wgsl
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        // Point sprites are clipped per point in the vertex stage
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Range of the colormap, see colormap.rs
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Sun and ground plane, see shadow.rs
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 7,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Shading mode, edge filter and feature angle, see shading.rs
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 8,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("camera_bind_group_layout"),
+            });
+
+        let render_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[&camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // The scene owns the per-object model uniform layout used by the point pipeline
+        let scene = Scene::new(device);
+        let point_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Point Pipeline Layout"),
+            bind_group_layouts: &[&camera_bind_group_layout, scene.model_bind_group_layout()],
+            push_constant_ranges: &[],
+        });
+
+        // Pipeline for rendering
+        let render_pipeline_solid = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Solid Pipeline"),
+            layout: Some(&render_pipeline_layout),
+
+            vertex: wgpu::VertexState {
+                module: &shader_solid, // <-- Change the shader
+                entry_point: Some("vs_main"), // 1. vertex entry point
+                buffers: &[
+                    Vertex::desc(), // The implementation of the vertex struct
+                    InstanceRaw::desc(), // Per-instance model matrices (slot 1)
+                ], // 2. tells wgpu that type of vetices we want to pass to vertex shader
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+
+            fragment: Some(wgpu::FragmentState { // 3. This is optional so we wrap to Some(), we need it for colors
+                module: &shader_solid,  // <-- Change the shader
+                entry_point: Some("fs_main"), // 1. fragment entry point
+                targets: &[Some(wgpu::ColorTargetState { // 4. tells wgpu what color outputs it should set up
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+
+            // The primitive field describes how to interpret our vertices when converting them into triangles.
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList, // Means that every three verties will correspond to one triangle.
+                strip_index_format: None,
+                // Face orientation and culling debug:
+                // - With DEBUG_FACE_COLORING = true in shaders:
+                //     front faces (CCW) = vertex colors, back faces = red
+                // - To see BOTH sides: keep cull_mode = None (debug view)
+                // - To see ONLY front faces: set cull_mode = Some(wgpu::Face::Back)
+                // - To see ONLY back faces:  set cull_mode = Some(wgpu::Face::Front)
+                // - If colors appear flipped, change front_face between Ccw/Cw to match your mesh winding
+                front_face: wgpu::FrontFace::Ccw, // CounterClockWise is facing forward, cw are culled
+                cull_mode: None, // Debug default: no culling to view both front/back coloring
+                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
+                // or Features::POLYGON_MODE_POINT
+                polygon_mode: wgpu::PolygonMode::Fill,
+                // Requires Features::DEPTH_CLIP_CONTROL
+                unclipped_depth: false,
+                // Requires Features::CONSERVATIVE_RASTERIZATION
+                conservative: false,
+            },
+
+            // Depth testing so that closer surfaces hide the ones behind them
+            depth_stencil: Some(Texture::depth_stencil_state()),
+            multisample: wgpu::MultisampleState {
+                count: sample_count, // Determines how many samples the pipeline will use
+                mask: !0, // Specifies which samples should be active, here we use all
+                alpha_to_coverage_enabled: false, // related to multisampling, only needed for alpha tested geometry
+            },
+            // If the pipeline will be used with a multiview render pass, this
+            // indicates how many array layers the attachments will have.
+            multiview: None,
+            // Useful for optimizing shader compilation on Android
+            cache: None,
+        });
+
+
+         // Pipeline for rendering
+         let render_pipeline_color = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Color Pipeline"),
+            layout: Some(&render_pipeline_layout),
+
+            vertex: wgpu::VertexState {
+                module: &shader_color, // <-- Change the shader
+                entry_point: Some("vs_main"), // 1. vertex entry point
+                buffers: &[
+                    Vertex::desc(),
+                    InstanceRaw::desc(),
+                ], // 2. tells wgpu that type of vetices we want to pass to vertex shader
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+
+            fragment: Some(wgpu::FragmentState { // 3. This is optional so we wrap to Some(), we need it for colors
+                module: &shader_color, // <-- Change the shader
+                entry_point: Some("fs_main"), // 1. fragment entry point
+                targets: &[Some(wgpu::ColorTargetState { // 4. tells wgpu what color outputs it should set up
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+
+            // The primitive field describes how to interpret our vertices when converting them into triangles.
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList, // Means that every three verties will correspond to one triangle.
+                strip_index_format: None,
+                // Face orientation and culling debug:
+                // - With DEBUG_FACE_COLORING = true in shaders:
+                //     front faces (CCW) = vertex colors, back faces = red
+                // - To see BOTH sides: keep cull_mode = None (debug view)
+                // - To see ONLY front faces: set cull_mode = Some(wgpu::Face::Back)
+                // - To see ONLY back faces:  set cull_mode = Some(wgpu::Face::Front)
+                // - If colors appear flipped, change front_face between Ccw/Cw to match your mesh winding
+                front_face: wgpu::FrontFace::Ccw, // CounterClockWise is facing forward, cw are culled
+                cull_mode: None, // Debug default: no culling to view both front/back coloring
+                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
+                // or Features::POLYGON_MODE_POINT
+                polygon_mode: wgpu::PolygonMode::Fill,
+                // Requires Features::DEPTH_CLIP_CONTROL
+                unclipped_depth: false,
+                // Requires Features::CONSERVATIVE_RASTERIZATION
+                conservative: false,
+            },
+
+            // Depth testing so that closer surfaces hide the ones behind them
+            depth_stencil: Some(Texture::depth_stencil_state()),
+            multisample: wgpu::MultisampleState {
+                count: sample_count, // Determines how many samples the pipeline will use
+                mask: !0, // Specifies which samples should be active, here we use all
+                alpha_to_coverage_enabled: false, // related to multisampling, only needed for alpha tested geometry
+            },
+            // If the pipeline will be used with a multiview render pass, this
+            // indicates how many array layers the attachments will have.
+            multiview: None,
+            // Useful for optimizing shader compilation on Android
+            cache: None,
+        });
+
+        // Pipeline for point sprites: one instanced quad per point, expanded in the vertex shader
+        let render_pipeline_point = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Point Pipeline"),
+            layout: Some(&point_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_point,
+                entry_point: Some("vs_main"),
+                buffers: &[PointInstance::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_point,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(Texture::depth_stencil_state()),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        // Pipeline for mesh edges: two vertices per edge, drawn with the instances of the faces
+        let render_pipeline_edge = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Edge Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_edge,
+                entry_point: Some("vs_main"),
+                buffers: &[EdgeVertex::desc(), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_edge,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // The depth offset is applied in the shader, WebGPU rejects depth bias for lines
+            depth_stencil: Some(Texture::depth_stencil_state()),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        // Same edges without depth test for x-ray, blended over the ghosted faces after compositing
+        let render_pipeline_edge_xray = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("X-Ray Edge Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_edge,
+                entry_point: Some("vs_main"),
+                buffers: &[EdgeVertex::desc(), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_edge,
+                entry_point: Some("fs_xray"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            // The composite pass draws into the resolved target, without depth
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        // Transparent pass pipelines, same vertex stages as their opaque counterparts
+        let order_independent = supports_order_independent_transparency(adapter, device, sample_count);
+        if !order_independent {
+            log::warn!("Transparency buffers not supported at {}x MSAA, translucent surfaces are blended in draw order", sample_count);
+        }
+        let blended = (!order_independent).then_some(color_format);
+        let render_pipeline_solid_transparent = create_transparent_pipeline(
+            device, "Solid Transparent Pipeline", &render_pipeline_layout, &shader_solid, &[Vertex::desc(), InstanceRaw::desc()], sample_count, blended,
+        );
+        let render_pipeline_color_transparent = create_transparent_pipeline(
+            device, "Color Transparent Pipeline", &render_pipeline_layout, &shader_color, &[Vertex::desc(), InstanceRaw::desc()], sample_count, blended,
+        );
+        let render_pipeline_point_transparent = create_transparent_pipeline(
+            device, "Point Transparent Pipeline", &point_pipeline_layout, &shader_point, &[PointInstance::desc()], sample_count, blended,
+        );
+
+        // Composite: a full screen triangle reading the resolved transparency buffers
+        let composite_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[0, 1].map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }),
+            label: Some("composite_bind_group_layout"),
+        });
+        let composite_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Composite Pipeline Layout"),
+            bind_group_layouts: &[&composite_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline_composite = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Composite Pipeline"),
+            layout: Some(&composite_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_composite,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_composite,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        // Nothing is cut until clip planes or a section box are set
+        let clipping = Clipping::default();
+        let clip_uniform = clipping.uniform();
+        let clip_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Clip Buffer"),
+            contents: bytemuck::cast_slice(&[clip_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let transparency = order_independent.then(|| TransparencyTargets::new(device, &composite_bind_group_layout, width, height, sample_count));
+        let overlay = Overlay::new(device, &camera_bind_group_layout, color_format, sample_count);
+        let attribute_colors = AttributeColors::new(device, color_format);
+        let labels = Labels::new(device, &camera_bind_group_layout, color_format, sample_count);
+        let measure_lines = MeasureLines::new(device, &camera_bind_group_layout, color_format);
+        let shadows = Shadows::new(device, &camera_bind_group_layout, &clip_buffer, color_format, sample_count);
+        let post = PostProcess::new(device, &camera_bind_group_layout, color_format, width, height);
+
+        // Pop and log any validation errors that might have occurred during pipeline creation
+        #[cfg(target_arch = "wasm32")]
+        if let Some(err) = device.pop_error_scope().await {
+            web_sys::console::error_1(&format!("WGPU validation (pipeline): {:?}", err).into());
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(err) = device.pop_error_scope().await {
+            eprintln!("WGPU validation (pipeline): {:?}", err);
+        }
+
+        // Depth buffer (and the multisampled color buffer) sized to the color target
+        let depth_texture = Texture::create_depth_texture(device, width, height, sample_count, "depth_texture");
+        let msaa_texture = (sample_count > 1).then(|| Texture::create_color_texture(device, color_format, width, height, sample_count, "msaa_texture"));
+
+        // The camera uniform is written by update_camera() before every frame
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_viewport(width, height);
+
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let shading_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shading Buffer"),
+            contents: bytemuck::cast_slice(&[ShadingUniform::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let [colormap_entry, sampler_entry, range_entry] = attribute_colors.bind_group_entries();
+        let [shadow_map_entry, shadow_sampler_entry, sun_entry] = shadows.bind_group_entries();
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                colormap_entry,
+                sampler_entry,
+                shadow_map_entry,
+                shadow_sampler_entry,
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: clip_buffer.as_entire_binding(),
+                },
+                range_entry,
+                sun_entry,
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: shading_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("camera_bind_group"),
+        });
+
+        Self {
+            render_pipeline_solid,
+            render_pipeline_color,
+            render_pipeline_point,
+            render_pipeline_edge,
+            render_pipeline_edge_xray,
+            render_pipeline_solid_transparent,
+            render_pipeline_color_transparent,
+            render_pipeline_point_transparent,
+            render_pipeline_composite,
+            composite_bind_group_layout,
+            transparency,
+            overlay,
+            attribute_colors,
+            labels,
+            measurements: Measurements::default(),
+            measure_lines,
+            shadows,
+            post,
+            use_color_pipeline: true,
+            shading_mode: ShadingMode::default(),
+            display_mode: DisplayMode::default(),
+            edge_filter: EdgeFilter::default(),
+            feature_angle: DEFAULT_FEATURE_ANGLE,
+            clipping,
+            clip_uniform,
+            clip_buffer,
+            shading_buffer,
+            color_format,
+            sample_count,
+            msaa_texture,
+            depth_texture,
+            scene,
+            camera_uniform,
+            camera_buffer,
+            camera_bind_group,
+        }
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    // Upload new or changed objects of the sources.
+    // Planes flagged as sections (see section.rs) become clip planes instead.
+    pub fn set_geometry(&mut self, device: &wgpu::Device, sources: &[AllGeometryData]) -> SyncStats {
+        self.clipping.set_sources(sources);
+        self.update_clipping();
+        self.attribute_colors.set_sources(sources);
+        self.scene.sync(device, sources)
+    }
+
+    // The depth and multisampled attachments must always match the color target size
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.depth_texture = Texture::create_depth_texture(device, width, height, self.sample_count, "depth_texture");
+        if self.sample_count > 1 {
+            self.msaa_texture = Some(Texture::create_color_texture(device, self.color_format, width, height, self.sample_count, "msaa_texture"));
+        }
+        if self.transparency.is_some() {
+            self.transparency = Some(TransparencyTargets::new(device, &self.composite_bind_group_layout, width, height, self.sample_count));
+        }
+        self.post.resize(device, width, height);
+        self.camera_uniform.update_viewport(width, height);
+    }
+
+    // Upload the camera matrices, viewport, shading mode, clip planes and sun for the next frame
+    pub fn update_camera(&mut self, queue: &wgpu::Queue, camera: &Camera) {
+        let bounds = self.scene.bounds();
+        self.camera_uniform.update_view_proj(camera);
+        self.shadows.update(queue, bounds);
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+        queue.write_buffer(&self.clip_buffer, 0, bytemuck::cast_slice(&[self.clip_uniform]));
+        let shading = ShadingUniform::new(self.shading_mode, self.edge_filter, self.feature_angle);
+        queue.write_buffer(&self.shading_buffer, 0, bytemuck::cast_slice(&[shading]));
+        self.post.update(queue, camera, bounds);
+        self.overlay.update(queue, camera);
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    // False when translucent surfaces fall back to alpha blending in draw order
+    pub fn order_independent_transparency(&self) -> bool {
+        self.transparency.is_some()
+    }
+
+    pub fn use_color_pipeline(&self) -> bool {
+        self.use_color_pipeline
+    }
+
+    pub fn set_use_color_pipeline(&mut self, use_color_pipeline: bool) {
+        self.use_color_pipeline = use_color_pipeline;
+    }
+
+    pub fn shading_mode(&self) -> ShadingMode {
+        self.shading_mode
+    }
+
+    // Takes effect with the next update_camera()
+    pub fn set_shading_mode(&mut self, mode: ShadingMode) {
+        self.shading_mode = mode;
+    }
+
+    pub fn display_mode(&self) -> DisplayMode {
+        self.display_mode
+    }
+
+    pub fn set_display_mode(&mut self, mode: DisplayMode) {
+        self.display_mode = mode;
+    }
+
+    pub fn xray(&self) -> bool {
+        self.scene.xray()
+    }
+
+    // Ghost everything but the selection and show hidden edges
+    pub fn set_xray(&mut self, xray: bool) {
+        self.scene.set_xray(xray);
+    }
+
+    pub fn edge_filter(&self) -> EdgeFilter {
+        self.edge_filter
+    }
+
+    // Takes effect with the next update_camera()
+    pub fn set_edge_filter(&mut self, filter: EdgeFilter) {
+        self.edge_filter = filter;
+    }
+
+    pub fn feature_angle(&self) -> f32 {
+        self.feature_angle
+    }
+
+    // Crease angle in degrees above which EdgeFilter::Feature draws an edge.
+    // Takes effect with the next update_camera()
+    pub fn set_feature_angle(&mut self, degrees: f32) {
+        self.feature_angle = degrees.clamp(0.0, 180.0);
+    }
+
+    pub fn overlay(&self) -> OverlaySettings {
+        self.overlay.settings()
+    }
+
+    // Show or hide the grid, axes and view gizmo and set the grid spacing.
+    // Takes effect with the next update_camera()
+    pub fn set_overlay(&mut self, settings: OverlaySettings) {
+        self.overlay.set_settings(settings);
+    }
+
+    // Standard view of the gizmo face under the cursor (pixels from the top left of the target)
+    pub fn gizmo_view_at(&self, camera: &Camera, cursor: [f32; 2]) -> Option<NamedView> {
+        let [width, height, ..] = self.camera_uniform.viewport;
+        self.overlay.draws_gizmo().then(|| gizmo_view_at(camera, [width, height], cursor)).flatten()
+    }
+
+    pub fn attribute_coloring(&self) -> Option<&AttributeColoring> {
+        self.attribute_colors.coloring()
+    }
+
+    // Color meshes by a vertex or face attribute, None shows their own colors again.
+    // A new attribute is written into the meshes by the next set_geometry(),
+    // the colormap and range take effect with the next render()
+    pub fn set_attribute_coloring(&mut self, coloring: Option<AttributeColoring>) {
+        self.scene.set_attribute(coloring.as_ref().map(|c| (c.source, c.attribute.as_str())));
+        self.attribute_colors.set_coloring(coloring);
+    }
+
+    // Values at both ends of the colormap, None while not coloring or no mesh has the attribute
+    pub fn attribute_range(&self) -> Option<[f32; 2]> {
+        self.attribute_colors.range()
+    }
+
+    pub fn labels(&self) -> LabelSettings {
+        self.labels.settings()
+    }
+
+    // Choose which labels are shown, how they are occluded and decluttered; takes effect with the next render()
+    pub fn set_labels(&mut self, settings: LabelSettings) {
+        self.labels.set_settings(settings);
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        self.labels.annotations()
+    }
+
+    // Replace the free text annotations
+    pub fn set_annotations(&mut self, annotations: Vec<Annotation>) {
+        self.labels.set_annotations(annotations);
+    }
+
+    pub fn measurements(&self) -> &Measurements {
+        &self.measurements
+    }
+
+    // Tool, picks and results of the measuring tools; drawn as they are at the next render()
+    pub fn measurements_mut(&mut self) -> &mut Measurements {
+        &mut self.measurements
+    }
+
+    pub fn sun(&self) -> SunSettings {
+        self.shadows.settings()
+    }
+
+    // Place the sun and switch its shadows and the ground plane.
+    // Takes effect with the next update_camera()
+    pub fn set_sun(&mut self, settings: SunSettings) {
+        self.shadows.set_settings(settings);
+    }
+
+    pub fn post(&self) -> PostSettings {
+        self.post.settings()
+    }
+
+    // Switch the ambient occlusion and the silhouette outlines; takes effect with the next update_camera()
+    pub fn set_post(&mut self, settings: PostSettings) {
+        self.post.set_settings(settings);
+    }
+
+    // Planes currently cutting the view: the section box or the API and source planes
+    pub fn clip_planes(&self) -> Vec<[f32; 4]> {
+        self.clipping.active_planes()
+    }
+
+    // Replace the API clip planes; each cuts away the side its z-axis points to.
+    // Together with the section planes of the sources at most MAX_CLIP_PLANES are used.
+    // Takes effect with the next update_camera()
+    pub fn set_clip_planes(&mut self, planes: &[Plane]) {
+        self.clipping.planes = planes.iter().filter_map(plane_equation).collect();
+        self.update_clipping();
+    }
+
+    pub fn section_box(&self) -> Option<(Point3<f32>, Point3<f32>)> {
+        self.clipping.section_box
+    }
+
+    // Keep only what is inside an axis-aligned box (replaces the clip planes while set).
+    // Takes effect with the next update_camera()
+    pub fn set_section_box(&mut self, bounds: Option<(Point3<f32>, Point3<f32>)>) {
+        // Corners in any order
+        self.clipping.section_box = bounds.and_then(|(a, b)| bounds_of([a, b]));
+        self.update_clipping();
+    }
+
+    pub fn section_fill(&self) -> Option<[f32; 3]> {
+        self.clipping.fill
+    }
+
+    // Cap color of cut closed solids, None leaves them open.
+    // Takes effect with the next update_camera()
+    pub fn set_section_fill(&mut self, fill: Option<[f32; 3]>) {
+        self.clipping.fill = fill;
+        self.update_clipping();
+    }
+
+    fn update_clipping(&mut self) {
+        self.clip_uniform = self.clipping.uniform();
+    }
+
+    // Draw the scene into a color target of the format and size given to new()/resize()
+    pub fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView) {
+        // Rebuild instance buffers if objects were added, moved or hidden since the last frame
+        self.scene.prepare(device, queue);
+        self.attribute_colors.prepare(device, queue);
+        self.measure_lines.prepare(device, queue, &self.measurements);
+        self.labels.prepare(device, queue, &self.scene, &self.camera_uniform, &self.clipping.active_planes(), &self.measurements.labels());
+        let xray = self.scene.xray();
+        let skip_edged = !self.display_mode.draws_faces();
+
+        // We also need a CommandEncoder to create the actual commands to send to GPU.
+        // Most modern graphics frameworks expect commands to to be stored in a command buffer before sending to GPU.
+        // The encoder builds a command buffer that we can then send to the GPU.
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
+
+        // The sun's view of the opaque model, read by the lighting of every pass after it
+        self.shadows.render_map(&mut encoder, &self.scene, skip_edged);
+
+        // Clearing the screen.
+        // We need to use the encoder to create a RenderPass.
+        // The RenderPass has all the methods for the actual drawing.
+        // The render method via shaders will draw the geometry.
+        // With MSAA we draw into the multisampled texture and resolve it into the target;
+        // the samples themselves are not needed after the pass.
+        let (attachment, resolve_target, store) = match &self.msaa_texture {
+            Some(msaa) => (&msaa.view, Some(view), wgpu::StoreOp::Discard),
+            None => (view, None, wgpu::StoreOp::Store),
+        };
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: attachment,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(CLEAR_COLOR),
+                        store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0), // Far plane
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            // We set the the pipeline on the render_pass using the one we created for shader.
+            if self.use_color_pipeline {
+                render_pass.set_pipeline(&self.render_pipeline_color);
+            } else {
+                render_pass.set_pipeline(&self.render_pipeline_solid);
+            }
+
+            // Set the camera bind group (pipeline expects it even if shaders don't use it)
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+
+            // Draw every visible object; objects sharing a geometry are drawn as instances.
+            // Without faces the meshes are drawn by their edges only.
+            self.scene.draw_triangles(&mut render_pass, DrawPass::Opaque, skip_edged);
+
+            // X-ray draws the edges after compositing, hidden ones included
+            if self.display_mode.draws_edges() && !xray {
+                render_pass.set_pipeline(&self.render_pipeline_edge);
+                self.scene.draw_edges(&mut render_pass);
+            }
+
+            // Point sprites are drawn afterwards with their own pipeline
+            render_pass.set_pipeline(&self.render_pipeline_point);
+            self.scene.draw_points(&mut render_pass, DrawPass::Opaque);
+
+            // Ground plane under the model, the grid and axes stay above it
+            self.shadows.draw_ground(&mut render_pass, &self.camera_bind_group);
+
+            // Grid and axes blend over the background, behind the opaque model
+            self.overlay.draw_scene(&mut render_pass, &self.camera_bind_group);
+
+            // Labels that the model in front of them hides or fades
+            self.labels.draw_scene(&mut render_pass, &self.camera_bind_group);
+
+            // Without the transparency buffers translucent surfaces blend over all of the above
+            if self.transparency.is_none() && self.scene.has_transparent() {
+                self.draw_transparent(&mut render_pass, skip_edged);
+            }
+        }
+
+        // Ambient occlusion and outlines darken the opaque image only, not the translucent surfaces over it
+        self.post.render(&mut encoder, view, &self.camera_bind_group, &self.scene, skip_edged);
+
+        // Translucent fragments, in any order, tested against the opaque depth
+        let transparency = self.transparency.as_ref().filter(|_| self.scene.has_transparent());
+        if let Some(targets) = transparency {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Transparent Pass"),
+                color_attachments: &[
+                    Some(TransparencyTargets::attachment(&targets.accum, &targets.accum_msaa, wgpu::Color::TRANSPARENT)),
+                    Some(TransparencyTargets::attachment(&targets.revealage, &targets.revealage_msaa, wgpu::Color::WHITE)),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            self.draw_transparent(&mut render_pass, skip_edged);
+        }
+
+        // Blend the transparent pass over the resolved opaque image, then the x-ray edges,
+        // the measurements, the labels, the gizmo and the legend
+        let measure = self.measure_lines.draws();
+        let labels = self.labels.draws_top();
+        let gizmo = self.overlay.draws_gizmo();
+        let legend = self.attribute_colors.draws_legend();
+        if transparency.is_some() || xray || measure || labels || gizmo || legend {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Composite Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            if let Some(targets) = transparency {
+                render_pass.set_pipeline(&self.render_pipeline_composite);
+                render_pass.set_bind_group(0, &targets.bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+            if xray {
+                render_pass.set_pipeline(&self.render_pipeline_edge_xray);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                self.scene.draw_edges(&mut render_pass);
+            }
+            if measure {
+                self.measure_lines.draw(&mut render_pass, &self.camera_bind_group);
+            }
+            if labels {
+                self.labels.draw_top(&mut render_pass, &self.camera_bind_group);
+            }
+            if gizmo {
+                let [width, height, ..] = self.camera_uniform.viewport;
+                self.overlay.draw_gizmo(&mut render_pass, &self.camera_bind_group, [width, height]);
+            }
+            if legend {
+                let [width, height, ..] = self.camera_uniform.viewport;
+                self.attribute_colors.draw_legend(&mut render_pass, [width, height]);
+            }
+        }
+        queue.submit(iter::once(encoder.finish()));
+    }
+
+    // Translucent triangles and points, into the transparency buffers or blended into the target
+    fn draw_transparent(&self, render_pass: &mut wgpu::RenderPass, skip_edged: bool) {
+        if self.use_color_pipeline {
+            render_pass.set_pipeline(&self.render_pipeline_color_transparent);
+        } else {
+            render_pass.set_pipeline(&self.render_pipeline_solid_transparent);
+        }
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        self.scene.draw_triangles(render_pass, DrawPass::Transparent, skip_edged);
+        render_pass.set_pipeline(&self.render_pipeline_point_transparent);
+        self.scene.draw_points(render_pass, DrawPass::Transparent);
+    }
+}
@@ -0,0 +1,600 @@
+// Scene registry: one entry per openmodel object, keyed by Data guid.
+// Objects can be added, removed, hidden or updated one at a time,
+// and a reload only re-uploads the objects whose content changed.
+// Objects with identical geometry share GPU buffers and are drawn with instancing,
+// each instance placed by its own model matrix.
+// Objects are drawn in an opaque pass and, when they have alpha below 1 (vertex alpha, object
+// opacity or x-ray), in a transparent pass; each fragment is drawn by exactly one of them.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use cgmath::{Matrix4, Point3, SquareMatrix};
+use openmodel::AllGeometryData;
+use wgpu::util::DeviceExt;
+use crate::colormap::AttributeSource;
+use crate::geometry::{snap_alpha, GeometryBuffers, GeometryObject};
+use crate::labels::GeometryLabels;
+use crate::picking::{bounds_of, PickGeometry, PickHit, Ray};
+use crate::section::{is_section_object, keeps};
+use crate::shading::XRAY_OPACITY;
+use crate::vertex::InstanceRaw;
+
+// The two passes of a frame: fragments with alpha 1 write depth in the opaque pass,
+// the others are blended order-independently in the transparent pass
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DrawPass {
+    Opaque,
+    Transparent,
+}
+
+// GPU buffers of one geometry. Empty parts have no buffer (wgpu cannot slice empty buffers).
+pub struct GpuGeometry {
+    vertex_buffer: Option<wgpu::Buffer>,
+    index_buffer: Option<wgpu::Buffer>,
+    num_indices: u32,
+    point_buffer: Option<wgpu::Buffer>,
+    num_points: u32,
+    edge_buffer: Option<wgpu::Buffer>,
+    num_edge_vertices: u32,
+    // Some vertices or points have alpha below 1, so the transparent pass is needed even at full opacity
+    translucent_triangles: bool,
+    translucent_points: bool,
+    closed: bool, // See GeometryBuffers::closed
+}
+
+impl GpuGeometry {
+    pub fn new(device: &wgpu::Device, geometry: &GeometryBuffers, label: &str) -> Self {
+        let create = |contents: &[u8], usage: wgpu::BufferUsages, suffix: &str| {
+            (!contents.is_empty()).then(|| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} {}", label, suffix)),
+                    contents,
+                    usage,
+                })
+            })
+        };
+        Self {
+            vertex_buffer: create(bytemuck::cast_slice(&geometry.vertices), wgpu::BufferUsages::VERTEX, "Vertex Buffer"),
+            index_buffer: create(bytemuck::cast_slice(&geometry.indices), wgpu::BufferUsages::INDEX, "Index Buffer"),
+            num_indices: geometry.indices.len() as u32,
+            point_buffer: create(bytemuck::cast_slice(&geometry.points), wgpu::BufferUsages::VERTEX, "Point Buffer"),
+            num_points: geometry.points.len() as u32,
+            edge_buffer: create(bytemuck::cast_slice(&geometry.edges), wgpu::BufferUsages::VERTEX, "Edge Buffer"),
+            num_edge_vertices: geometry.edges.len() as u32,
+            translucent_triangles: geometry.vertices.iter().any(|v| v.color[3] < 1.0),
+            translucent_points: geometry.points.iter().any(|p| p.color[3] < 1.0),
+            closed: geometry.closed,
+        }
+    }
+
+    pub fn has_triangles(&self) -> bool {
+        self.index_buffer.is_some()
+    }
+
+    pub fn has_points(&self) -> bool {
+        self.point_buffer.is_some()
+    }
+
+    pub fn has_edges(&self) -> bool {
+        self.edge_buffer.is_some()
+    }
+
+    // Draw the triangles once per instance in the instance buffer (vertex buffer slot 1).
+    // The caller sets the triangle pipeline and camera bind group.
+    pub fn draw_triangles(&self, render_pass: &mut wgpu::RenderPass, instance_buffer: &wgpu::Buffer, instance_count: u32) {
+        if let (Some(vertex_buffer), Some(index_buffer)) = (&self.vertex_buffer, &self.index_buffer) {
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..self.num_indices, 0, 0..instance_count);
+        }
+    }
+
+    // Draw the edge lines once per instance; the caller sets the edge pipeline and camera bind group
+    pub fn draw_edges(&self, render_pass: &mut wgpu::RenderPass, instance_buffer: &wgpu::Buffer, instance_count: u32) {
+        if let Some(edge_buffer) = &self.edge_buffer {
+            render_pass.set_vertex_buffer(0, edge_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+            render_pass.draw(0..self.num_edge_vertices, 0..instance_count);
+        }
+    }
+
+    // Draw the point sprites: 6 vertices (two triangles) per point instance
+    pub fn draw_points(&self, render_pass: &mut wgpu::RenderPass) {
+        if let Some(point_buffer) = &self.point_buffer {
+            render_pass.set_vertex_buffer(0, point_buffer.slice(..));
+            render_pass.draw(0..6, 0..self.num_points);
+        }
+    }
+}
+
+// GPU buffers plus the CPU copies used for picking and labels, shared by all objects with the same geometry
+struct SharedGeometry {
+    gpu: GpuGeometry,
+    pick: PickGeometry,
+    labels: GeometryLabels,
+}
+
+impl SharedGeometry {
+    fn new(device: &wgpu::Device, geometry: &GeometryBuffers, label: &str) -> Self {
+        Self { gpu: GpuGeometry::new(device, geometry, label), pick: PickGeometry::new(geometry), labels: GeometryLabels::new(geometry) }
+    }
+}
+
+// Per-object uniform of the point pipeline (bind group 1)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ModelUniform {
+    model: [[f32; 4]; 4],
+    highlight: [f32; 4], // x: 1.0 for the selected object, y: opacity (zw unused)
+}
+
+pub struct SceneObject {
+    pub name: String,
+    pub hash: u64,          // Content hash of the source object, compared on reload
+    pub geometry_hash: u64, // Key of the shared geometry (content without identity and placement)
+    pub visible: bool,
+    pub opacity: f32, // 0-1, multiplies the vertex alpha
+    pub model: Matrix4<f32>,
+    // Index of the source passed to sync() and of the object in its GeometryObject::all(); None when inserted
+    pub source: Option<[usize; 2]>,
+    // Point sprites are already instanced per point, so their model matrix is a uniform (bind group 1)
+    model_uniform: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+}
+
+// Triangles of one geometry drawn once per visible object using it
+struct InstanceBatch {
+    geometry_hash: u64,
+    instance_buffer: wgpu::Buffer,
+    instance_count: u32,
+    opaque: bool,      // Some instance is fully opaque
+    transparent: bool, // Some instance or vertex has alpha below 1
+}
+
+// Counts reported by Scene::sync
+#[derive(Debug, Default)]
+pub struct SyncStats {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+pub struct Scene {
+    // BTreeMap keeps the draw order stable between frames and reloads
+    objects: BTreeMap<String, SceneObject>,
+    geometries: HashMap<u64, SharedGeometry>,
+    batches: Vec<InstanceBatch>,
+    batches_dirty: bool,
+    has_transparent: bool, // Whether the last prepare() found anything for the transparent pass
+    bounds: Option<(Point3<f32>, Point3<f32>)>, // Of the visible objects, updated whenever they change
+    selected: Option<String>,
+    xray: bool,
+    attribute: Option<(AttributeSource, String)>, // Mesh attribute baked into Vertex.value, see colormap.rs
+    model_bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl Scene {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let model_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("model_bind_group_layout"),
+        });
+        Self {
+            objects: BTreeMap::new(),
+            geometries: HashMap::new(),
+            batches: Vec::new(),
+            batches_dirty: true,
+            has_transparent: false,
+            bounds: None,
+            selected: None,
+            xray: false,
+            attribute: None,
+            model_bind_group_layout,
+        }
+    }
+
+    // Layout of the per-object model uniform used by the point pipeline
+    pub fn model_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.model_bind_group_layout
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SceneObject> {
+        self.objects.get(key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.objects.keys()
+    }
+
+    // Add or replace a single object from prebuilt buffers
+    pub fn insert(&mut self, device: &wgpu::Device, key: &str, name: &str, geometry: &GeometryBuffers, model: Matrix4<f32>) {
+        let geometry_hash = geometry.content_hash();
+        let model_array: [[f32; 4]; 4] = model.into();
+        let hash = geometry_hash ^ crate::fnv1a64(bytemuck::cast_slice(&model_array));
+        self.geometries.entry(geometry_hash).or_insert_with(|| SharedGeometry::new(device, geometry, name));
+        self.place(device, key, name, hash, geometry_hash, model);
+        self.update_bounds();
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        let removed = self.objects.remove(key).is_some();
+        if removed {
+            self.drop_unused_geometries();
+            self.update_bounds();
+        }
+        removed
+    }
+
+    // Show or hide an object without touching its GPU buffers
+    pub fn set_visible(&mut self, key: &str, visible: bool) -> bool {
+        match self.objects.get_mut(key) {
+            Some(object) => {
+                object.visible = visible;
+                self.batches_dirty = true;
+                self.update_bounds();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Set the opacity of an object (0 invisible, 1 opaque); kept across reloads like visibility
+    pub fn set_opacity(&mut self, key: &str, opacity: f32) -> bool {
+        match self.objects.get_mut(key) {
+            Some(object) => {
+                object.opacity = snap_alpha(opacity);
+                self.batches_dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn xray(&self) -> bool {
+        self.xray
+    }
+
+    pub fn attribute(&self) -> Option<(AttributeSource, &str)> {
+        self.attribute.as_ref().map(|(source, name)| (*source, name.as_str()))
+    }
+
+    // Mesh attribute written into the vertices for coloring; meshes are rebuilt by the next sync()
+    pub fn set_attribute(&mut self, attribute: Option<(AttributeSource, &str)>) {
+        self.attribute = attribute.map(|(source, name)| (source, name.to_string()));
+    }
+
+    // X-ray: every object except the selected one is drawn with XRAY_OPACITY,
+    // so members inside envelopes can be inspected and picked
+    pub fn set_xray(&mut self, xray: bool) {
+        if xray != self.xray {
+            self.xray = xray;
+            self.batches_dirty = true;
+        }
+    }
+
+    // Opacity an object is drawn with: its own, lowered by x-ray unless it is selected
+    fn draw_opacity(&self, key: &str, object: &SceneObject) -> f32 {
+        if self.xray && self.selected.as_deref() != Some(key) {
+            object.opacity.min(XRAY_OPACITY)
+        } else {
+            object.opacity
+        }
+    }
+
+    // Make the scene match the loaded sources.
+    // Objects whose guid and content hash did not change keep their GPU buffers;
+    // a moved object only gets a new model matrix, and geometry is only built when no
+    // other object already uploaded the same one. Objects no longer present are dropped.
+    pub fn sync(&mut self, device: &wgpu::Device, sources: &[AllGeometryData]) -> SyncStats {
+        let mut stats = SyncStats::default();
+        let mut seen: HashSet<String> = HashSet::new();
+
+        // Meshes built with another attribute in their vertices count as changed
+        let attribute = self.attribute.clone();
+        let attribute = attribute.as_ref().map(|(source, name)| (*source, name.as_str()));
+        let salt = attribute.map_or(0, |(source, name)| crate::fnv1a64(format!("{:?}/{}", source, name).as_bytes()));
+        // Section planes cut the view (see Renderer::set_geometry) and are not drawn
+        let source_objects = sources
+            .iter()
+            .enumerate()
+            .flat_map(|(s, source)| GeometryObject::all(source).into_iter().enumerate().map(move |(i, object)| ([s, i], object)))
+            .filter(|(_, object)| !is_section_object(object))
+            .map(|(source, object)| {
+                let salt = if matches!(object, GeometryObject::Mesh(..)) { salt } else { 0 };
+                (object.data().guid().to_string(), object.content_hash() ^ salt, object.geometry_hash() ^ salt, object, source)
+            });
+
+        for (mut key, hash, geometry_hash, object, source) in source_objects {
+            // The same guid can appear twice (e.g. in local and remote files): keep both
+            if seen.contains(&key) {
+                let mut n = 1;
+                while seen.contains(&format!("{}#{}", key, n)) { n += 1; }
+                key = format!("{}#{}", key, n);
+            }
+            seen.insert(key.clone());
+
+            match self.objects.get(&key) {
+                Some(existing) if existing.hash == hash => stats.unchanged += 1,
+                existing => {
+                    if existing.is_some() { stats.updated += 1 } else { stats.added += 1 }
+                    let name = object.data().name();
+                    self.geometries.entry(geometry_hash).or_insert_with(|| {
+                        SharedGeometry::new(device, &GeometryBuffers::from_object_colored_by(object, attribute), name)
+                    });
+                    self.place(device, &key, name, hash, geometry_hash, object.model_matrix());
+                }
+            }
+            // Unchanged objects can still have moved within the sources
+            if let Some(entry) = self.objects.get_mut(&key) {
+                entry.source = Some(source);
+            }
+        }
+
+        let before = self.objects.len();
+        self.objects.retain(|key, _| seen.contains(key));
+        stats.removed = before - self.objects.len();
+        if self.selected.as_ref().is_some_and(|key| !self.objects.contains_key(key)) {
+            self.selected = None;
+        }
+        self.drop_unused_geometries();
+        self.update_bounds();
+        stats
+    }
+
+    // Create or update the object entry for an already uploaded geometry; keeps visibility
+    fn place(&mut self, device: &wgpu::Device, key: &str, name: &str, hash: u64, geometry_hash: u64, model: Matrix4<f32>) {
+        let visible = self.objects.get(key).is_none_or(|o| o.visible);
+        let opacity = self.objects.get(key).map_or(1.0, |o| o.opacity);
+        let has_points = self.geometries.get(&geometry_hash).is_some_and(|g| g.gpu.has_points());
+        let model_uniform = has_points.then(|| {
+            let uniform = ModelUniform { model: model.into(), highlight: [0.0; 4] };
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Model Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.model_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+                label: Some("model_bind_group"),
+            });
+            (buffer, bind_group)
+        });
+        self.objects.insert(key.to_string(), SceneObject {
+            name: name.to_string(),
+            hash,
+            geometry_hash,
+            visible,
+            opacity,
+            model,
+            source: None,
+            model_uniform,
+        });
+        self.batches_dirty = true;
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.as_deref()
+    }
+
+    // Select one object (highlighted when drawn) or clear the selection
+    pub fn set_selected(&mut self, key: Option<&str>) {
+        let key = key.filter(|k| self.objects.contains_key(*k)).map(str::to_string);
+        if key != self.selected {
+            self.selected = key;
+            self.batches_dirty = true;
+        }
+    }
+
+    // Closest visible object along a world-space ray.
+    // Point sprites are matched in screen space, so the cursor position in pixels is needed too.
+    // Geometry cut away by the clip planes cannot be hit.
+    pub fn pick(&self, ray: &Ray, view_proj: &Matrix4<f32>, viewport: [f32; 2], cursor: [f32; 2], clip_planes: &[[f32; 4]]) -> Option<PickHit> {
+        let (t_min, t_max) = ray.clip(clip_planes)?;
+        // Triangles are tested from the first kept point on, so a cut face in front does not hide what is behind
+        let kept_ray = Ray { origin: ray.at(t_min), direction: ray.direction };
+        let mut best: Option<PickHit> = None;
+        for (key, object) in self.objects.iter().filter(|(_, o)| o.visible) {
+            let Some(geometry) = self.geometries.get(&object.geometry_hash) else { continue };
+            let Some(inverse) = object.model.invert() else { continue };
+            let local_ray = kept_ray.transformed(&inverse);
+
+            let mut hit: Option<PickHit> = None;
+            if let Some(triangle) = geometry.pick.intersect_triangles(&local_ray).filter(|h| t_min + h.t <= t_max) {
+                let t = t_min + triangle.t;
+                hit = Some(PickHit {
+                    key: key.clone(),
+                    t,
+                    position: ray.at(t).into(),
+                    element: Some(triangle.element),
+                    vertex_key: Some(triangle.vertex_key),
+                    point_index: None,
+                });
+            }
+            let points = geometry.pick.intersect_points(ray, &object.model, view_proj, viewport, cursor);
+            // Sprites are cut as a whole, like in shader_point.wgsl
+            let kept = |index: usize| keeps(clip_planes, Point3::from_homogeneous(object.model * geometry.pick.points[index].0.to_homogeneous()));
+            if let Some((t, index)) = points.filter(|&(_, index)| kept(index)) {
+                if hit.as_ref().is_none_or(|h| t < h.t) {
+                    hit = Some(PickHit {
+                        key: key.clone(),
+                        t,
+                        position: ray.at(t).into(),
+                        element: None,
+                        vertex_key: None,
+                        point_index: Some(index),
+                    });
+                }
+            }
+            if let Some(hit) = hit {
+                if best.as_ref().is_none_or(|b| hit.t < b.t) {
+                    best = Some(hit);
+                }
+            }
+        }
+        best
+    }
+
+    // World-space bounding box of one object: its geometry box transformed by the model matrix
+    pub fn object_bounds(&self, key: &str) -> Option<(Point3<f32>, Point3<f32>)> {
+        let object = self.objects.get(key)?;
+        let (min, max) = self.geometries.get(&object.geometry_hash)?.pick.bounds?;
+        bounds_of((0..8).map(|corner| {
+            let p = Point3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+            Point3::from_homogeneous(object.model * p.to_homogeneous())
+        }))
+    }
+
+    // World-space bounding box of the visible objects, used for framing and the camera clip planes
+    pub fn bounds(&self) -> Option<(Point3<f32>, Point3<f32>)> {
+        self.bounds
+    }
+
+    // Read every frame (shadows, post-processing), so only walked when objects change
+    fn update_bounds(&mut self) {
+        self.bounds = bounds_of(
+            self.objects
+                .iter()
+                .filter(|(_, o)| o.visible)
+                .filter_map(|(key, _)| self.object_bounds(key))
+                .flat_map(|(min, max)| [min, max]),
+        );
+    }
+
+    // Visible objects with the label anchors of their geometry (object space), see labels.rs
+    pub fn labeled_objects(&self) -> impl Iterator<Item = (&str, &SceneObject, &GeometryLabels)> + '_ {
+        self.objects
+            .iter()
+            .filter(|(_, object)| object.visible && object.opacity > 0.0)
+            .filter_map(|(key, object)| Some((key.as_str(), object, &self.geometries.get(&object.geometry_hash)?.labels)))
+    }
+
+    fn drop_unused_geometries(&mut self) {
+        let used: HashSet<u64> = self.objects.values().map(|o| o.geometry_hash).collect();
+        self.geometries.retain(|hash, _| used.contains(hash));
+        self.batches_dirty = true;
+    }
+
+    // Rebuild the instance buffers after objects were added, moved, removed, hidden or selected
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.batches_dirty {
+            return;
+        }
+        self.batches_dirty = false;
+
+        self.has_transparent = false;
+        for (key, object) in &self.objects {
+            if let Some((buffer, _)) = &object.model_uniform {
+                let highlight = if self.selected.as_ref() == Some(key) { 1.0 } else { 0.0 };
+                let opacity = self.draw_opacity(key, object);
+                let uniform = ModelUniform { model: object.model.into(), highlight: [highlight, opacity, 0.0, 0.0] };
+                queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[uniform]));
+                let translucent = self.geometries.get(&object.geometry_hash).is_some_and(|g| g.gpu.translucent_points);
+                self.has_transparent |= object.visible && (opacity < 1.0 || translucent);
+            }
+        }
+
+        // Group visible objects by geometry, in draw order of the first object using it
+        let mut order: Vec<u64> = Vec::new();
+        let mut instances: HashMap<u64, Vec<InstanceRaw>> = HashMap::new();
+        for (key, object) in self.objects.iter().filter(|(_, o)| o.visible) {
+            let list = instances.entry(object.geometry_hash).or_insert_with(|| {
+                order.push(object.geometry_hash);
+                Vec::new()
+            });
+            let closed = self.geometries.get(&object.geometry_hash).is_some_and(|g| g.gpu.closed);
+            list.push(InstanceRaw::new(object.model, self.selected.as_ref() == Some(key), self.draw_opacity(key, object), closed));
+        }
+
+        self.batches = order
+            .into_iter()
+            .map(|geometry_hash| {
+                let raw = &instances[&geometry_hash];
+                let translucent = self.geometries.get(&geometry_hash).is_some_and(|g| g.gpu.translucent_triangles);
+                InstanceBatch {
+                    geometry_hash,
+                    instance_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Instance Buffer"),
+                        contents: bytemuck::cast_slice(raw),
+                        usage: wgpu::BufferUsages::VERTEX,
+                    }),
+                    instance_count: raw.len() as u32,
+                    opaque: raw.iter().any(|i| i.opacity >= 1.0),
+                    transparent: translucent || raw.iter().any(|i| i.opacity < 1.0),
+                }
+            })
+            .collect();
+        let transparent_triangles = self.batches.iter().filter(|b| b.transparent);
+        self.has_transparent |= transparent_triangles.filter_map(|b| self.geometries.get(&b.geometry_hash)).any(|g| g.gpu.has_triangles());
+    }
+
+    // Whether the transparent pass has anything to draw; valid after prepare()
+    pub fn has_transparent(&self) -> bool {
+        self.has_transparent
+    }
+
+    // Draw all visible triangles of a pass, one instanced draw per shared geometry.
+    // The wireframe mode skips geometries that are drawn by their edges instead.
+    pub fn draw_triangles(&self, render_pass: &mut wgpu::RenderPass, pass: DrawPass, skip_edged: bool) {
+        for batch in &self.batches {
+            let in_pass = match pass {
+                DrawPass::Opaque => batch.opaque,
+                DrawPass::Transparent => batch.transparent,
+            };
+            if let Some(geometry) = self.geometries.get(&batch.geometry_hash).filter(|_| in_pass) {
+                if !(skip_edged && geometry.gpu.has_edges()) {
+                    geometry.gpu.draw_triangles(render_pass, &batch.instance_buffer, batch.instance_count);
+                }
+            }
+        }
+    }
+
+    // Draw the edges of all visible meshes with the same instances as their triangles
+    pub fn draw_edges(&self, render_pass: &mut wgpu::RenderPass) {
+        for batch in &self.batches {
+            if let Some(geometry) = self.geometries.get(&batch.geometry_hash) {
+                geometry.gpu.draw_edges(render_pass, &batch.instance_buffer, batch.instance_count);
+            }
+        }
+    }
+
+    // Draw all visible point sprites of a pass; the caller sets the point pipeline and camera bind group
+    pub fn draw_points(&self, render_pass: &mut wgpu::RenderPass, pass: DrawPass) {
+        for (key, object) in self.objects.iter().filter(|(_, o)| o.visible) {
+            if let (Some((_, bind_group)), Some(geometry)) = (&object.model_uniform, self.geometries.get(&object.geometry_hash)) {
+                let opacity = self.draw_opacity(key, object);
+                let in_pass = match pass {
+                    DrawPass::Opaque => opacity >= 1.0,
+                    DrawPass::Transparent => opacity < 1.0 || geometry.gpu.translucent_points,
+                };
+                if in_pass {
+                    render_pass.set_bind_group(1, bind_group, &[]);
+                    geometry.gpu.draw_points(render_pass);
+                }
+            }
+        }
+    }
+}
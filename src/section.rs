@@ -0,0 +1,113 @@
+// Section views: user clipping planes and the section box.
+// The active planes travel in their own uniform next to the camera's and clipping.wgsl discards
+// what lies in front of them, so cutting never touches the uploaded geometry.
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Point3, SquareMatrix, Vector4};
+use openmodel::AllGeometryData;
+use openmodel::geometry::Plane;
+use crate::geometry::{column_major_matrix, GeometryObject};
+
+// Size of the plane array in the clip uniform
+pub const MAX_CLIP_PLANES: usize = 6;
+
+// Planes of the sources named "section..." (any case) cut the view instead of being drawn
+pub const SECTION_PLANE_PREFIX: &str = "section";
+
+pub fn is_section_plane(plane: &Plane) -> bool {
+    let name = plane.data.name();
+    name.get(..SECTION_PLANE_PREFIX.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(SECTION_PLANE_PREFIX))
+}
+
+pub fn is_section_object(object: &GeometryObject) -> bool {
+    matches!(object, GeometryObject::Plane(plane) if is_section_plane(plane))
+}
+
+// World-space equation [a, b, c, d] with a unit normal, Data.transformation applied.
+// The side the z-axis points to (a*x + b*y + c*z + d > 0) is cut away. None for a zero z-axis.
+pub fn plane_equation(plane: &Plane) -> Option<[f32; 4]> {
+    let model = column_major_matrix(plane.data.transformation());
+    let (o, z) = (&plane.origin, &plane.zaxis);
+    let origin = Point3::from_homogeneous(model * Vector4::new(o.x as f32, o.y as f32, o.z as f32, 1.0));
+    let normal = (model.invert()?.transpose() * Vector4::new(z.x as f32, z.y as f32, z.z as f32, 0.0)).truncate();
+    if normal.magnitude2() < 1e-12 {
+        return None;
+    }
+    let normal = normal.normalize();
+    Some([normal.x, normal.y, normal.z, -normal.dot(origin.to_vec())])
+}
+
+// The six planes of an axis-aligned box, keeping what is inside
+pub fn box_planes(min: Point3<f32>, max: Point3<f32>) -> [[f32; 4]; 6] {
+    [
+        [1.0, 0.0, 0.0, -max.x],
+        [-1.0, 0.0, 0.0, min.x],
+        [0.0, 1.0, 0.0, -max.y],
+        [0.0, -1.0, 0.0, min.y],
+        [0.0, 0.0, 1.0, -max.z],
+        [0.0, 0.0, -1.0, min.z],
+    ]
+}
+
+// Point not cut away by any of the planes
+pub fn keeps(planes: &[[f32; 4]], point: Point3<f32>) -> bool {
+    planes.iter().all(|[a, b, c, d]| a * point.x + b * point.y + c * point.z + d <= 0.0)
+}
+
+// Clip planes and section fill as clipping.wgsl reads them (binding 5 of the camera bind group)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ClipUniform {
+    pub planes: [[f32; 4]; MAX_CLIP_PLANES], // Plane equations, see plane_equation()
+    pub fill: [f32; 4], // Fill of cut solids (w: 1 on, 0 off)
+    pub count: [u32; 4], // x: active planes (yzw unused)
+}
+
+impl ClipUniform {
+    // Planes beyond MAX_CLIP_PLANES are dropped
+    pub fn new(planes: &[[f32; 4]], fill: Option<[f32; 3]>) -> Self {
+        let count = planes.len().min(MAX_CLIP_PLANES);
+        let mut uniform = Self {
+            planes: [[0.0; 4]; MAX_CLIP_PLANES],
+            fill: fill.map_or([0.0; 4], |[r, g, b]| [r, g, b, 1.0]),
+            count: [count as u32, 0, 0, 0],
+        };
+        uniform.planes[..count].copy_from_slice(&planes[..count]);
+        uniform
+    }
+}
+
+// Clipping state of the renderer
+#[derive(Clone, Debug, Default)]
+pub struct Clipping {
+    pub planes: Vec<[f32; 4]>,        // Set through the API
+    pub source_planes: Vec<[f32; 4]>, // Section planes of the loaded sources
+    pub section_box: Option<(Point3<f32>, Point3<f32>)>,
+    pub fill: Option<[f32; 3]>, // Cap color of cut solids, None leaves them open
+}
+
+impl Clipping {
+    // Collect the section planes of the sources, in source order
+    pub fn set_sources(&mut self, sources: &[AllGeometryData]) {
+        self.source_planes = sources
+            .iter()
+            .flat_map(|source| &source.planes)
+            .filter(|plane| is_section_plane(plane))
+            .filter_map(plane_equation)
+            .collect();
+    }
+
+    // The section box replaces the planes while it is on; only the first MAX_CLIP_PLANES planes are used
+    pub fn active_planes(&self) -> Vec<[f32; 4]> {
+        if let Some((min, max)) = self.section_box {
+            return box_planes(min, max).to_vec();
+        }
+        let planes: Vec<[f32; 4]> = self.planes.iter().chain(&self.source_planes).copied().collect();
+        if planes.len() > MAX_CLIP_PLANES {
+            log::warn!("{} clip planes, only the first {} are used", planes.len(), MAX_CLIP_PLANES);
+        }
+        planes.into_iter().take(MAX_CLIP_PLANES).collect()
+    }
+
+    pub fn uniform(&self) -> ClipUniform {
+        ClipUniform::new(&self.active_planes(), self.fill)
+    }
+}
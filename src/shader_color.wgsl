@@ -2,6 +2,8 @@
 
 struct CameraUniform {
     view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+    eye: vec4<f32>,
 }
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
@@ -15,24 +17,66 @@ const DEBUG_FACE_COLORING: bool = false; // false -> vertex colors
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
-    @location(1) color: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) value: vec2<f32>, // Colored attribute and whether the vertex has it
+};
+
+// Per-instance placement (Data.transformation, PointCloud/LineCloud xform)
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+    @location(12) highlight: f32,
+    @location(13) opacity: f32,
+    @location(14) closed: f32,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) highlight: f32,
+    @location(4) alpha: f32,
+    @location(5) value: vec2<f32>,
+    @location(6) closed: f32,
 };
 
 @vertex
 fn vs_main(
     model: VertexInput,
+    instance: InstanceInput,
 ) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
     var out: VertexOutput;
-    out.color = model.color;
+    out.color = model.color.rgb;
+    out.alpha = model.color.a * instance.opacity;
+    out.world_position = world_position.xyz;
+    out.normal = normal_matrix * model.normal;
+    out.highlight = instance.highlight;
+    out.closed = instance.closed;
+    out.value = model.value;
     if (BYPASS_CAMERA) {
         out.clip_position = vec4<f32>(model.position.xy * 0.5, model.position.z, 1.0);
     } else {
-        out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+        out.clip_position = camera.view_proj * world_position;
     }
     return out;
 }
@@ -41,15 +85,47 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput, @builtin(front_facing) is_front: bool) -> @location(0) vec4<f32> {
+    // Shade before branching: derivatives in shade() need uniform control flow
+    let shaded = shade(attribute_color(in.color, in.value), in.world_position, in.normal);
+    let lit = apply_highlight(shaded, in.highlight);
+    // Translucent fragments are drawn by fs_transparent
+    if (in.alpha < OPAQUE_ALPHA || clipped(in.world_position)) {
+        discard;
+    }
+    if (section_cap(is_front, in.closed)) {
+        return vec4<f32>(clipping.fill.rgb, 1.0);
+    }
     if (DEBUG_FACE_COLORING) {
         // Debug mode: fronts use vertex color; backs are BLACK
         if (is_front) {
-            return vec4<f32>(in.color, 1.0);
+            return vec4<f32>(lit, 1.0);
         } else {
             return vec4<f32>(0.0, 0.0, 0.0, 1.0);
         }
     } else {
-        // Default COLOR look: per-vertex colors
-        return vec4<f32>(in.color, 1.0);
+        // Default COLOR look: lit per-vertex colors
+        return vec4<f32>(lit, 1.0);
+    }
+}
+
+// Transparent pass: lit per-vertex colors weighted by alpha
+@fragment
+fn fs_transparent(in: VertexOutput) -> TransparentOutput {
+    let shaded = shade(attribute_color(in.color, in.value), in.world_position, in.normal);
+    let lit = apply_highlight(shaded, in.highlight);
+    if (in.alpha >= OPAQUE_ALPHA || in.alpha <= 0.0 || clipped(in.world_position)) {
+        discard;
+    }
+    return transparent_output(lit, in.alpha, in.clip_position.z);
+}
+
+// Without order-independent transparency: alpha blended over the opaque pass in draw order
+@fragment
+fn fs_blended(in: VertexOutput) -> @location(0) vec4<f32> {
+    let shaded = shade(attribute_color(in.color, in.value), in.world_position, in.normal);
+    let lit = apply_highlight(shaded, in.highlight);
+    if (in.alpha >= OPAQUE_ALPHA || in.alpha <= 0.0 || clipped(in.world_position)) {
+        discard;
     }
+    return vec4<f32>(lit, in.alpha);
 }
@@ -0,0 +1,27 @@
+// Full screen pass blending the transparent pass result over the opaque image
+
+@group(0) @binding(0)
+var accum_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var revealage_texture: texture_2d<f32>;
+
+// One triangle covering the viewport
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let revealage = textureLoad(revealage_texture, pixel, 0).r;
+    // Nothing transparent covers this pixel
+    if (revealage >= 1.0) {
+        discard;
+    }
+    let accum = textureLoad(accum_texture, pixel, 0);
+    let color = accum.rgb / max(accum.a, 1e-5);
+    // Blended with SrcAlpha / OneMinusSrcAlpha over the opaque image
+    return vec4<f32>(color, 1.0 - revealage);
+}
@@ -0,0 +1,100 @@
+// Mesh edges drawn as lines on top of (or instead of) the shaded faces
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+    eye: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+const EDGE_FILTER_ALL: u32 = 0u;
+const EDGE_FILTER_BOUNDARY: u32 = 1u;
+const EDGE_FILTER_FEATURE: u32 = 2u;
+
+const EDGE_COLOR: vec3<f32> = vec3<f32>(0.1, 0.1, 0.1);
+// X-ray draws edges without depth test, hidden ones included, over the ghosted faces
+const XRAY_EDGE_ALPHA: f32 = 0.6;
+// Pulls edges towards the camera so they win the depth test against their own faces.
+// Done in clip space because WebGPU does not allow depth bias on line topologies.
+const EDGE_DEPTH_OFFSET: f32 = 0.0005;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) boundary: f32,
+    @location(2) angle: f32,
+};
+
+// Same instances as the faces of the mesh
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+    @location(12) highlight: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) visible: f32,
+    @location(1) highlight: f32,
+    @location(2) world_position: vec3<f32>,
+};
+
+fn edge_visible(boundary: f32, angle: f32) -> bool {
+    let edge_filter = shading.modes.y;
+    if (edge_filter == EDGE_FILTER_BOUNDARY) {
+        return boundary > 0.5;
+    }
+    if (edge_filter == EDGE_FILTER_FEATURE) {
+        return boundary > 0.5 || angle >= shading.angles.x;
+    }
+    return true;
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: VertexOutput;
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    var clip = camera.view_proj * world_position;
+    clip.z -= EDGE_DEPTH_OFFSET * clip.w;
+    out.clip_position = clip;
+    // Both ends of an edge carry the same flags, so the whole line is kept or discarded
+    out.visible = select(0.0, 1.0, edge_visible(model.boundary, model.angle));
+    out.highlight = instance.highlight;
+    out.world_position = world_position.xyz;
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (in.visible < 0.5 || clipped(in.world_position)) {
+        discard;
+    }
+    return vec4<f32>(apply_highlight(EDGE_COLOR, in.highlight), 1.0);
+}
+
+@fragment
+fn fs_xray(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (in.visible < 0.5 || clipped(in.world_position)) {
+        discard;
+    }
+    // Selected members stand out in full color
+    let alpha = mix(XRAY_EDGE_ALPHA, 1.0, in.highlight);
+    return vec4<f32>(apply_highlight(EDGE_COLOR, in.highlight), alpha);
+}
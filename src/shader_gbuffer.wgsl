@@ -0,0 +1,64 @@
+// Prepass of the post-processing, see post.rs: depth and face normals of the opaque triangles.
+// clipping.wgsl and transparency.wgsl are prepended, so cut away and translucent parts are left out.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+    eye: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(13) opacity: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) alpha: f32,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    out.alpha = model.color.a * instance.opacity;
+    return out;
+}
+
+// World space face normal mapped to 0..1
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Before branching: derivatives need uniform control flow
+    var n = normalize(cross(dpdx(in.world_position), dpdy(in.world_position)));
+    if (in.alpha < OPAQUE_ALPHA || clipped(in.world_position)) {
+        discard;
+    }
+    // The side facing the viewer, like the two-sided lighting
+    if (dot(n, camera.eye.xyz - in.world_position) < 0.0) {
+        n = -n;
+    }
+    return vec4<f32>(n * 0.5 + 0.5, 1.0);
+}
@@ -0,0 +1,55 @@
+// Ground plane under the model that receives the sun's shadows, see shadow.rs.
+// lighting.wgsl is prepended for sun_visibility() and the `sun` uniform.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+    eye: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+const GROUND_PLANE_COLOR: vec3<f32> = vec3<f32>(0.86, 0.86, 0.85);
+// Background the plane fades into at its rim (CLEAR_COLOR in renderer.rs)
+const BACKGROUND_COLOR: vec3<f32> = vec3<f32>(0.9, 0.9, 0.9);
+// How much a full strength sun darkens the plane where it is blocked
+const SHADOW_DARKNESS: f32 = 0.6;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) local: vec2<f32>, // -1..1 across the plane
+};
+
+// A square drawn as a triangle strip
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 4>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let world_position = vec3<f32>(sun.ground.xy + corner * sun.ground.w, sun.ground.z);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
+    out.world_position = world_position;
+    out.local = corner;
+    return out;
+}
+
+// Only the shadows darken the plane, so it stays close to the background elsewhere
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = GROUND_PLANE_COLOR;
+    if (sun.direction.w > 0.0) {
+        let n_dot_l = normalize(sun.direction.xyz).z;
+        let blocked = 1.0 - sun_visibility(in.world_position, n_dot_l);
+        color *= 1.0 - SHADOW_DARKNESS * sun.direction.w * blocked;
+    }
+    // Fade into the background towards the rim so the plane has no hard edge
+    let fade = smoothstep(0.5, 1.0, length(in.local));
+    return vec4<f32>(mix(color, BACKGROUND_COLOR, fade), 1.0);
+}
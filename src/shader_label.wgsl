@@ -0,0 +1,86 @@
+// Labels: glyph quads placed on screen by labels.rs, textured from the font atlas
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+    eye: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Red: glyph, green: glyph with its one pixel halo
+@group(1) @binding(0)
+var atlas_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var atlas_sampler: sampler;
+
+// Atlas layout, see labels.rs
+const ATLAS_COLUMNS: u32 = 16u;
+const CELL: vec2<f32> = vec2<f32>(7.0, 9.0);
+const ATLAS_SIZE: vec2<f32> = vec2<f32>(112.0, 54.0);
+const HALO_ALPHA: f32 = 0.8;
+// Labels behind the model with LabelOcclusion::Faded
+const FADED_ALPHA: f32 = 0.3;
+
+struct GlyphInput {
+    @location(0) rect: vec4<f32>, // Left, top, width, height in pixels
+    @location(1) color: vec4<f32>,
+    @location(2) glyph: u32,
+    @location(3) depth: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+// Each glyph is a screen-aligned quad made of two triangles
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    glyph: GlyphInput,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+
+    // Pixels from the top left to NDC
+    let pixel = glyph.rect.xy + corner * glyph.rect.zw;
+    let ndc = vec2<f32>(pixel.x / camera.viewport.x * 2.0 - 1.0, 1.0 - pixel.y / camera.viewport.y * 2.0);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, glyph.depth, 1.0);
+    let cell = vec2<f32>(f32(glyph.glyph % ATLAS_COLUMNS), f32(glyph.glyph / ATLAS_COLUMNS));
+    out.uv = (cell + corner) * CELL / ATLAS_SIZE;
+    out.color = glyph.color;
+    return out;
+}
+
+// Fragment shader
+
+// Dark text gets a light halo and light text a dark one, so labels read on any background
+fn label_color(in: VertexOutput) -> vec4<f32> {
+    let texel = textureSample(atlas_texture, atlas_sampler, in.uv).rg;
+    let luminance = dot(in.color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+    let halo = select(vec3<f32>(1.0), vec3<f32>(0.0), luminance > 0.3);
+    let alpha = texel.g * mix(HALO_ALPHA, 1.0, texel.r) * in.color.a;
+    return vec4<f32>(mix(halo, in.color.rgb, texel.r), alpha);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return label_color(in);
+}
+
+@fragment
+fn fs_faded(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = label_color(in);
+    return vec4<f32>(color.rgb, color.a * FADED_ALPHA);
+}
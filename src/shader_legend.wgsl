@@ -0,0 +1,26 @@
+// Attribute color legend, an image drawn 1:1 into its viewport rectangle
+
+@group(0) @binding(0)
+var legend_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var legend_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the viewport
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y); // Image rows from the top
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(legend_texture, legend_sampler, in.uv);
+}
@@ -0,0 +1,68 @@
+// Measurements: dimension lines and square markers at the measured points, over the model
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+    eye: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct LineInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_line(line: LineInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(line.position, 1.0);
+    out.color = line.color;
+    return out;
+}
+
+@fragment
+fn fs_line(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+
+struct MarkerInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) size: f32,
+};
+
+// Each marker is a screen-aligned square made of two triangles
+@vertex
+fn vs_marker(
+    @builtin(vertex_index) vertex_index: u32,
+    marker: MarkerInput,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+
+    var out: VertexOutput;
+    let center = camera.view_proj * vec4<f32>(marker.position, 1.0);
+    // Pixel offset to NDC (2 units span the viewport), scaled by w to survive the perspective divide
+    let offset = corner * marker.size / camera.viewport.xy * center.w;
+    out.clip_position = vec4<f32>(center.xy + offset, center.zw);
+    out.color = marker.color;
+    return out;
+}
+
+@fragment
+fn fs_marker(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
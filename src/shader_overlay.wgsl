@@ -0,0 +1,154 @@
+// Overlay: construction grid on the XY plane, world axes and the view gizmo
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+    eye: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct OverlayUniform {
+    inv_view_proj: mat4x4<f32>,
+    gizmo_view_proj: mat4x4<f32>,
+    gizmo_eye: vec4<f32>, // Direction from the target towards the eye
+    grid: vec4<f32>, // x: minor spacing, y: major spacing, z: fade distance, w: 1 when adaptive
+}
+@group(1) @binding(0)
+var<uniform> overlay: OverlayUniform;
+
+const GRID_MINOR_COLOR: vec3<f32> = vec3<f32>(0.55, 0.55, 0.55);
+const GRID_MAJOR_COLOR: vec3<f32> = vec3<f32>(0.4, 0.4, 0.4);
+const GRID_MINOR_ALPHA: f32 = 0.5;
+const GRID_MAJOR_ALPHA: f32 = 0.8;
+// Adaptive grids keep minor cells at least this many pixels wide, in steps of 10x
+const MIN_CELL_PIXELS: f32 = 8.0;
+
+struct GridOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+// One triangle covering the viewport; the fragments find their point on the plane z = 0
+@vertex
+fn vs_grid(@builtin(vertex_index) vertex_index: u32) -> GridOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: GridOutput;
+    out.ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+fn unproject(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let p = overlay.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+    return p.xyz / p.w;
+}
+
+// Antialiased line coverage (0-1) of a grid with the given spacing, lines about one pixel wide
+fn grid_lines(coord: vec2<f32>, spacing: f32) -> f32 {
+    let cell = coord / spacing;
+    let width = fwidth(cell);
+    let distance = abs(fract(cell - 0.5) - 0.5) / max(width, vec2<f32>(1e-6));
+    return 1.0 - min(min(distance.x, distance.y), 1.0);
+}
+
+struct GridFragment {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+@fragment
+fn fs_grid(in: GridOutput) -> GridFragment {
+    let near = unproject(in.ndc, 0.0);
+    let far = unproject(in.ndc, 1.0);
+    let direction = far - near;
+    let t = -near.z / select(direction.z, 1e-6, abs(direction.z) < 1e-6);
+    let world = near + direction * t;
+
+    // Derivatives first: they need uniform control flow
+    var minor = overlay.grid.x;
+    var major = overlay.grid.y;
+    if (overlay.grid.w > 0.5) {
+        let world_per_pixel = max(length(vec2<f32>(dpdx(world.x), dpdy(world.x))), length(vec2<f32>(dpdx(world.y), dpdy(world.y))));
+        let level = max(ceil(log2(MIN_CELL_PIXELS * world_per_pixel / minor) / log2(10.0)), 0.0);
+        let scale = pow(10.0, level);
+        minor *= scale;
+        major *= scale;
+    }
+    let minor_lines = grid_lines(world.xy, minor);
+    let major_lines = grid_lines(world.xy, major);
+
+    let clip = camera.view_proj * vec4<f32>(world, 1.0);
+    // Only where the plane lies between the near and far planes
+    if (t < 0.0 || t > 1.0 || clip.w <= 0.0) {
+        discard;
+    }
+
+    // Fade with distance and at grazing angles, where the lines would turn into noise
+    let distance_fade = 1.0 - smoothstep(0.3, 1.0, length(world - camera.eye.xyz) / overlay.grid.z);
+    let angle_fade = smoothstep(0.0, 0.2, abs(normalize(direction).z));
+    let minor_alpha = minor_lines * GRID_MINOR_ALPHA;
+    let major_alpha = major_lines * GRID_MAJOR_ALPHA;
+    let alpha = max(minor_alpha, major_alpha) * distance_fade * angle_fade;
+    if (alpha <= 0.0) {
+        discard;
+    }
+
+    var out: GridFragment;
+    out.color = vec4<f32>(select(GRID_MINOR_COLOR, GRID_MAJOR_COLOR, major_alpha >= minor_alpha), alpha);
+    out.depth = clip.z / clip.w;
+    return out;
+}
+
+struct LineInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct LineOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) world_position: vec3<f32>,
+};
+
+// Axis lines are unit segments scaled to the fade distance
+@vertex
+fn vs_axes(in: LineInput) -> LineOutput {
+    var out: LineOutput;
+    out.world_position = in.position * overlay.grid.z;
+    out.clip_position = camera.view_proj * vec4<f32>(out.world_position, 1.0);
+    out.color = in.color.rgb;
+    return out;
+}
+
+@fragment
+fn fs_axes(in: LineOutput) -> @location(0) vec4<f32> {
+    let fade = 1.0 - smoothstep(0.3, 1.0, length(in.world_position - camera.eye.xyz) / overlay.grid.z);
+    return vec4<f32>(in.color, fade);
+}
+
+struct GizmoInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+struct GizmoOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+// The gizmo cube turns with the camera but never moves or zooms
+@vertex
+fn vs_gizmo(in: GizmoInput) -> GizmoOutput {
+    var out: GizmoOutput;
+    out.clip_position = overlay.gizmo_view_proj * vec4<f32>(in.position, 1.0);
+    // Faces turned towards the viewer are brighter
+    out.color = in.color.rgb * (0.55 + 0.45 * max(dot(in.normal, overlay.gizmo_eye.xyz), 0.0));
+    return out;
+}
+
+@fragment
+fn fs_gizmo(in: GizmoOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
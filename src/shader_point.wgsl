@@ -0,0 +1,92 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    viewport: vec4<f32>,
+    eye: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Model matrix of the object owning the points (Data.transformation, PointCloud xform)
+struct ModelUniform {
+    model: mat4x4<f32>,
+    highlight: vec4<f32>, // x: selection highlight, y: opacity
+}
+@group(1) @binding(0)
+var<uniform> model_uniform: ModelUniform;
+
+struct InstanceInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) size: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) corner: vec2<f32>,
+    @location(2) alpha: f32,
+};
+
+// Each point is a screen-aligned quad made of two triangles
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+
+    var out: VertexOutput;
+    out.color = apply_highlight(instance.color.rgb, model_uniform.highlight.x);
+    out.alpha = instance.color.a * model_uniform.highlight.y;
+    out.corner = corner;
+
+    let world_position = model_uniform.model * vec4<f32>(instance.position, 1.0);
+    // A cut point drops its whole sprite: zero alpha is discarded by both fragment shaders
+    if (clipped(world_position.xyz)) {
+        out.alpha = 0.0;
+    }
+
+    let center = camera.view_proj * world_position;
+    // Pixel offset to NDC (2 units span the viewport), scaled by w to survive the perspective divide
+    let offset = corner * instance.size / camera.viewport.xy * center.w;
+    out.clip_position = vec4<f32>(center.xy + offset, center.zw);
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Round sprites: discard the corners of the quad
+    // Translucent points are drawn by fs_transparent
+    if (dot(in.corner, in.corner) > 1.0 || in.alpha < OPAQUE_ALPHA) {
+        discard;
+    }
+    return vec4<f32>(in.color, 1.0);
+}
+
+@fragment
+fn fs_transparent(in: VertexOutput) -> TransparentOutput {
+    if (dot(in.corner, in.corner) > 1.0 || in.alpha >= OPAQUE_ALPHA || in.alpha <= 0.0) {
+        discard;
+    }
+    return transparent_output(in.color, in.alpha, in.clip_position.z);
+}
+
+@fragment
+fn fs_blended(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (dot(in.corner, in.corner) > 1.0 || in.alpha >= OPAQUE_ALPHA || in.alpha <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(in.color, in.alpha);
+}
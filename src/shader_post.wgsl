@@ -0,0 +1,164 @@
+// Post-processing of the opaque image, see post.rs. Screen-space ambient occlusion from the
+// depth and normals of the prepass (shader_gbuffer.wgsl), blurred, then multiplied into the
+// image together with silhouette outlines where the depth or the normals jump.
+
+struct PostUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    eye: vec4<f32>, // w unused
+    forward: vec4<f32>, // Viewing direction (w unused)
+    size: vec4<f32>, // xy: image size, zw: occlusion texture size in pixels
+    occlusion: vec4<f32>, // x: radius in model units, y: strength, z: samples, w: 1 on
+    outline: vec4<f32>, // rgb: color, w: 1 on
+    crease: vec4<f32>, // x: cosine of the crease angle, y: step tolerance relative to the distance
+}
+@group(0) @binding(0)
+var<uniform> post: PostUniform;
+@group(0) @binding(1)
+var depth_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var normal_texture: texture_2d<f32>;
+@group(0) @binding(3)
+var occlusion_texture: texture_2d<f32>;
+@group(0) @binding(4)
+var occlusion_sampler: sampler;
+
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+// One triangle covering the viewport
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn load_depth(pixel: vec2<i32>) -> f32 {
+    return textureLoad(depth_texture, clamp(pixel, vec2<i32>(0), vec2<i32>(post.size.xy) - 1), 0).r;
+}
+
+fn load_normal(pixel: vec2<i32>) -> vec3<f32> {
+    let encoded = textureLoad(normal_texture, clamp(pixel, vec2<i32>(0), vec2<i32>(post.size.xy) - 1), 0).xyz;
+    return normalize(encoded * 2.0 - 1.0);
+}
+
+// Surface point seen at the center of a pixel
+fn world_position(pixel: vec2<i32>, depth: f32) -> vec3<f32> {
+    let uv = (vec2<f32>(pixel) + 0.5) / post.size.xy;
+    let world = post.inv_view_proj * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    return world.xyz / world.w;
+}
+
+// Distance in front of the camera, along the viewing direction
+fn view_depth(p: vec3<f32>) -> f32 {
+    return dot(p - post.eye.xyz, post.forward.xyz);
+}
+
+// Per pixel rotation of the sample pattern (interleaved gradient noise), evened out by the blur
+fn noise(position: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(position, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+// Share of the hemisphere above a pixel that nearby surfaces hide, as 1 (open) .. 0 (hidden)
+@fragment
+fn fs_occlusion(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    // The occlusion texture may be smaller than the image
+    let pixel = vec2<i32>(position.xy * post.size.xy / post.size.zw);
+    let depth = load_depth(pixel);
+    if (depth >= 1.0) {
+        return vec4<f32>(1.0);
+    }
+    let p = world_position(pixel, depth);
+    let n = load_normal(pixel);
+    let helper = select(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(1.0, 0.0, 0.0), abs(n.z) > 0.9);
+    let t = normalize(cross(helper, n));
+    let b = cross(n, t);
+
+    let radius = post.occlusion.x;
+    let samples = u32(post.occlusion.z);
+    let rotation = noise(position.xy) * 6.2831853;
+    var occlusion = 0.0;
+    for (var i = 0u; i < samples; i++) {
+        // Cosine weighted directions on a spiral, more samples close to the point
+        let f = (f32(i) + 0.5) / f32(samples);
+        let angle = f32(i) * GOLDEN_ANGLE + rotation;
+        let r = sqrt(f);
+        let direction = t * (r * cos(angle)) + b * (r * sin(angle)) + n * sqrt(1.0 - f);
+        let scale = mix(0.1, 1.0, fract(f32(i) * 0.618034 + rotation * 0.1591549));
+        let s = p + direction * radius * scale * scale;
+
+        let clip = post.view_proj * vec4<f32>(s, 1.0);
+        let uv = vec2<f32>(clip.x / clip.w * 0.5 + 0.5, 0.5 - clip.y / clip.w * 0.5);
+        if (any(uv < vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0))) {
+            continue;
+        }
+        let sample_pixel = vec2<i32>(uv * post.size.xy);
+        let sample_depth = load_depth(sample_pixel);
+        if (sample_depth >= 1.0) {
+            continue;
+        }
+        // Hidden when the visible surface there is in front of the sample; far away surfaces count less
+        let q = world_position(sample_pixel, sample_depth);
+        if (view_depth(q) < view_depth(s) - 0.02 * radius) {
+            occlusion += smoothstep(0.0, 1.0, radius / max(distance(p, q), 1e-6));
+        }
+    }
+    let open = 1.0 - post.occlusion.y * occlusion / f32(max(samples, 1u));
+    return vec4<f32>(clamp(open, 0.0, 1.0));
+}
+
+// 5x5 box filter over the noisy occlusion
+@fragment
+fn fs_blur(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let last = vec2<i32>(post.size.zw) - 1;
+    var sum = 0.0;
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            sum += textureLoad(occlusion_texture, clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), last), 0).r;
+        }
+    }
+    return vec4<f32>(sum / 25.0);
+}
+
+// Outline coverage of a pixel of the model: neighbours on the background, on a surface
+// stepping back from this one, or turned by more than the crease angle
+fn silhouette(pixel: vec2<i32>) -> f32 {
+    let depth = load_depth(pixel);
+    if (depth >= 1.0) {
+        return 0.0;
+    }
+    let p = world_position(pixel, depth);
+    let n = load_normal(pixel);
+    let tolerance = post.crease.y * abs(view_depth(p));
+    var offsets = array<vec2<i32>, 4>(vec2<i32>(1, 0), vec2<i32>(-1, 0), vec2<i32>(0, 1), vec2<i32>(0, -1));
+    var edges = 0.0;
+    for (var i = 0; i < 4; i++) {
+        let other = pixel + offsets[i];
+        let other_depth = load_depth(other);
+        if (other_depth >= 1.0) {
+            edges += 1.0;
+            continue;
+        }
+        let q = world_position(other, other_depth);
+        let step_back = abs(dot(q - p, n)) > tolerance && view_depth(q) > view_depth(p);
+        if (step_back || dot(n, load_normal(other)) < post.crease.x) {
+            edges += 1.0;
+        }
+    }
+    return min(edges * 0.5, 1.0);
+}
+
+// Blended as color + target * alpha: the outline over the occluded image
+@fragment
+fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    var open = 1.0;
+    if (post.occlusion.w > 0.5) {
+        // Explicit level: no derivatives, so this is fine after branching
+        open = textureSampleLevel(occlusion_texture, occlusion_sampler, position.xy / post.size.xy, 0.0).r;
+    }
+    var outline = 0.0;
+    if (post.outline.w > 0.5) {
+        outline = silhouette(vec2<i32>(position.xy));
+    }
+    return vec4<f32>(post.outline.rgb * outline, open * (1.0 - outline));
+}
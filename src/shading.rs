@@ -0,0 +1,114 @@
+// Surface shading modes of the triangle pipelines, switched at runtime with the L key
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ShadingMode {
+    Flat,    // Lit with per-face normals
+    #[default]
+    Smooth,  // Lit with interpolated vertex normals
+    Unlit,   // Raw colors, no lighting
+}
+
+impl ShadingMode {
+    // Cycle Flat -> Smooth -> Unlit -> Flat
+    pub fn next(self) -> Self {
+        match self {
+            ShadingMode::Flat => ShadingMode::Smooth,
+            ShadingMode::Smooth => ShadingMode::Unlit,
+            ShadingMode::Unlit => ShadingMode::Flat,
+        }
+    }
+
+    // Value read by lighting.wgsl (shading.modes.x)
+    pub fn as_u32(self) -> u32 {
+        match self {
+            ShadingMode::Flat => 0,
+            ShadingMode::Smooth => 1,
+            ShadingMode::Unlit => 2,
+        }
+    }
+}
+
+// Opacity of the ghosted (not selected) objects in x-ray mode
+pub const XRAY_OPACITY: f32 = 0.2;
+
+// Default crease angle of EdgeFilter::Feature in degrees
+pub const DEFAULT_FEATURE_ANGLE: f32 = 30.0;
+
+// How meshes are drawn, switched at runtime with the Z key
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum DisplayMode {
+    #[default]
+    Shaded,      // Faces only
+    ShadedEdges, // Faces with mesh edges on top
+    Wireframe,   // Mesh edges only; curves, points and helpers stay shaded
+}
+
+impl DisplayMode {
+    // Cycle Shaded -> ShadedEdges -> Wireframe -> Shaded
+    pub fn next(self) -> Self {
+        match self {
+            DisplayMode::Shaded => DisplayMode::ShadedEdges,
+            DisplayMode::ShadedEdges => DisplayMode::Wireframe,
+            DisplayMode::Wireframe => DisplayMode::Shaded,
+        }
+    }
+
+    pub fn draws_faces(self) -> bool {
+        self != DisplayMode::Wireframe
+    }
+
+    pub fn draws_edges(self) -> bool {
+        self != DisplayMode::Shaded
+    }
+}
+
+// Which mesh edges are drawn, switched at runtime with the B key
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum EdgeFilter {
+    #[default]
+    All,      // Every unique halfedge edge
+    Boundary, // Edges with a face on one side only
+    Feature,  // Boundary edges and creases sharper than the feature angle
+}
+
+impl EdgeFilter {
+    // Cycle All -> Boundary -> Feature -> All
+    pub fn next(self) -> Self {
+        match self {
+            EdgeFilter::All => EdgeFilter::Boundary,
+            EdgeFilter::Boundary => EdgeFilter::Feature,
+            EdgeFilter::Feature => EdgeFilter::All,
+        }
+    }
+
+    // Value read by shader_edge.wgsl (shading.modes.y)
+    pub fn as_u32(self) -> u32 {
+        match self {
+            EdgeFilter::All => 0,
+            EdgeFilter::Boundary => 1,
+            EdgeFilter::Feature => 2,
+        }
+    }
+}
+
+// Shading mode and edge filter as lighting.wgsl and shader_edge.wgsl read them
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadingUniform {
+    pub modes: [u32; 4], // x: ShadingMode, y: EdgeFilter (zw unused)
+    pub angles: [f32; 4], // x: feature angle in degrees (yzw unused)
+}
+
+impl ShadingUniform {
+    pub fn new(mode: ShadingMode, filter: EdgeFilter, feature_angle: f32) -> Self {
+        Self {
+            modes: [mode.as_u32(), filter.as_u32(), 0, 0],
+            angles: [feature_angle, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl Default for ShadingUniform {
+    fn default() -> Self {
+        Self::new(ShadingMode::default(), EdgeFilter::default(), DEFAULT_FEATURE_ANGLE)
+    }
+}
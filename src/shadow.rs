@@ -0,0 +1,382 @@
+// Sun light and shadows for facade and daylight studies.
+// A directional sun, placed by azimuth and altitude or by date, solar time and latitude, lights
+// the model next to the headlight. Its shadows come from one shadow map fitted to the visible
+// model, rendered before the opaque pass; lighting.wgsl filters it with a comparison sampler.
+// An optional ground plane under the model catches the shadows the model casts on the site.
+// World axes: +Z is up, +Y north and +X east.
+use cgmath::{Angle, Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+use crate::camera::{CameraUniform, OPENGL_TO_WGPU_MATRIX};
+use crate::scene::{DrawPass, Scene};
+use crate::texture::Texture;
+use crate::vertex::{InstanceRaw, Vertex};
+
+// Width and height of the shadow map in texels, the largest texture WebGL2 guarantees
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+// The ground plane reaches this many model sizes from the model's center, room for long evening shadows
+const GROUND_EXTENT: f32 = 3.0;
+
+// Where the sun is and what it lights; set with Renderer::set_sun, the H key toggles `enabled`
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SunSettings {
+    pub enabled: bool,
+    pub azimuth: f32, // Degrees clockwise from north: 90 east, 180 south
+    pub altitude: f32, // Degrees above the horizon
+    pub date: Option<SunDate>, // Replaces azimuth and altitude when set
+    pub strength: f32, // Brightness of the sun, the headlight dims to a fill light while it is on
+    pub shadows: bool,
+    pub ground_plane: bool, // Catch the shadows on a plane under the model while the sun is on
+}
+
+impl Default for SunSettings {
+    fn default() -> Self {
+        Self { enabled: false, azimuth: 135.0, altitude: 45.0, date: None, strength: 0.6, shadows: true, ground_plane: true }
+    }
+}
+
+// Sun position from a day of the year and the local solar time
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SunDate {
+    pub month: u32, // 1..12
+    pub day: u32,
+    pub hour: f32, // Local solar time, 12 is noon
+    pub latitude: f32, // Degrees, north positive
+}
+
+// Day of the year of a month and day, 1 January is 1 (no leap years)
+fn day_of_year(month: u32, day: u32) -> u32 {
+    const DAYS_BEFORE: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+    DAYS_BEFORE[(month.clamp(1, 12) - 1) as usize] + day.clamp(1, 31)
+}
+
+// Azimuth and altitude in degrees of the sun at a date, accurate to about a degree:
+// declination from the day of the year, hour angle from the solar time.
+pub fn solar_position(date: &SunDate) -> [f32; 2] {
+    let declination = Deg(-23.44 * (Deg(360.0 / 365.0 * (day_of_year(date.month, date.day) + 10) as f32)).cos());
+    let hour_angle = Deg(15.0 * (date.hour - 12.0));
+    let latitude = Deg(date.latitude);
+    let altitude = (latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos()).asin();
+    // Measured from the south towards the west, then turned to start in the north
+    let from_south = hour_angle.sin().atan2(hour_angle.cos() * latitude.sin() - declination.tan() * latitude.cos());
+    let azimuth = (from_south.to_degrees() + 180.0).rem_euclid(360.0);
+    [azimuth, altitude.to_degrees()]
+}
+
+impl SunSettings {
+    // Azimuth and altitude in degrees, from the date when there is one
+    pub fn position(&self) -> [f32; 2] {
+        self.date.as_ref().map_or([self.azimuth, self.altitude], solar_position)
+    }
+
+    // Unit vector towards the sun
+    pub fn direction(&self) -> Vector3<f32> {
+        let [azimuth, altitude] = self.position().map(Deg);
+        Vector3::new(azimuth.sin() * altitude.cos(), azimuth.cos() * altitude.cos(), altitude.sin())
+    }
+
+    // The sun as the shaders see it: None while off or below the horizon.
+    // Shadows are cast by what lies inside the bounds.
+    pub fn light(&self, bounds: Option<(Point3<f32>, Point3<f32>)>) -> Option<SunLight> {
+        let direction = self.direction();
+        if !self.enabled || self.strength <= 0.0 || direction.z <= 0.0 {
+            return None;
+        }
+        let view_proj = bounds.filter(|_| self.shadows).map(|bounds| sun_view_proj(direction, bounds));
+        Some(SunLight { direction, strength: self.strength, view_proj })
+    }
+}
+
+// Where SunSettings place the sun for the visible model
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SunLight {
+    pub direction: Vector3<f32>, // Towards the sun
+    pub strength: f32,
+    pub view_proj: Option<Matrix4<f32>>, // World to shadow map, None without shadows
+}
+
+// The sun, its shadow map and the ground plane as lighting.wgsl reads them
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SunUniform {
+    pub view_proj: [[f32; 4]; 4], // World to shadow map
+    pub direction: [f32; 4], // Towards the sun (w: strength, 0 off)
+    pub shadow: [f32; 4], // x: 1 when the shadow map is valid (yzw unused)
+    pub ground: [f32; 4], // Ground plane center xy, height and half size (w: 0 off)
+}
+
+impl SunUniform {
+    // No sun lights the model by the headlight alone
+    pub fn new(sun: Option<&SunLight>, ground: Option<[f32; 4]>) -> Self {
+        let view_proj = sun.and_then(|sun| sun.view_proj);
+        Self {
+            view_proj: view_proj.unwrap_or_else(Matrix4::identity).into(),
+            direction: sun.map_or([0.0; 4], |sun| [sun.direction.x, sun.direction.y, sun.direction.z, sun.strength]),
+            shadow: [if view_proj.is_some() { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0],
+            ground: ground.unwrap_or([0.0; 4]),
+        }
+    }
+}
+
+// Orthographic projection along the sun direction that just contains the bounds, depth 0..1 from the sun
+pub fn sun_view_proj(direction: Vector3<f32>, (min, max): (Point3<f32>, Point3<f32>)) -> Matrix4<f32> {
+    let center = min.midpoint(max);
+    let radius = (max - min).magnitude() * 0.5;
+    let direction = direction.normalize();
+    // Any up vector that is not parallel to the sun
+    let up = if direction.z.abs() > 0.99 { Vector3::unit_y() } else { Vector3::unit_z() };
+    let view = Matrix4::look_at_rh(center + direction * (radius + 1.0), center, up);
+
+    // The corners in light space; the view looks down its -z axis
+    let corners = (0..8).map(|corner| {
+        let p = Point3::new(
+            if corner & 1 == 0 { min.x } else { max.x },
+            if corner & 2 == 0 { min.y } else { max.y },
+            if corner & 4 == 0 { min.z } else { max.z },
+        );
+        Point3::from_homogeneous(view * p.to_homogeneous())
+    });
+    let (mut low, mut high) = (Point3::new(f32::MAX, f32::MAX, f32::MAX), Point3::new(f32::MIN, f32::MIN, f32::MIN));
+    for p in corners {
+        low = Point3::new(low.x.min(p.x), low.y.min(p.y), low.z.min(p.z));
+        high = Point3::new(high.x.max(p.x), high.y.max(p.y), high.z.max(p.z));
+    }
+    // A margin keeps flat models and the outermost texels inside the map
+    let margin = radius * 0.01 + 1e-4;
+    let projection = cgmath::ortho(low.x - margin, high.x + margin, low.y - margin, high.y + margin, -high.z - margin, -low.z + margin);
+    OPENGL_TO_WGPU_MATRIX * projection * view
+}
+
+// Ground plane just under the lowest point of the bounds: center xy, height and half size
+pub fn ground_plane((min, max): (Point3<f32>, Point3<f32>)) -> [f32; 4] {
+    let center = min.midpoint(max);
+    let size = (max.x - min.x).max(max.y - min.y).max(max.z - min.z).max(1e-3);
+    // Slightly below, so it never fights with faces or the grid at the model's base
+    [center.x, center.y, min.z - size * 1e-3, size * GROUND_EXTENT]
+}
+
+// Shadow map, the pass that fills it and the ground plane pipeline
+pub struct Shadows {
+    settings: SunSettings,
+    map: Texture,
+    sampler: wgpu::Sampler,
+    sun_buffer: wgpu::Buffer, // SunUniform of the camera bind group
+    caster_pipeline: wgpu::RenderPipeline,
+    // The camera uniform with the sun's view_proj, read by the shadow pass
+    sun_camera_buffer: wgpu::Buffer,
+    sun_camera_bind_group: wgpu::BindGroup,
+    ground_pipeline: wgpu::RenderPipeline,
+    casts: bool, // Whether the last update() placed a shadow map
+    ground: bool, // Whether the last update() placed the ground plane
+}
+
+impl Shadows {
+    // The shadow pass reads the clip planes from `clip_buffer` (a ClipUniform, see section.rs)
+    pub fn new(device: &wgpu::Device, camera_bind_group_layout: &wgpu::BindGroupLayout, clip_buffer: &wgpu::Buffer, color_format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let map = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("shadow_map"),
+            size: wgpu::Extent3d { width: SHADOW_MAP_SIZE, height: SHADOW_MAP_SIZE, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = map.create_view(&wgpu::TextureViewDescriptor::default());
+        let map = Texture { texture: map, view };
+        // Linear filtering compares the four nearest texels, which softens the edges further
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let sun_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sun Buffer"),
+            contents: bytemuck::cast_slice(&[SunUniform::new(None, None)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sun_camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sun Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // Same bindings as the camera and clip uniforms of the camera bind group
+        let sun_camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[0, 5].map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }),
+            label: Some("sun_camera_bind_group_layout"),
+        });
+        let sun_camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &sun_camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: sun_camera_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 5, resource: clip_buffer.as_entire_binding() },
+            ],
+            label: Some("sun_camera_bind_group"),
+        });
+
+        // Depth only: the fragment stage just discards cut away and translucent fragments
+        let caster_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(include_str!("clipping.wgsl"), include_str!("transparency.wgsl"), include_str!("shader_shadow.wgsl")).into()),
+        });
+        let caster_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&sun_camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let caster_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&caster_layout),
+            vertex: wgpu::VertexState {
+                module: &caster_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &caster_shader,
+                entry_point: Some("fs_main"),
+                targets: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // Slope scaled bias against shadow acne on faces the sun grazes
+            depth_stencil: Some(wgpu::DepthStencilState {
+                bias: wgpu::DepthBiasState { constant: 2, slope_scale: 2.0, clamp: 0.0 },
+                ..Texture::depth_stencil_state()
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let ground_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Ground Shader"),
+            source: wgpu::ShaderSource::Wgsl(concat!(include_str!("lighting.wgsl"), include_str!("shader_ground.wgsl")).into()),
+        });
+        let ground_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Ground Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let ground_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Ground Pipeline"),
+            layout: Some(&ground_layout),
+            vertex: wgpu::VertexState {
+                module: &ground_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &ground_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleStrip, ..Default::default() },
+            depth_stencil: Some(Texture::depth_stencil_state()),
+            multisample: wgpu::MultisampleState { count: sample_count, mask: !0, alpha_to_coverage_enabled: false },
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            settings: SunSettings::default(),
+            map,
+            sampler,
+            sun_buffer,
+            caster_pipeline,
+            sun_camera_buffer,
+            sun_camera_bind_group,
+            ground_pipeline,
+            casts: false,
+            ground: false,
+        }
+    }
+
+    // Shadow map, comparison sampler and sun entries (bindings 3, 4 and 7) of the camera bind group
+    pub fn bind_group_entries(&self) -> [wgpu::BindGroupEntry<'_>; 3] {
+        [
+            wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&self.map.view) },
+            wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+            wgpu::BindGroupEntry { binding: 7, resource: self.sun_buffer.as_entire_binding() },
+        ]
+    }
+
+    pub fn settings(&self) -> SunSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: SunSettings) {
+        self.settings = settings;
+    }
+
+    // Place the sun, its shadow map and the ground plane around the visible model
+    pub fn update(&mut self, queue: &wgpu::Queue, bounds: Option<(Point3<f32>, Point3<f32>)>) {
+        let light = self.settings.light(bounds);
+        let ground = bounds.filter(|_| self.settings.enabled && self.settings.ground_plane).map(ground_plane);
+        let uniform = SunUniform::new(light.as_ref(), ground);
+        queue.write_buffer(&self.sun_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.casts = uniform.shadow[0] > 0.0;
+        self.ground = ground.is_some();
+        if self.casts {
+            let sun_camera = CameraUniform { view_proj: uniform.view_proj, ..CameraUniform::new() };
+            queue.write_buffer(&self.sun_camera_buffer, 0, bytemuck::cast_slice(&[sun_camera]));
+        }
+    }
+
+    // Render the opaque triangles into the shadow map; before the passes that read it
+    pub fn render_map(&self, encoder: &mut wgpu::CommandEncoder, scene: &Scene, skip_edged: bool) {
+        if !self.casts {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.map.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.caster_pipeline);
+        render_pass.set_bind_group(0, &self.sun_camera_bind_group, &[]);
+        scene.draw_triangles(&mut render_pass, DrawPass::Opaque, skip_edged);
+    }
+
+    // Ground plane, after the opaque model in the same pass
+    pub fn draw_ground(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if !self.ground {
+            return;
+        }
+        render_pass.set_pipeline(&self.ground_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+}
@@ -0,0 +1,399 @@
+// Geometry sources: which files, directories and URLs the viewer loads and how they reload.
+// Native builds read the configuration from the command line and TOML/JSON config files,
+// web builds from the page query string (?source=...&offline=1).
+use serde::{Deserialize, Serialize};
+use openmodel::AllGeometryData;
+use crate::texture::Texture;
+use crate::{fnv1a64, GEOMETRY_POLL_INTERVAL_MS, REMOTE_GEOMETRY_URL};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+pub const USAGE: &str = "\
+Usage: wink [OPTIONS] [SOURCE...]
+
+SOURCE is an openmodel JSON file, a directory (every *.json inside) or an http(s) URL.
+Without sources and --config the bundled sample and the remote demo file are shown.
+
+Options:
+  --config <FILE>   Read sources from a TOML or JSON config file
+  --offline         Ignore URL sources
+  --no-watch        Do not reload sources when they change
+  --poll-ms <MS>    Check URL sources for changes every MS milliseconds (default 1000)
+  --no-embedded     Show nothing instead of the bundled sample when no source loads
+  --stream <URL>    Show geometry pushed by a WebSocket endpoint, e.g. ws://localhost:9001
+  --msaa <N>        Anti-aliasing samples per pixel: 1 (off), 2, 4 (default), 8 or 16
+  -h, --help        Print this help";
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_msaa() -> u32 {
+    Texture::DEFAULT_SAMPLE_COUNT
+}
+
+// One geometry source
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    // File path, directory path or http(s) URL (on web: URL or path relative to the page)
+    pub path: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    // Reload on change: file watcher for native files, polling for URLs and everything on web
+    #[serde(default = "default_true")]
+    pub watch: bool,
+    // Polling interval for URLs (and for all sources on web); None uses GEOMETRY_POLL_INTERVAL_MS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_ms: Option<u64>,
+}
+
+impl Source {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into(), enabled: true, watch: true, poll_ms: None }
+    }
+
+    pub fn is_url(&self) -> bool {
+        self.path.starts_with("http://") || self.path.starts_with("https://")
+    }
+
+    pub fn poll_interval_ms(&self) -> u64 {
+        self.poll_ms.unwrap_or(GEOMETRY_POLL_INTERVAL_MS).max(1)
+    }
+}
+
+// Everything the viewer loads; the defaults reproduce the bundled demo setup
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub sources: Vec<Source>,
+    // Skip URL sources, e.g. on machines without network access
+    #[serde(default)]
+    pub offline: bool,
+    // Show the sample compiled into the binary when no source could be loaded
+    #[serde(default = "default_true")]
+    pub embedded_fallback: bool,
+    // WebSocket endpoint streaming geometry commands (see the stream module)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<String>,
+    // Requested MSAA samples per pixel; the viewer uses the highest count up to this the GPU supports
+    #[serde(default = "default_msaa")]
+    pub msaa: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sources: vec![
+                #[cfg(not(target_arch = "wasm32"))]
+                Source::new(crate::LOCAL_GEOMETRY_PATH),
+                #[cfg(target_arch = "wasm32")]
+                Source::new(crate::LOCAL_GEOMETRY_HTTP_PATH),
+                Source::new(REMOTE_GEOMETRY_URL),
+            ],
+            offline: false,
+            embedded_fallback: true,
+            stream: None,
+            msaa: Texture::DEFAULT_SAMPLE_COUNT,
+        }
+    }
+}
+
+impl Config {
+    // Sources that will actually be loaded
+    pub fn active_sources(&self) -> impl Iterator<Item = &Source> {
+        self.sources.iter().filter(|s| s.enabled && !(self.offline && s.is_url()))
+    }
+
+    // A streaming viewer starts empty rather than showing the sample
+    pub fn uses_embedded_fallback(&self) -> bool {
+        self.embedded_fallback && self.stream.is_none()
+    }
+
+    // Parse a config file body; TOML when the name ends in .toml, JSON otherwise
+    pub fn parse(text: &str, file_name: &str) -> anyhow::Result<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if file_name.ends_with(".toml") {
+            return Ok(toml::from_str(text)?);
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = file_name;
+        Ok(serde_json::from_str(text)?)
+    }
+
+    // Read a config file; relative source paths are resolved against the file's directory
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        use anyhow::Context;
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let mut config = Self::parse(&text, &path.to_string_lossy()).with_context(|| format!("parsing {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for source in config.sources.iter_mut().filter(|s| !s.is_url()) {
+            if Path::new(&source.path).is_relative() {
+                source.path = base.join(&source.path).to_string_lossy().into_owned();
+            }
+        }
+        Ok(config)
+    }
+
+    // Command line arguments (without the program name), see USAGE.
+    // Sources given on the command line are added after those of --config.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> anyhow::Result<Self> {
+        use anyhow::{anyhow, bail};
+        let mut config: Option<Config> = None;
+        let mut sources: Vec<Source> = Vec::new();
+        let (mut offline, mut no_watch, mut no_embedded) = (false, false, false);
+        let mut poll_ms: Option<u64> = None;
+        let mut stream: Option<String> = None;
+        let mut msaa: Option<u32> = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => {
+                    let file = args.next().ok_or_else(|| anyhow!("--config needs a file"))?;
+                    config = Some(Self::from_file(file)?);
+                }
+                "--offline" => offline = true,
+                "--no-watch" => no_watch = true,
+                "--no-embedded" => no_embedded = true,
+                "--stream" => stream = Some(args.next().ok_or_else(|| anyhow!("--stream needs a URL"))?),
+                "--msaa" => {
+                    let value = args.next().ok_or_else(|| anyhow!("--msaa needs a value"))?;
+                    msaa = Some(value.parse().ok().filter(|&n| n > 0).ok_or_else(|| anyhow!("invalid --msaa value '{}'", value))?);
+                }
+                "--poll-ms" => {
+                    let value = args.next().ok_or_else(|| anyhow!("--poll-ms needs a value"))?;
+                    poll_ms = Some(value.parse().map_err(|_| anyhow!("invalid --poll-ms value '{}'", value))?);
+                }
+                flag if flag.starts_with("--") => bail!("unknown option '{}'", flag),
+                path => sources.push(Source::new(path)),
+            }
+        }
+
+        // Defaults only apply when neither a config file, sources nor a stream were given
+        let mut config = match config {
+            Some(config) => config,
+            None if !sources.is_empty() || stream.is_some() => Config { sources: Vec::new(), ..Config::default() },
+            None => Config::default(),
+        };
+        config.sources.extend(sources);
+        for source in &mut config.sources {
+            source.watch &= !no_watch;
+            source.poll_ms = poll_ms.or(source.poll_ms);
+        }
+        config.offline |= offline;
+        config.embedded_fallback &= !no_embedded;
+        config.stream = stream.or(config.stream);
+        config.msaa = msaa.unwrap_or(config.msaa);
+        Ok(config)
+    }
+
+    // Web page query string, e.g. "?source=/geometry/a.json&source=https://...&offline=1&stream=ws://...&msaa=1".
+    // Without source and stream parameters the default sources are used.
+    pub fn from_query(query: &str) -> Self {
+        let mut config = Config::default();
+        let mut sources: Vec<Source> = Vec::new();
+        let (mut watch, mut poll_ms) = (true, None);
+        for pair in query.trim_start_matches('?').split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value);
+            let flag = !matches!(value.as_str(), "0" | "false" | "no");
+            match key {
+                "source" if !value.is_empty() => sources.push(Source::new(value)),
+                "offline" => config.offline = flag,
+                "watch" => watch = flag,
+                "embedded" => config.embedded_fallback = flag,
+                "poll" => poll_ms = value.parse().ok(),
+                "stream" if !value.is_empty() => config.stream = Some(value),
+                "msaa" => config.msaa = value.parse().unwrap_or(config.msaa).max(1),
+                _ => {}
+            }
+        }
+        if !sources.is_empty() || config.stream.is_some() {
+            config.sources = sources;
+        }
+        for source in &mut config.sources {
+            source.watch &= watch;
+            source.poll_ms = poll_ms.or(source.poll_ms);
+        }
+        config
+    }
+}
+
+// Decode %XX escapes and '+' of a query string value
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Parse the JSON of every loaded file/URL (label, text) in order, skipping exact duplicates
+// (e.g. the local copy of the remote file). Falls back to the embedded sample if nothing parsed.
+pub fn parse_sources(texts: &[(String, String)], embedded_fallback: bool) -> Vec<AllGeometryData> {
+    let mut sources = Vec::new();
+    let mut seen: Vec<u64> = Vec::new();
+    for (label, text) in texts {
+        let hash = fnv1a64(text.as_bytes());
+        if seen.contains(&hash) {
+            log::info!("Skipping {} (duplicate of an earlier source)", label);
+            continue;
+        }
+        seen.push(hash);
+        match serde_json::from_str::<AllGeometryData>(text) {
+            Ok(geometry) => sources.push(geometry),
+            Err(err) => log::warn!("Failed to parse {}: {}", label, err),
+        }
+    }
+    if sources.is_empty() && embedded_fallback {
+        if let Ok(geometry) = serde_json::from_str(include_str!("openmodel/all_geometry.json")) {
+            sources.push(geometry);
+        }
+    }
+    sources
+}
+
+// Native: loads the configured sources and keeps the last text of every URL,
+// so a local edit re-reads only the files and a URL change fetches only that URL
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Loader {
+    config: Config,
+    remote_texts: HashMap<String, String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Loader {
+    pub fn new(config: Config) -> Self {
+        Self { config, remote_texts: HashMap::new() }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // Read every file and fetch every URL (blocking)
+    pub fn load(&mut self) -> Vec<AllGeometryData> {
+        let urls: Vec<String> = self.config.active_sources().filter(|s| s.is_url()).map(|s| s.path.clone()).collect();
+        for url in urls {
+            self.refresh_url(&url);
+        }
+        self.reload()
+    }
+
+    // Re-read the files, reuse the last text of every URL
+    pub fn reload(&self) -> Vec<AllGeometryData> {
+        parse_sources(&self.texts(), self.config.uses_embedded_fallback())
+    }
+
+    // Hash of the current content of all file and directory sources, to skip saves that change nothing
+    pub fn local_hash(&self) -> u64 {
+        let mut bytes = Vec::new();
+        for (label, text) in self.texts().iter().filter(|(label, _)| !self.remote_texts.contains_key(label)) {
+            bytes.extend_from_slice(label.as_bytes());
+            bytes.extend_from_slice(text.as_bytes());
+        }
+        fnv1a64(&bytes)
+    }
+
+    fn texts(&self) -> Vec<(String, String)> {
+        let mut texts: Vec<(String, String)> = Vec::new();
+        for source in self.config.active_sources() {
+            if source.is_url() {
+                if let Some(text) = self.remote_texts.get(&source.path) {
+                    texts.push((source.path.clone(), text.clone()));
+                }
+            } else {
+                for file in json_files(Path::new(&source.path)) {
+                    match std::fs::read_to_string(&file) {
+                        Ok(text) => texts.push((file.display().to_string(), text)),
+                        Err(err) => log::warn!("Failed to read {}: {}", file.display(), err),
+                    }
+                }
+            }
+        }
+        texts
+    }
+
+    // Fetch one URL; true if its content changed since the last fetch
+    pub fn refresh_url(&mut self, url: &str) -> bool {
+        match fetch_url(url) {
+            Some(text) if self.remote_texts.get(url) != Some(&text) => {
+                self.remote_texts.insert(url.to_string(), text);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// A file source itself, or the *.json files of a directory source in name order
+#[cfg(not(target_arch = "wasm32"))]
+pub fn json_files(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+    let mut files: Vec<PathBuf> = std::fs::read_dir(path)
+        .map(|entries| entries.filter_map(|e| e.ok().map(|e| e.path())).filter(|p| has_json_extension(p) && p.is_file()).collect())
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn has_json_extension(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"))
+}
+
+// Native: GET a URL, None if the request fails (blocking, keep off the render thread)
+#[cfg(not(target_arch = "wasm32"))]
+fn fetch_url(url: &str) -> Option<String> {
+    match reqwest::blocking::get(url) {
+        Ok(resp) if resp.status().is_success() => match resp.text() {
+            Ok(text) => Some(text),
+            Err(err) => {
+                log::warn!("Failed reading {}: {}", url, err);
+                None
+            }
+        },
+        Ok(resp) => {
+            log::warn!("Fetching {} returned status {}", url, resp.status());
+            None
+        }
+        Err(err) => {
+            log::warn!("Fetching {} failed: {}", url, err);
+            None
+        }
+    }
+}
+
+// Web: fetch every active source (URLs or paths relative to the page)
+#[cfg(target_arch = "wasm32")]
+pub async fn fetch_source_texts(config: &Config) -> Vec<(String, String)> {
+    let mut texts = Vec::new();
+    for source in config.active_sources() {
+        match crate::fetch_text(&source.path).await {
+            Some(text) => texts.push((source.path.clone(), text)),
+            None => web_sys::console::warn_1(&format!("Failed to fetch {}", source.path).into()),
+        }
+    }
+    texts
+}
@@ -0,0 +1,175 @@
+// Live geometry streaming over WebSocket, e.g. from a parametric script in a design tool.
+// Every text (or UTF-8 binary) message is one JSON command:
+//   {"type": "scene",  "geometry": {...}}   replace all streamed objects
+//   {"type": "add",    "geometry": {...}}   insert objects, replacing those with the same guid
+//   {"type": "update", "geometry": {...}}   same as add, so senders need not track what exists
+//   {"type": "remove", "guids": ["..."]}    remove objects by guid
+//   {"type": "clear"}                        remove all streamed objects
+//   {"type": "annotations", "annotations": [{"text": "...", "position": [x, y, z]}]}   replace the annotations
+// A bare openmodel JSON document (no "type") is a full scene, so files can be sent unchanged.
+// Streamed objects are drawn on top of the loaded sources and reset when the viewer restarts.
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use openmodel::AllGeometryData;
+use winit::event_loop::EventLoopProxy;
+use crate::geometry::GeometryObject;
+use crate::labels::Annotation;
+use crate::UserEvent;
+
+// Wait between connection attempts, so the viewer can start before the design tool
+const RECONNECT_DELAY_MS: u64 = 1000;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamMessage {
+    Scene { geometry: AllGeometryData },
+    Add { geometry: AllGeometryData },
+    Update { geometry: AllGeometryData },
+    Remove { guids: Vec<String> },
+    Clear,
+    Annotations { annotations: Vec<Annotation> },
+}
+
+impl StreamMessage {
+    pub fn parse(text: &str) -> serde_json::Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        if value.get("type").is_some() {
+            serde_json::from_value(value)
+        } else {
+            Ok(StreamMessage::Scene { geometry: serde_json::from_value(value)? })
+        }
+    }
+
+    // Apply the command to the streamed objects.
+    // Only drawable objects carry a guid; vectors, colors and xforms are kept from full scenes only.
+    pub fn apply(self, streamed: &mut AllGeometryData) {
+        match self {
+            StreamMessage::Scene { geometry } => *streamed = geometry,
+            StreamMessage::Add { geometry } | StreamMessage::Update { geometry } => {
+                // Remove first so an object may also change its type, e.g. from a line to a pline
+                let guids: HashSet<String> = GeometryObject::all(&geometry).iter().map(|o| o.data().guid().to_string()).collect();
+                remove_guids(streamed, &guids);
+                macro_rules! append {
+                    ($($list:ident),*) => { $(streamed.$list.extend(geometry.$list);)* };
+                }
+                append!(meshes, points, point_clouds, lines, plines, line_clouds, planes);
+            }
+            StreamMessage::Remove { guids } => remove_guids(streamed, &guids.into_iter().collect()),
+            StreamMessage::Clear => *streamed = AllGeometryData::default(),
+            StreamMessage::Annotations { .. } => {} // Labels, not geometry: see State::apply_stream
+        }
+    }
+}
+
+fn remove_guids(geometry: &mut AllGeometryData, guids: &HashSet<String>) {
+    macro_rules! retain {
+        ($($list:ident),*) => { $(geometry.$list.retain(|o| !guids.contains(&o.data.guid().to_string()));)* };
+    }
+    retain!(meshes, points, point_clouds, lines, plines, line_clouds, planes);
+}
+
+// Native: connect and call on_message for every command until the connection closes or
+// on_message returns false. Blocking; plain ws:// only, the endpoint is expected to be local.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn receive(url: &str, mut on_message: impl FnMut(StreamMessage) -> bool) -> anyhow::Result<()> {
+    use tungstenite::Message;
+    let (mut socket, _) = tungstenite::connect(url)?;
+    log::info!("Streaming geometry from {}", url);
+    loop {
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text.to_string(),
+            Ok(Message::Binary(bytes)) => String::from_utf8_lossy(&bytes).into_owned(),
+            Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            // Pings are answered by tungstenite on the next read
+            Ok(_) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        match StreamMessage::parse(&text) {
+            Ok(message) => {
+                if !on_message(message) {
+                    let _ = socket.close(None);
+                    return Ok(());
+                }
+            }
+            Err(err) => log::warn!("Invalid stream message: {}", err),
+        }
+    }
+}
+
+// Native: receive on a background thread and forward commands to the event loop,
+// reconnecting after the endpoint goes away. The thread ends when the event loop is gone.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn spawn_stream_client(proxy: EventLoopProxy<UserEvent>, url: String) -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("wink-stream-client".into())
+        .spawn(move || {
+            let mut event_loop_alive = true;
+            let mut reported_failure = false;
+            while event_loop_alive {
+                let result = receive(&url, |message| {
+                    event_loop_alive = proxy.send_event(UserEvent::Stream(Box::new(message))).is_ok();
+                    reported_failure = false;
+                    event_loop_alive
+                });
+                // Report a failing endpoint once instead of on every retry
+                match result {
+                    Ok(()) => log::info!("Stream {} closed", url),
+                    Err(err) if !reported_failure => {
+                        log::warn!("Stream {} unavailable, retrying: {}", url, err);
+                        reported_failure = true;
+                    }
+                    Err(_) => {}
+                }
+                std::thread::sleep(std::time::Duration::from_millis(RECONNECT_DELAY_MS));
+            }
+        })?;
+    Ok(())
+}
+
+// Web: connect with the browser WebSocket and forward commands to the event loop,
+// reconnecting after the connection closes
+#[cfg(target_arch = "wasm32")]
+pub fn connect_web(proxy: EventLoopProxy<UserEvent>, url: String) {
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    let socket = match web_sys::WebSocket::new(&url) {
+        Ok(socket) => socket,
+        Err(err) => {
+            web_sys::console::error_2(&format!("Stream {} unavailable:", url).into(), &err);
+            return;
+        }
+    };
+    // Binary messages arrive as ArrayBuffer instead of Blob, so they can be read synchronously
+    socket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+
+    let message_proxy = proxy.clone();
+    let on_message = Closure::<dyn FnMut(web_sys::MessageEvent)>::new(move |event: web_sys::MessageEvent| {
+        let data = event.data();
+        let text = match data.as_string() {
+            Some(text) => text,
+            None => String::from_utf8_lossy(&web_sys::js_sys::Uint8Array::new(&data).to_vec()).into_owned(),
+        };
+        match StreamMessage::parse(&text) {
+            Ok(message) => {
+                let _ = message_proxy.send_event(UserEvent::Stream(Box::new(message)));
+            }
+            Err(err) => web_sys::console::warn_1(&format!("Invalid stream message: {}", err).into()),
+        }
+    });
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+    // The close handler runs once per connection and owns the message handler, so both are
+    // freed when the connection ends instead of leaking a set for every reconnect
+    let closed_socket = socket.clone();
+    let on_close = Closure::once_into_js(move || {
+        closed_socket.set_onmessage(None);
+        closed_socket.set_onclose(None);
+        drop(on_message);
+        web_sys::console::log_1(&format!("Stream {} closed, reconnecting", url).into());
+        let reconnect = Closure::once_into_js(move || connect_web(proxy, url));
+        if let Some(window) = web_sys::window() {
+            let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(reconnect.unchecked_ref(), RECONNECT_DELAY_MS as i32);
+        }
+    });
+    socket.set_onclose(Some(on_close.unchecked_ref()));
+}
@@ -0,0 +1,150 @@
+// GPU textures owned by the viewer (render attachments and generated lookup images, not image assets)
+pub struct Texture {
+    #[allow(unused)]
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl Texture {
+    // Depth format shared by the depth attachment and every pipeline that tests against it
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    // MSAA samples per pixel requested by default; 4 is supported by every WebGPU/WebGL2 device
+    pub const DEFAULT_SAMPLE_COUNT: u32 = 4;
+
+    // Features of a format as device validation sees them: the adapter's own with
+    // TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES or on downlevel backends (e.g. WebGL),
+    // otherwise only what WebGPU guarantees for every device
+    pub fn format_features(adapter: &wgpu::Adapter, device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::TextureFormatFeatures {
+        let adapter_specific = device.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        let downlevel = !adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::WEBGPU_TEXTURE_FORMAT_SUPPORT);
+        if adapter_specific || downlevel {
+            adapter.get_texture_format_features(format)
+        } else {
+            format.guaranteed_format_features(device.features())
+        }
+    }
+
+    // Whether a color attachment of this format can be drawn into (and blended if `blendable`)
+    // with `sample_count` samples, resolving into a single sample texture when multisampled.
+    // Check every set of attachments created with the scene's sample count.
+    pub fn supports_attachment(adapter: &wgpu::Adapter, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, blendable: bool) -> bool {
+        let features = Self::format_features(adapter, device, format);
+        let flags = features.flags;
+        features.allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+            && (!blendable || flags.contains(wgpu::TextureFormatFeatureFlags::BLENDABLE))
+            && (sample_count == 1 || (flags.sample_count_supported(sample_count) && flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)))
+    }
+
+    // Highest sample count up to `requested` that every attachment format of the pass supports,
+    // e.g. the color target and DEPTH_FORMAT. Without TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+    // on a WebGPU device only 1 and 4 are guaranteed.
+    pub fn supported_sample_count(adapter: &wgpu::Adapter, device: &wgpu::Device, formats: &[wgpu::TextureFormat], requested: u32) -> u32 {
+        let flags: Vec<wgpu::TextureFormatFeatureFlags> = formats.iter().map(|&format| Self::format_features(adapter, device, format).flags).collect();
+        [16, 8, 4, 2]
+            .into_iter()
+            .filter(|&count| count <= requested)
+            .find(|&count| flags.iter().all(|flags| flags.sample_count_supported(count)))
+            .unwrap_or(1)
+    }
+
+    // Create a depth texture matching the surface size.
+    // The texture has to be recreated whenever the surface is resized,
+    // because the depth attachment must have the same size as the color attachment.
+    // The sample count must match the color attachment and the pipelines.
+    pub fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32, sample_count: u32, label: &str) -> Self {
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            // RENDER_ATTACHMENT to write depth, TEXTURE_BINDING so later passes could sample it.
+            // Multisampled depth is attachment only: GL cannot combine a multisampled depth
+            // texture with the multisampled color renderbuffer in one framebuffer.
+            usage: if sample_count > 1 {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+            },
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&desc);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self { texture, view }
+    }
+
+    // Color attachment that is not presented: the multisampled image resolved into the surface
+    // (or headless target) and the transparency buffers. Single sampled ones can be read by
+    // later passes, multisampled ones are only resolved.
+    pub fn create_color_texture(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32, sample_count: u32, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: if sample_count > 1 {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+            },
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self { texture, view }
+    }
+
+    // Sampled texture written from the CPU with queue.write_texture (colormap, legend)
+    pub fn create_image_texture(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self { texture, view }
+    }
+
+    // Depth state used by the opaque pipelines: closer fragments win (Less) and write depth
+    pub fn depth_stencil_state() -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: Self::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+    // Depth state of the transparent pass: hidden behind opaque surfaces, but blended
+    // fragments do not write depth, so they never hide each other
+    pub fn depth_read_only_state() -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            depth_write_enabled: false,
+            ..Self::depth_stencil_state()
+        }
+    }
+}
@@ -0,0 +1,22 @@
+// Weighted blended order-independent transparency (McGuire and Bavoil 2013).
+// Prepended after lighting.wgsl to shader_solid.wgsl, shader_color.wgsl and shader_point.wgsl:
+// their fs_transparent entry points accumulate every fragment with alpha below OPAQUE_ALPHA,
+// shader_composite.wgsl blends the result over the opaque image.
+// Adapters that cannot draw or blend the accumulation formats use their fs_blended entry points instead.
+
+// Alpha at or above this is drawn by the opaque pass (same as OPAQUE_ALPHA in geometry.rs)
+const OPAQUE_ALPHA: f32 = 0.999;
+
+struct TransparentOutput {
+    @location(0) accum: vec4<f32>, // Weighted premultiplied color and alpha, added up
+    @location(1) revealage: f32,   // Multiplied by (1 - alpha) of every fragment
+};
+
+// Closer fragments (depth 0..1) get a larger weight, so they dominate the average
+fn transparent_output(color: vec3<f32>, alpha: f32, depth: f32) -> TransparentOutput {
+    let weight = alpha * clamp(3e3 * pow(1.0 - depth, 3.0), 1e-2, 3e3);
+    var out: TransparentOutput;
+    out.accum = vec4<f32>(color * alpha, alpha) * weight;
+    out.revealage = alpha;
+    return out;
+}
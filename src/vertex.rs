@@ -1,28 +1,174 @@
-
+use cgmath::{Matrix, Matrix3, Matrix4, SquareMatrix};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub position: [f32; 3],
-    pub color: [f32; 3],
+    pub color: [f32; 4], // RGBA, alpha below 1 draws in the transparent pass
+    pub normal: [f32; 3], // Smooth vertex normal, zero when unknown (the shader falls back to the face normal)
+    pub value: [f32; 2], // Attribute colored by (see colormap.rs) and 1 if the vertex has it, else zeros
 }
 
 impl Vertex {
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress, // How wide is a vertex in bytes 24
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress, // How wide is a vertex in bytes 48
             step_mode: wgpu::VertexStepMode::Vertex, // Vertex data or pre-instance data
             attributes: &[  // mapping to the struct attributes
                 wgpu::VertexAttribute {
                     offset: 0, // offset in bytes until the attribute starts
-                    shader_location: 0, // location in the shader 0 - position, 1 - color
+                    shader_location: 0, // location in the shader 0 - position, 1 - color, 2 - normal, 3 - attribute value
                     format: wgpu::VertexFormat::Float32x3, // same as vec3<f32>
                 },
                 wgpu::VertexAttribute {
                     offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
                     shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 7]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+            ]
+        }
+    }
+}
+
+// Per-instance data of a screen-space point sprite (one quad per point)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointInstance {
+    pub position: [f32; 3],
+    pub color: [f32; 4], // RGBA, alpha below 1 draws in the transparent pass
+    pub size: f32, // Sprite diameter in pixels
+}
+
+impl PointInstance {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<PointInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance, // One instance per point, the quad corners come from vertex_index
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 7]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ]
+        }
+    }
+}
+
+// One end of a mesh edge drawn by the edge pipeline (LineList: two vertices per edge)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EdgeVertex {
+    pub position: [f32; 3],
+    pub boundary: f32, // 1.0 if the edge has a face on one side only
+    pub angle: f32,    // Angle between the normals of the two faces in degrees (0 for flat and boundary edges)
+}
+
+impl EdgeVertex {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<EdgeVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x3 },
+                wgpu::VertexAttribute { offset: 12, shader_location: 1, format: wgpu::VertexFormat::Float32 },
+                wgpu::VertexAttribute { offset: 16, shader_location: 2, format: wgpu::VertexFormat::Float32 },
+            ]
+        }
+    }
+}
+
+// One character of a label, already placed on screen (see labels.rs)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GlyphInstance {
+    pub rect: [f32; 4], // Left, top, width and height in pixels from the top left of the target
+    pub color: [f32; 4], // Text color, alpha multiplies the glyph coverage
+    pub glyph: u32, // Atlas cell, the character code minus labels::FIRST_CHAR
+    pub depth: f32, // Depth of the label anchor, tested when labels are hidden behind the model
+}
+
+impl GlyphInstance {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GlyphInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance, // One quad per glyph, the corners come from vertex_index
+            attributes: &[
+                wgpu::VertexAttribute { offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x4 },
+                wgpu::VertexAttribute { offset: 16, shader_location: 1, format: wgpu::VertexFormat::Float32x4 },
+                wgpu::VertexAttribute { offset: 32, shader_location: 2, format: wgpu::VertexFormat::Uint32 },
+                wgpu::VertexAttribute { offset: 36, shader_location: 3, format: wgpu::VertexFormat::Float32 },
+            ]
+        }
+    }
+}
+
+// Per-instance model and normal matrices of a mesh drawn with instancing.
+// Matrices are passed as columns because a vertex attribute can be at most a vec4.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    pub model: [[f32; 4]; 4],
+    pub normal: [[f32; 3]; 3], // Inverse transpose of the upper 3x3, keeps normals correct under non-uniform scale
+    pub highlight: f32, // 1.0 for the selected object, 0.0 otherwise
+    pub opacity: f32, // Multiplies the vertex alpha (object opacity, x-ray ghosting)
+    pub closed: f32, // 1.0 for closed meshes, which get a section cap where they are cut
+}
+
+impl InstanceRaw {
+    pub fn new(model: Matrix4<f32>, highlight: bool, opacity: f32, closed: bool) -> Self {
+        let linear = Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate());
+        let normal = linear.invert().map_or(Matrix3::identity(), |inverse| inverse.transpose());
+        Self {
+            model: model.into(),
+            normal: normal.into(),
+            highlight: if highlight { 1.0 } else { 0.0 },
+            opacity,
+            closed: if closed { 1.0 } else { 0.0 },
+        }
+    }
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            // The shader only steps to the next instance when it starts a new instance
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                // Locations 5-8: model matrix columns (locations 0-4 are reserved for per-vertex data)
+                wgpu::VertexAttribute { offset: 0, shader_location: 5, format: wgpu::VertexFormat::Float32x4 },
+                wgpu::VertexAttribute { offset: 16, shader_location: 6, format: wgpu::VertexFormat::Float32x4 },
+                wgpu::VertexAttribute { offset: 32, shader_location: 7, format: wgpu::VertexFormat::Float32x4 },
+                wgpu::VertexAttribute { offset: 48, shader_location: 8, format: wgpu::VertexFormat::Float32x4 },
+                // Locations 9-11: normal matrix columns
+                wgpu::VertexAttribute { offset: 64, shader_location: 9, format: wgpu::VertexFormat::Float32x3 },
+                wgpu::VertexAttribute { offset: 76, shader_location: 10, format: wgpu::VertexFormat::Float32x3 },
+                wgpu::VertexAttribute { offset: 88, shader_location: 11, format: wgpu::VertexFormat::Float32x3 },
+                // Location 12: selection highlight
+                wgpu::VertexAttribute { offset: 100, shader_location: 12, format: wgpu::VertexFormat::Float32 },
+                // Location 13: object opacity
+                wgpu::VertexAttribute { offset: 104, shader_location: 13, format: wgpu::VertexFormat::Float32 },
+                // Location 14: closed mesh, capped by a section
+                wgpu::VertexAttribute { offset: 108, shader_location: 14, format: wgpu::VertexFormat::Float32 },
             ]
         }
     }
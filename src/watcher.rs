@@ -0,0 +1,179 @@
+// Native hot reload, off the render thread.
+// A file system watcher reports edits of watched file and directory sources immediately, and
+// every watched URL is checked with a HEAD request at its own poll interval. Whenever any of them
+// changes the sources are parsed on this thread and sent to the event loop as UserEvent::GeometryLoaded.
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use notify::{RecursiveMode, Watcher};
+use openmodel::AllGeometryData;
+use winit::event_loop::EventLoopProxy;
+use crate::sources::Loader;
+use crate::UserEvent;
+
+// Editors often write a file in several steps (truncate, write, rename); wait until it settles
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+// A watched URL and when to check it next.
+// The tag starts from a HEAD request next to the startup load, and the loader still holds the
+// startup text, so an unchanged URL is neither fetched nor reloaded on the first poll.
+struct UrlPoll {
+    url: String,
+    interval: Duration,
+    next_check: Instant,
+    tag: Option<String>,
+}
+
+// Start watching; the thread ends when the event loop is gone or nothing is watched
+pub fn spawn_geometry_watcher(proxy: EventLoopProxy<UserEvent>, loader: Loader) -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("wink-geometry-watcher".into())
+        .spawn(move || {
+            // Sending fails once the event loop is gone, which ends the thread
+            let send = |sources| proxy.send_event(UserEvent::GeometryLoaded(sources)).is_ok();
+            if let Err(err) = watch(loader, send) {
+                log::error!("Geometry watcher stopped: {:#}", err);
+            }
+        })?;
+    Ok(())
+}
+
+// Blocks until send returns false or the file watcher goes away
+fn watch(mut loader: Loader, mut send: impl FnMut(Vec<AllGeometryData>) -> bool) -> anyhow::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = sender.send(event);
+    })?;
+
+    // Watch directories rather than files: saving by rename replaces the file and would end a
+    // watch on the file itself. Paths are canonical so they compare equal to the event paths.
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    let mut watched: Vec<PathBuf> = Vec::new();
+    let mut polls: Vec<UrlPoll> = Vec::new();
+    for source in loader.config().active_sources().filter(|s| s.watch) {
+        if source.is_url() {
+            let interval = Duration::from_millis(source.poll_interval_ms());
+            polls.push(UrlPoll {
+                url: source.path.clone(),
+                interval,
+                next_check: Instant::now() + interval,
+                tag: remote_change_tag(&source.path),
+            });
+            continue;
+        }
+        let path = Path::new(&source.path);
+        let (dir, file) = if path.is_dir() {
+            (path.to_path_buf(), None)
+        } else {
+            match (path.parent().map(|p| if p.as_os_str().is_empty() { Path::new(".") } else { p }), path.file_name()) {
+                (Some(dir), Some(name)) if dir.is_dir() => (dir.to_path_buf(), Some(name)),
+                _ => {
+                    log::warn!("Not watching {}: directory does not exist", path.display());
+                    continue;
+                }
+            }
+        };
+        // One directory that cannot be watched should not stop the others or the URL polls
+        let dir = match dir.canonicalize() {
+            Ok(dir) => dir,
+            Err(err) => {
+                log::warn!("Not watching {}: {}", dir.display(), err);
+                continue;
+            }
+        };
+        if !watched.contains(&dir) {
+            if let Err(err) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                log::warn!("Not watching {}: {}", dir.display(), err);
+                continue;
+            }
+            watched.push(dir.clone());
+        }
+        let file = file.map(|name| dir.join(name));
+        match file {
+            Some(file) => files.push(file),
+            None => dirs.push(dir),
+        }
+    }
+    if watched.is_empty() && polls.is_empty() {
+        log::info!("No geometry sources to watch");
+        return Ok(());
+    }
+    let is_watched = |path: &Path| {
+        files.iter().any(|f| f == path)
+            || (crate::sources::has_json_extension(path) && path.parent().is_some_and(|p| dirs.iter().any(|d| d == p)))
+    };
+
+    // Content the viewer was started with, so unchanged saves and HEADs do not reload
+    let mut local_hash = loader.local_hash();
+
+    loop {
+        // Without URLs to poll, wait for file events only
+        let timeout = polls
+            .iter()
+            .map(|p| p.next_check)
+            .min()
+            .map(|next| next.saturating_duration_since(Instant::now()));
+        let event = match timeout {
+            Some(timeout) => receiver.recv_timeout(timeout),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let mut changed = false;
+
+        match event {
+            Ok(Ok(event)) => {
+                if event.paths.iter().any(|p| is_watched(p)) {
+                    // Collect the rest of this save before reading the files
+                    while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+                    let hash = loader.local_hash();
+                    if hash != local_hash {
+                        local_hash = hash;
+                        changed = true;
+                    }
+                }
+            }
+            Ok(Err(err)) => log::warn!("File watcher error: {}", err),
+            Err(RecvTimeoutError::Timeout) => {
+                let now = Instant::now();
+                for poll in polls.iter_mut().filter(|p| p.next_check <= now) {
+                    poll.next_check = now + poll.interval;
+                    let tag = remote_change_tag(&poll.url);
+                    if tag.is_some() && tag != poll.tag {
+                        poll.tag = tag;
+                        changed |= loader.refresh_url(&poll.url);
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        if changed && !send(loader.reload()) {
+            return Ok(());
+        }
+    }
+}
+
+// Remote HEAD ETag/Last-Modified (or length) to detect changes without downloading the file
+fn remote_change_tag(url: &str) -> Option<String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_millis(2000))
+        .build()
+        .ok()?;
+    let resp = client.head(url).send().ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    let headers = resp.headers();
+    headers
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| format!("etag:{}", s))
+        .or_else(|| headers
+            .get(reqwest::header::LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| format!("lm:{}", s)))
+        .or_else(|| headers
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| format!("len:{}", s)))
+}
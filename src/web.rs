@@ -0,0 +1,240 @@
+// JavaScript API of the WASM build, so the viewer can be embedded as a component.
+// Every function hands its request to the event loop through the EventLoopProxy, like the
+// geometry watcher and the stream client do; requests made before the canvas is ready are
+// applied once it is.
+//
+//   import init, { load_geometry, set_view, set_visible, on_pick } from './wink.js';
+//   await init();                           // starts the viewer (run_web)
+//   load_geometry(await (await fetch('/model.json')).text());
+//   set_view('top');
+//   on_pick(pick => console.log(pick?.guid));
+use std::cell::RefCell;
+use std::str::FromStr;
+use cgmath::Point3;
+use openmodel::AllGeometryData;
+use openmodel::geometry::Plane;
+use wasm_bindgen::prelude::*;
+use winit::event_loop::EventLoopProxy;
+use crate::camera::{NamedView, Projection};
+use crate::colormap::AttributeColoring;
+use crate::labels::{parse_annotations, LabelSettings};
+use crate::measure::{MeasureTool, Measurements};
+use crate::overlay::OverlaySettings;
+use crate::post::PostSettings;
+use crate::shadow::SunSettings;
+use crate::stream::StreamMessage;
+use crate::{UserEvent, ViewerCommand};
+
+thread_local! {
+    static PROXY: RefCell<Option<EventLoopProxy<UserEvent>>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn set_proxy(proxy: EventLoopProxy<UserEvent>) {
+    PROXY.with(|p| *p.borrow_mut() = Some(proxy));
+}
+
+fn send(event: UserEvent) -> Result<(), JsValue> {
+    PROXY.with(|p| match p.borrow().as_ref() {
+        Some(proxy) => proxy.send_event(event).map_err(|_| JsValue::from_str("wink viewer has stopped")),
+        None => Err(JsValue::from_str("wink viewer is not running, call run_web first")),
+    })
+}
+
+fn parse_geometry(text: &str) -> Result<AllGeometryData, JsValue> {
+    serde_json::from_str(text).map_err(|err| JsValue::from_str(&format!("invalid openmodel JSON: {}", err)))
+}
+
+// Show an openmodel JSON document, replacing geometry loaded through this API or streamed
+#[wasm_bindgen]
+pub fn load_geometry(json: &str) -> Result<(), JsValue> {
+    let geometry = parse_geometry(json)?;
+    send(UserEvent::Stream(Box::new(StreamMessage::Scene { geometry })))
+}
+
+// Same as load_geometry for UTF-8 bytes, e.g. a Uint8Array from a file input
+#[wasm_bindgen]
+pub fn load_geometry_bytes(bytes: &[u8]) -> Result<(), JsValue> {
+    let text = std::str::from_utf8(bytes).map_err(|err| JsValue::from_str(&format!("geometry is not UTF-8: {}", err)))?;
+    load_geometry(text)
+}
+
+// Apply a stream command (add/update/remove by guid, see the stream module)
+#[wasm_bindgen]
+pub fn apply_command(json: &str) -> Result<(), JsValue> {
+    let message = StreamMessage::parse(json).map_err(|err| JsValue::from_str(&format!("invalid command: {}", err)))?;
+    send(UserEvent::Stream(Box::new(message)))
+}
+
+// Remove all geometry, including the sources loaded at startup
+#[wasm_bindgen]
+pub fn clear_scene() -> Result<(), JsValue> {
+    send(UserEvent::Command(ViewerCommand::Clear))
+}
+
+// "top", "bottom", "front", "back", "left", "right" or "iso"
+#[wasm_bindgen]
+pub fn set_view(name: &str) -> Result<(), JsValue> {
+    let view = NamedView::from_str(name).map_err(|err| JsValue::from_str(&err))?;
+    send(UserEvent::Command(ViewerCommand::SetView(view)))
+}
+
+#[wasm_bindgen]
+pub fn set_orthographic(orthographic: bool) -> Result<(), JsValue> {
+    let projection = if orthographic { Projection::Orthographic } else { Projection::Perspective };
+    send(UserEvent::Command(ViewerCommand::SetProjection(projection)))
+}
+
+#[wasm_bindgen]
+pub fn frame_all() -> Result<(), JsValue> {
+    send(UserEvent::Command(ViewerCommand::FrameAll))
+}
+
+#[wasm_bindgen]
+pub fn set_visible(guid: &str, visible: bool) -> Result<(), JsValue> {
+    send(UserEvent::Command(ViewerCommand::SetVisible { guid: guid.to_string(), visible }))
+}
+
+// 0 (invisible) to 1 (opaque); translucent objects are blended order-independently
+#[wasm_bindgen]
+pub fn set_opacity(guid: &str, opacity: f32) -> Result<(), JsValue> {
+    send(UserEvent::Command(ViewerCommand::SetOpacity { guid: guid.to_string(), opacity }))
+}
+
+// Ghost all objects but the picked one and show hidden edges
+#[wasm_bindgen]
+pub fn set_xray(xray: bool) -> Result<(), JsValue> {
+    send(UserEvent::Command(ViewerCommand::SetXray(xray)))
+}
+
+// JSON array of openmodel planes, as in AllGeometryData.planes; each cuts away the side its
+// z-axis points to. An empty array removes them; section planes of the sources stay.
+#[wasm_bindgen]
+pub fn set_clip_planes(json: &str) -> Result<(), JsValue> {
+    let planes: Vec<Plane> = serde_json::from_str(json).map_err(|err| JsValue::from_str(&format!("invalid planes: {}", err)))?;
+    send(UserEvent::Command(ViewerCommand::SetClipPlanes(planes)))
+}
+
+// [min_x, min_y, min_z, max_x, max_y, max_z] keeps only what is inside, null turns it off
+#[wasm_bindgen]
+pub fn set_section_box(bounds: Option<Vec<f32>>) -> Result<(), JsValue> {
+    let bounds = match bounds.as_deref() {
+        None => None,
+        Some(&[x0, y0, z0, x1, y1, z1]) => Some((Point3::new(x0, y0, z0), Point3::new(x1, y1, z1))),
+        Some(_) => return Err(JsValue::from_str("section box needs 6 numbers: min x, y, z and max x, y, z")),
+    };
+    send(UserEvent::Command(ViewerCommand::SetSectionBox(bounds)))
+}
+
+// [r, g, b] from 0 to 1 fills cut solids, null leaves them open
+#[wasm_bindgen]
+pub fn set_section_fill(color: Option<Vec<f32>>) -> Result<(), JsValue> {
+    let fill = match color.as_deref() {
+        None => None,
+        Some(&[r, g, b]) => Some([r, g, b]),
+        Some(_) => return Err(JsValue::from_str("section fill needs 3 numbers: r, g, b")),
+    };
+    send(UserEvent::Command(ViewerCommand::SetSectionFill(fill)))
+}
+
+// JSON overlay settings, missing fields take their defaults:
+// {"visible": true, "grid": true, "axes": true, "gizmo": true, "minor_spacing": 1, "major_spacing": 10, "adaptive": true}
+#[wasm_bindgen]
+pub fn set_overlay(json: &str) -> Result<(), JsValue> {
+    let settings: OverlaySettings = serde_json::from_str(json).map_err(|err| JsValue::from_str(&format!("invalid overlay: {}", err)))?;
+    send(UserEvent::Command(ViewerCommand::SetOverlay(settings)))
+}
+
+// Color meshes by an attribute, null shows their own colors again. JSON with the attribute name,
+// the rest optional: {"attribute": "stress", "source": "vertex" | "face",
+// "colormap": "viridis" | "diverging" | "stepped", "range": [min, max]}; without a range the
+// colormap spans the loaded values
+#[wasm_bindgen]
+pub fn set_attribute_coloring(json: Option<String>) -> Result<(), JsValue> {
+    let coloring = match json {
+        Some(json) => Some(serde_json::from_str::<AttributeColoring>(&json).map_err(|err| JsValue::from_str(&format!("invalid attribute coloring: {}", err)))?),
+        None => None,
+    };
+    send(UserEvent::Command(ViewerCommand::SetAttributeColoring(coloring)))
+}
+
+// JSON label settings, missing fields take their defaults:
+// {"visible": true, "names": false, "vertex_keys": false, "values": false, "annotations": true,
+// "occlusion": "always" | "hidden" | "faded", "declutter": true, "scale": 2}
+#[wasm_bindgen]
+pub fn set_labels(json: &str) -> Result<(), JsValue> {
+    let settings: LabelSettings = serde_json::from_str(json).map_err(|err| JsValue::from_str(&format!("invalid labels: {}", err)))?;
+    send(UserEvent::Command(ViewerCommand::SetLabels(settings)))
+}
+
+// Replace the annotations: a JSON array (or {"annotations": [...]}) of
+// {"text": "Column C3", "position": [x, y, z], "color": [r, g, b]}, color optional
+#[wasm_bindgen]
+pub fn set_annotations(json: &str) -> Result<(), JsValue> {
+    let annotations = parse_annotations(json).map_err(|err| JsValue::from_str(&format!("invalid annotations: {}", err)))?;
+    send(UserEvent::Command(ViewerCommand::SetAnnotations(annotations)))
+}
+
+// Call the function with the picked object (guid, name, dtype, face/vertex keys, position,
+// attributes) or null when a click hits nothing. Replaces the previous subscriber.
+#[wasm_bindgen]
+pub fn on_pick(callback: web_sys::js_sys::Function) -> Result<(), JsValue> {
+    let forward = move |result: Option<&crate::picking::PickResult>| {
+        let value = match result.map(serde_json::to_string) {
+            Some(Ok(json)) => web_sys::js_sys::JSON::parse(&json).unwrap_or(JsValue::NULL),
+            _ => JsValue::NULL,
+        };
+        if let Err(err) = callback.call1(&JsValue::NULL, &value) {
+            web_sys::console::error_2(&"Pick callback failed:".into(), &err);
+        }
+    };
+    send(UserEvent::Command(ViewerCommand::SetPickCallback(Box::new(forward))))
+}
+
+// "distance", "angle" or "area": left clicks measure instead of selecting; null selects again
+#[wasm_bindgen]
+pub fn set_measure_tool(name: Option<String>) -> Result<(), JsValue> {
+    let tool = match name {
+        Some(name) => Some(MeasureTool::from_str(&name).map_err(|err| JsValue::from_str(&err))?),
+        None => None,
+    };
+    send(UserEvent::Command(ViewerCommand::SetMeasureTool(tool)))
+}
+
+#[wasm_bindgen]
+pub fn clear_measurements() -> Result<(), JsValue> {
+    send(UserEvent::Command(ViewerCommand::ClearMeasurements))
+}
+
+// JSON sun settings, missing fields take their defaults:
+// {"enabled": true, "azimuth": 135, "altitude": 45, "strength": 0.6, "shadows": true, "ground_plane": true}.
+// Azimuth in degrees clockwise from north (+Y); a date replaces azimuth and altitude:
+// "date": {"month": 6, "day": 21, "hour": 15.5, "latitude": 47.4} with the local solar time
+#[wasm_bindgen]
+pub fn set_sun(json: &str) -> Result<(), JsValue> {
+    let settings: SunSettings = serde_json::from_str(json).map_err(|err| JsValue::from_str(&format!("invalid sun: {}", err)))?;
+    send(UserEvent::Command(ViewerCommand::SetSun(settings)))
+}
+
+// JSON post-processing settings, missing fields take their defaults:
+// {"ambient_occlusion": true, "occlusion_radius": 0.05, "occlusion_strength": 1, "silhouettes": true,
+// "silhouette_color": [0.1, 0.1, 0.1], "crease_angle": 30}; the radius is a fraction of the model size
+#[wasm_bindgen]
+pub fn set_post(json: &str) -> Result<(), JsValue> {
+    let settings: PostSettings = serde_json::from_str(json).map_err(|err| JsValue::from_str(&format!("invalid post: {}", err)))?;
+    send(UserEvent::Command(ViewerCommand::SetPost(settings)))
+}
+
+// Call the function whenever the measurements change, with {"measurements": [...], "geometry": {...}}:
+// the distances, angles and areas, and the same as an openmodel document of lines and points.
+// Replaces the previous subscriber.
+#[wasm_bindgen]
+pub fn on_measure(callback: web_sys::js_sys::Function) -> Result<(), JsValue> {
+    let forward = move |measurements: &Measurements| {
+        let json = serde_json::json!({ "measurements": measurements.measurements(), "geometry": measurements.to_geometry() });
+        let value = web_sys::js_sys::JSON::parse(&json.to_string()).unwrap_or(JsValue::NULL);
+        if let Err(err) = callback.call1(&JsValue::NULL, &value) {
+            web_sys::console::error_2(&"Measure callback failed:".into(), &err);
+        }
+    };
+    send(UserEvent::Command(ViewerCommand::SetMeasureCallback(Box::new(forward))))
+}
@@ -0,0 +1,44 @@
+use cgmath::{Deg, Point3, Quaternion, Rotation3};
+use wink::camera::{Camera, Projection};
+
+// Depth of a point in the view, 0..1 inside the near and far planes; None behind the eye
+fn depth(camera: &Camera, p: Point3<f32>) -> Option<f32> {
+    let clip = camera.build_view_projection_matrix() * p.to_homogeneous();
+    (clip.w > 0.0).then(|| clip.z / clip.w)
+}
+
+#[test]
+fn clip_planes_keep_the_model_after_panning_and_framing() {
+    let (min, max) = (Point3::new(-10.0, -10.0, 0.0), Point3::new(10.0, 10.0, 5.0));
+    let corners: Vec<Point3<f32>> = (0..8)
+        .map(|c| Point3::new(if c & 1 == 0 { min.x } else { max.x }, if c & 2 == 0 { min.y } else { max.y }, if c & 4 == 0 { min.z } else { max.z }))
+        .collect();
+    // Whatever lies in front of the eye is inside the near and far planes
+    let in_depth_range = |camera: &Camera, case: &str| {
+        for &corner in &corners {
+            let z = depth(camera, corner);
+            assert!(z.is_none_or(|z| (0.0..=1.0).contains(&z)), "{:?} {case}: {corner:?} at depth {z:?}", camera.projection);
+        }
+    };
+    for projection in [Projection::Perspective, Projection::Orthographic] {
+        let mut camera = Camera::new(200.0, 100.0);
+        camera.projection = projection;
+        camera.set_scene_bounds(min, max);
+        camera.frame_bounds(min, max);
+
+        // Panned far beyond the scene radius, the model stays in front of the eye
+        camera.pan(10.0 * camera.scene_radius, 0.0);
+        assert!(corners.iter().all(|&corner| depth(&camera, corner).is_some()));
+        in_depth_range(&camera, "panned");
+        // Orbiting the panned target brings the model nearer or farther than the target
+        for angle in [90.0, 180.0, 270.0] {
+            camera.orientation = Quaternion::from_angle_z(Deg(angle)) * camera.orientation;
+            camera.update_position();
+            in_depth_range(&camera, "panned and orbited");
+        }
+
+        // A small selection in a corner puts the target far from the scene center
+        camera.frame_bounds(Point3::new(9.0, 9.0, 0.0), Point3::new(10.0, 10.0, 1.0));
+        in_depth_range(&camera, "framed selection");
+    }
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use openmodel::AllGeometryData;
+use openmodel::geometry::{Mesh, Point};
+use wink::camera::{Camera, NamedView};
+use wink::colormap::{
+    attribute_names, attribute_range, format_value, legend_image, AttributeColoring, AttributeSource, Colormap, STEPPED_BANDS,
+};
+use wink::geometry::GeometryBuffers;
+use wink::shading::ShadingMode;
+use common::headless;
+
+// Two triangles on z = 0 with a vertex attribute "stress" (x + 10) and a face attribute "utilization"
+fn analysis_mesh() -> Mesh {
+    let mut mesh = Mesh::new();
+    let keys: Vec<usize> = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]
+        .iter()
+        .map(|[x, y]| mesh.add_vertex(Point::new(*x, *y, 0.0), None))
+        .collect();
+    for key in &keys {
+        let vertex = mesh.vertex.get_mut(key).unwrap();
+        let x = vertex.x;
+        vertex.set_color(1.0, 1.0, 1.0);
+        vertex.set_attribute("stress", x + 10.0);
+    }
+    for (face, utilization) in [(vec![keys[0], keys[1], keys[2]], 0.25), (vec![keys[0], keys[2], keys[3]], 0.75)] {
+        let face = mesh.add_face(face, None).unwrap();
+        mesh.facedata.entry(face).or_default().insert("utilization".to_string(), utilization);
+    }
+    mesh
+}
+
+#[test]
+fn colormaps_labels_and_legend() {
+    assert_eq!(Colormap::Viridis.sample(0.0), [68, 1, 84]);
+    assert_eq!(Colormap::Viridis.sample(1.0), [253, 231, 37]);
+    assert_eq!(Colormap::Diverging.sample(0.5), [221, 221, 221]);
+    // Stepped bands are flat and end in the viridis end colors
+    let band = 1.0 / STEPPED_BANDS as f32;
+    assert_eq!(Colormap::Stepped.sample(0.1 * band), Colormap::Stepped.sample(0.9 * band));
+    assert_ne!(Colormap::Stepped.sample(0.9 * band), Colormap::Stepped.sample(1.1 * band));
+    assert_eq!(Colormap::Stepped.sample(1.0), Colormap::Viridis.sample(1.0));
+    assert_eq!("Diverging".parse::<Colormap>(), Ok(Colormap::Diverging));
+    assert!("jet".parse::<Colormap>().is_err());
+
+    assert_eq!(format_value(12.5), "12.5");
+    assert_eq!(format_value(-0.0001), "-1.00e-4");
+    assert_eq!(format_value(250000.0), "2.50e5");
+    assert_eq!(format_value(3.0), "3");
+
+    // Bar on the left, maximum on top
+    let legend = legend_image(Colormap::Viridis, [0.0, 1.0]);
+    let [r, g, b] = Colormap::Viridis.sample(1.0);
+    assert_eq!(legend.pixels[(8 * legend.width + 12) as usize], [r, g, b, 255]);
+    assert!(legend.width > 40 && legend.pixels.len() == (legend.width * legend.height) as usize);
+}
+
+#[test]
+fn attributes_of_the_sources() {
+    let sources = [AllGeometryData { meshes: vec![analysis_mesh()], ..Default::default() }];
+    // Colors are not offered
+    assert_eq!(
+        attribute_names(&sources),
+        [(AttributeSource::Vertex, "stress".to_string()), (AttributeSource::Face, "utilization".to_string())]
+    );
+    assert_eq!(attribute_range(&sources, AttributeSource::Vertex, "stress"), Some([9.0, 11.0]));
+    assert_eq!(attribute_range(&sources, AttributeSource::Face, "utilization"), Some([0.25, 0.75]));
+    assert_eq!(attribute_range(&sources, AttributeSource::Face, "stress"), None);
+
+    // Vertex values are shared, face values get their own vertices
+    let mesh = analysis_mesh();
+    let mut buffers = GeometryBuffers::new();
+    buffers.append_mesh_colored_by(&mesh, [0.8; 3], Some((AttributeSource::Vertex, "stress")));
+    assert_eq!(buffers.vertices.len(), 4);
+    assert!(buffers.vertices.iter().all(|v| v.value == [v.position[0] + 10.0, 1.0]));
+    let mut buffers = GeometryBuffers::new();
+    buffers.append_mesh_colored_by(&mesh, [0.8; 3], Some((AttributeSource::Face, "utilization")));
+    assert_eq!(buffers.vertices.len(), 6);
+    let mut values: Vec<[f32; 2]> = buffers.vertices.iter().map(|v| v.value).collect();
+    values.sort_by(|a, b| a[0].total_cmp(&b[0]));
+    assert_eq!(values, [[[0.25, 1.0]; 3], [[0.75, 1.0]; 3]].concat());
+
+    let coloring: AttributeColoring = serde_json::from_str(r#"{"attribute": "utilization", "source": "face", "range": [0, 1]}"#).unwrap();
+    assert_eq!((coloring.colormap, coloring.range), (Colormap::Viridis, Some([0.0, 1.0])));
+}
+
+#[test]
+fn meshes_are_colored_by_attribute_with_a_legend() {
+    let (width, height) = (256, 256);
+    let Some(mut headless) = headless(width, height) else { return };
+    let sources = [AllGeometryData { meshes: vec![analysis_mesh()], ..Default::default() }];
+    let mut camera = Camera::new(width as f32, height as f32);
+    camera.set_view(NamedView::Top);
+    headless.frame_all(&sources, &mut camera);
+    let mut overlay = headless.renderer_mut().overlay();
+    overlay.visible = false;
+    headless.renderer_mut().set_overlay(overlay);
+    headless.renderer_mut().set_shading_mode(ShadingMode::Unlit);
+    let close = |a: [u8; 4], [r, g, b]: [u8; 3]| a[..3].iter().zip([r, g, b]).all(|(x, y)| x.abs_diff(y) <= 2);
+
+    let plain = headless.render(&sources, &camera).unwrap();
+    let (center, corner) = ((width / 2, height / 2), (16, height - 16));
+    assert!(close(plain.pixel(center.0, center.1), [255, 255, 255]));
+
+    // A fixed range puts the face values at both ends of the colormap; upper left is the 0.75 face
+    let coloring = AttributeColoring { range: Some([0.25, 0.75]), ..AttributeColoring::new(AttributeSource::Face, "utilization") };
+    headless.renderer_mut().set_attribute_coloring(Some(coloring.clone()));
+    let colored = headless.render(&sources, &camera).unwrap();
+    assert!(close(colored.pixel(width * 3 / 8, height * 3 / 8), Colormap::Viridis.sample(1.0)));
+    assert!(close(colored.pixel(width * 5 / 8, height * 5 / 8), Colormap::Viridis.sample(0.0)));
+    assert_ne!(colored.pixel(corner.0, corner.1), plain.pixel(corner.0, corner.1), "legend in the bottom left corner");
+
+    // Only the colormap changes: no rebuild
+    headless.renderer_mut().set_attribute_coloring(Some(AttributeColoring { colormap: Colormap::Diverging, ..coloring }));
+    let diverging = headless.render(&sources, &camera).unwrap();
+    assert_eq!(diverging.pixel(width * 5 / 8, height * 5 / 8)[..3], [59, 76, 192]);
+
+    // Vertex values fit their range automatically: minimum on the left, maximum on the right (above the legend)
+    headless.renderer_mut().set_attribute_coloring(Some(AttributeColoring::new(AttributeSource::Vertex, "stress")));
+    let stress = headless.render(&sources, &camera).unwrap();
+    assert_eq!(headless.renderer_mut().attribute_range(), Some([9.0, 11.0]));
+    let (left, right) = (stress.pixel(width / 4 - 10, height / 4), stress.pixel(width * 3 / 4 + 10, height / 4));
+    assert!(left[0] < 90 && right[0] > 180, "{left:?} {right:?}");
+
+    headless.renderer_mut().set_attribute_coloring(None);
+    assert_eq!(headless.render(&sources, &camera).unwrap().pixel(corner.0, corner.1), plain.pixel(corner.0, corner.1));
+}
@@ -0,0 +1,69 @@
+// Helpers shared by the headless GPU tests; each test file uses its own subset
+#![allow(dead_code)]
+
+use cgmath::Point3;
+use openmodel::geometry::{Mesh, Point};
+use wink::camera::Camera;
+use wink::headless::{HeadlessRenderer, RgbaImage};
+
+// A headless renderer with the default sample count, None (and a note) without a GPU adapter
+pub fn headless(width: u32, height: u32) -> Option<HeadlessRenderer> {
+    skip_without_adapter(HeadlessRenderer::new(width, height))
+}
+
+pub fn headless_with_sample_count(width: u32, height: u32, sample_count: u32) -> Option<HeadlessRenderer> {
+    skip_without_adapter(HeadlessRenderer::with_sample_count(width, height, sample_count))
+}
+
+fn skip_without_adapter(headless: anyhow::Result<HeadlessRenderer>) -> Option<HeadlessRenderer> {
+    headless.map_err(|err| eprintln!("skipping headless test, no adapter: {err:#}")).ok()
+}
+
+pub fn mesh(points: &[[f64; 3]], faces: &[&[usize]]) -> Mesh {
+    let mut mesh = Mesh::new();
+    let keys: Vec<usize> = points.iter().map(|p| mesh.add_vertex(Point::new(p[0], p[1], p[2]), None)).collect();
+    for face in faces {
+        mesh.add_face(face.iter().map(|&i| keys[i]).collect(), None).unwrap();
+    }
+    mesh
+}
+
+// Closed box with its lower corner at (x, y, z), width by width in plan
+pub fn block([x, y, z]: [f64; 3], width: f64, height: f64) -> Mesh {
+    let points = [
+        [x, y, z], [x + width, y, z], [x + width, y + width, z], [x, y + width, z],
+        [x, y, z + height], [x + width, y, z + height], [x + width, y + width, z + height], [x, y + width, z + height],
+    ];
+    mesh(&points, &[&[0, 3, 2, 1], &[4, 5, 6, 7], &[0, 1, 5, 4], &[1, 2, 6, 5], &[2, 3, 7, 6], &[3, 0, 4, 7]])
+}
+
+// Closed unit box from z to z + 1
+pub fn cube(z: f64) -> Mesh {
+    block([0.0, 0.0, z], 1.0, 1.0)
+}
+
+// Flat 2 x 2 square around the origin at z = 0
+pub fn slab() -> Mesh {
+    mesh(&[[-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0]], &[&[0, 1, 2, 3]])
+}
+
+// The pixel a world point projects to
+pub fn pixel_at(image: &RgbaImage, camera: &Camera, p: Point3<f32>) -> [u8; 4] {
+    let clip = camera.build_view_projection_matrix() * p.to_homogeneous();
+    let x = (clip.x / clip.w + 1.0) * 0.5 * image.width as f32;
+    let y = (1.0 - clip.y / clip.w) * 0.5 * image.height as f32;
+    image.pixel(x as u32, y as u32)
+}
+
+pub fn changed_pixels(a: &RgbaImage, b: &RgbaImage) -> usize {
+    a.pixels.chunks_exact(4).zip(b.pixels.chunks_exact(4)).filter(|(a, b)| a != b).count()
+}
+
+// Pixels that differ in the window [x0, x1) x [y0, y1) around a pixel position
+pub fn changed_pixels_near(a: &RgbaImage, b: &RgbaImage, [x, y]: [f32; 2], [x0, x1, y0, y1]: [i32; 4]) -> usize {
+    let range = |v: f32, lo: i32, hi: i32, size: u32| (v as i32 + lo).max(0) as u32..(v as i32 + hi).min(size as i32) as u32;
+    range(y, y0, y1, a.height)
+        .flat_map(|y| range(x, x0, x1, a.width).map(move |x| (x, y)))
+        .filter(|&(x, y)| a.pixel(x, y) != b.pixel(x, y))
+        .count()
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use openmodel::AllGeometryData;
+use wink::camera::Camera;
+use wink::geometry::GeometryBuffers;
+use wink::shading::{DisplayMode, EdgeFilter};
+use common::{headless, mesh};
+
+#[test]
+fn unique_edges_with_boundary_and_crease_angle() {
+    let mut cube = GeometryBuffers::new();
+    cube.append_mesh_edges(&common::cube(0.0));
+    // Two vertices per edge, each edge once although two faces share it
+    assert_eq!(cube.edges.len(), 12 * 2);
+    assert!(cube.edges.iter().all(|e| e.boundary == 0.0 && (e.angle - 90.0).abs() < 1e-3));
+
+    // A flat quad split in two triangles: the diagonal is flat, the outline is the boundary
+    let mut quad = GeometryBuffers::new();
+    quad.append_mesh_edges(&mesh(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]], &[&[0, 1, 2], &[0, 2, 3]]));
+    assert_eq!(quad.edges.len(), 5 * 2);
+    assert_eq!(quad.edges.iter().filter(|e| e.boundary == 1.0).count(), 4 * 2);
+    assert!(quad.edges.iter().all(|e| e.angle.abs() < 1e-3));
+}
+
+#[test]
+fn display_modes_render_different_images() {
+    let (width, height) = (160, 120);
+    let Some(mut headless) = headless(width, height) else { return };
+
+    let sources = [AllGeometryData { meshes: vec![common::cube(0.0)], ..Default::default() }];
+    let mut camera = Camera::new(width as f32, height as f32);
+    headless.frame_all(&sources, &mut camera);
+
+    let mut images = Vec::new();
+    for mode in [DisplayMode::Shaded, DisplayMode::ShadedEdges, DisplayMode::Wireframe] {
+        headless.renderer_mut().set_display_mode(mode);
+        images.push(headless.render(&sources, &camera).unwrap());
+    }
+    assert_ne!(images[0], images[1]);
+    assert_ne!(images[1], images[2]);
+
+    // A closed cube has no boundary, so its boundary wireframe is empty
+    headless.renderer_mut().set_edge_filter(EdgeFilter::Boundary);
+    let boundary = headless.render(&sources, &camera).unwrap();
+    assert_ne!(boundary, images[2]);
+}
@@ -0,0 +1,117 @@
+// Offscreen rendering on whatever adapter is available (CI usually only has a software/GL one)
+mod common;
+
+use cgmath::Point3;
+use openmodel::AllGeometryData;
+use wink::camera::Camera;
+use wink::texture::Texture;
+use common::{headless, headless_with_sample_count};
+
+fn embedded_geometry() -> AllGeometryData {
+    serde_json::from_str(include_str!("../src/openmodel/all_geometry.json")).unwrap()
+}
+
+#[test]
+fn renders_scene_to_rgba_and_png() {
+    let (width, height) = (160, 120);
+    let Some(mut headless) = headless(width, height) else { return };
+
+    let camera = Camera::new(width as f32, height as f32);
+    let image = headless.render(&[embedded_geometry()], &camera).unwrap();
+    assert_eq!((image.width, image.height), (width, height));
+    assert_eq!(image.pixels.len(), (width * height * 4) as usize);
+
+    // The corner shows the clear color, the center shows geometry
+    let background = image.pixel(0, 0);
+    assert!(image.pixels.chunks_exact(4).any(|p| p != background));
+    assert!(image.pixels.chunks_exact(4).all(|p| p[3] == 255));
+
+    // Rendering the same frame twice gives the same image
+    assert_eq!(headless.render(&[embedded_geometry()], &camera).unwrap(), image);
+
+    let path = std::env::temp_dir().join("wink_headless_test.png");
+    image.save_png(&path).unwrap();
+    let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut decoded = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut decoded).unwrap();
+    assert_eq!(decoded, image.pixels);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn multisampling_smooths_edges_and_survives_resize() {
+    let (width, height) = (160, 120);
+    let (Some(mut aliased), Some(mut smooth)) = (headless_with_sample_count(width, height, 1), headless(width, height)) else { return };
+    assert_eq!(aliased.renderer_mut().sample_count(), 1);
+    // The default level is lowered, never raised, to what the adapter supports
+    let sample_count = smooth.renderer_mut().sample_count();
+    assert!(sample_count.is_power_of_two() && sample_count <= Texture::DEFAULT_SAMPLE_COUNT);
+
+    let sources = [embedded_geometry()];
+    let camera = Camera::new(width as f32, height as f32);
+    let image = smooth.render(&sources, &camera).unwrap();
+    if sample_count > 1 {
+        // Resolved edge pixels blend geometry and background
+        assert_ne!(image, aliased.render(&sources, &camera).unwrap());
+    }
+
+    // The multisampled attachments follow the target size
+    smooth.resize(width / 2, height / 2);
+    let small = smooth.render(&sources, &Camera::new((width / 2) as f32, (height / 2) as f32)).unwrap();
+    assert_eq!((small.width, small.height), (width / 2, height / 2));
+}
+
+#[test]
+fn any_requested_sample_count_renders() {
+    let (width, height) = (64, 48);
+    let sources = [embedded_geometry()];
+    let camera = Camera::new(width as f32, height as f32);
+    for requested in [2, 8, 16] {
+        let Some(mut headless) = headless_with_sample_count(width, height, requested) else { return };
+        // Lowered until the color target and the depth buffer both support it
+        let sample_count = headless.renderer_mut().sample_count();
+        assert!(sample_count.is_power_of_two() && sample_count <= requested);
+        let image = headless.render(&sources, &camera).unwrap();
+        assert_eq!((image.width, image.height), (width, height));
+    }
+}
+
+#[test]
+fn duplicate_guids_resolve_to_their_own_source() {
+    let (width, height) = (32, 32);
+    let Some(mut headless) = headless(width, height) else { return };
+    let camera = Camera::new(width as f32, height as f32);
+    let (cube, slab) = (common::cube(0.0), common::slab());
+    let guid = cube.data.guid().to_string();
+    // The same object in a local and a remote file, after another object in the remote one
+    let local = || AllGeometryData { meshes: vec![cube.clone()], ..Default::default() };
+    let remote = || AllGeometryData { meshes: vec![slab.clone(), cube.clone()], ..Default::default() };
+    let source = |headless: &mut wink::headless::HeadlessRenderer, key: &str| headless.renderer_mut().scene().get(key).and_then(|o| o.source);
+
+    headless.render(&[local(), remote()], &camera).unwrap();
+    assert_eq!(source(&mut headless, &guid), Some([0, 0]));
+    assert_eq!(source(&mut headless, &format!("{guid}#1")), Some([1, 1]));
+
+    // Unchanged objects follow the sources when they are reordered
+    headless.render(&[remote(), local()], &camera).unwrap();
+    assert_eq!(source(&mut headless, &guid), Some([0, 1]));
+    assert_eq!(source(&mut headless, &format!("{guid}#1")), Some([1, 0]));
+}
+
+#[test]
+fn scene_bounds_follow_the_visible_objects() {
+    let Some(mut headless) = headless(32, 32) else { return };
+    let (cube, slab) = (common::cube(0.0), common::slab());
+    let guid = cube.data.guid().to_string();
+    let sources = [AllGeometryData { meshes: vec![cube, slab], ..Default::default() }];
+    headless.render(&sources, &Camera::new(32.0, 32.0)).unwrap();
+
+    let scene = headless.renderer_mut().scene_mut();
+    assert_eq!(scene.bounds(), Some((Point3::new(-1.0, -1.0, 0.0), Point3::new(1.0, 1.0, 1.0))));
+    assert!(scene.set_visible(&guid, false));
+    assert_eq!(scene.bounds(), Some((Point3::new(-1.0, -1.0, 0.0), Point3::new(1.0, 1.0, 0.0))));
+    assert!(scene.set_visible(&guid, true));
+    assert!(scene.remove(&guid));
+    assert_eq!(scene.bounds(), Some((Point3::new(-1.0, -1.0, 0.0), Point3::new(1.0, 1.0, 0.0))));
+}
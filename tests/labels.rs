@@ -0,0 +1,130 @@
+mod common;
+
+use cgmath::Point3;
+use openmodel::AllGeometryData;
+use openmodel::geometry::Point;
+use wink::camera::{Camera, NamedView};
+use wink::headless::{HeadlessRenderer, RgbaImage};
+use wink::stream::StreamMessage;
+use wink::labels::{layout_labels, parse_annotations, text_size, Annotation, Label, LabelKind, LabelOcclusion, LabelSettings};
+use common::{changed_pixels, headless, slab};
+
+fn label(kind: LabelKind, text: &str, position: Point3<f32>) -> Label {
+    Label { kind, text: text.to_string(), position, color: [0.0; 3], align: [0.5, 0.5], offset: [0.0; 2] }
+}
+
+#[test]
+fn annotations_and_settings_from_json() {
+    let list = parse_annotations(r#"[{"text": "Column C3", "position": [1, 2, 0], "color": [0, 0, 1]}]"#).unwrap();
+    let object = parse_annotations(r#"{"annotations": [{"text": "Column C3", "position": [1, 2, 0], "color": [0, 0, 1]}]}"#).unwrap();
+    assert_eq!(list, object);
+    assert_eq!(list[0], Annotation { text: "Column C3".to_string(), position: [1.0, 2.0, 0.0], color: Some([0.0, 0.0, 1.0]) });
+    assert!(parse_annotations(r#"{"text": "no position"}"#).is_err());
+    // Streamed like geometry
+    let message = StreamMessage::parse(r#"{"type": "annotations", "annotations": [{"text": "Column C3", "position": [1, 2, 0], "color": [0, 0, 1]}]}"#);
+    assert!(matches!(message, Ok(StreamMessage::Annotations { annotations }) if annotations == list));
+
+    let settings: LabelSettings = serde_json::from_str(r#"{"names": true, "occlusion": "hidden"}"#).unwrap();
+    assert!(settings.visible && settings.names && settings.annotations && settings.declutter && !settings.vertex_keys);
+    assert_eq!((settings.occlusion, settings.scale), (LabelOcclusion::Hidden, 2));
+
+    // 6 pixel advance, 7 pixel cells with their halo, 9 pixel lines
+    assert_eq!(text_size("ab", 1), [13.0, 9.0]);
+    assert_eq!(text_size("abc\nd", 2), [38.0, 36.0]);
+}
+
+#[test]
+fn labels_keep_their_size_and_declutter() {
+    let viewport = [800.0, 600.0];
+    let mut camera = Camera::new(viewport[0], viewport[1]);
+    camera.set_view(NamedView::Top);
+    let settings = LabelSettings::default();
+    let layout = |labels: &[Label], camera: &Camera, settings: &LabelSettings| {
+        layout_labels(labels, camera.build_view_projection_matrix(), camera.position, viewport, settings)
+    };
+
+    // Same size on screen near and far
+    let near = [label(LabelKind::Name, "near", Point3::new(0.0, 0.0, 0.0))];
+    let rect = layout(&near, &camera, &settings)[0].rect;
+    camera.distance *= 4.0;
+    camera.update_position();
+    let far = layout(&near, &camera, &settings)[0].rect;
+    assert_eq!((rect[2], rect[3]), (far[2], far[3]));
+    assert_eq!([rect[0] + rect[2] / 2.0, rect[1] + rect[3] / 2.0], [400.0, 300.0]);
+
+    // The annotation wins over the name it overlaps, labels apart from it stay
+    let labels = [
+        label(LabelKind::Name, "name", Point3::new(0.0, 0.0, 0.0)),
+        label(LabelKind::Annotation, "note", Point3::new(0.0, 0.0, 0.0)),
+        label(LabelKind::VertexKey, "12", Point3::new(camera.distance * 0.2, 0.0, 0.0)),
+    ];
+    let kept: Vec<usize> = layout(&labels, &camera, &settings).iter().map(|placed| placed.label).collect();
+    assert_eq!(kept, [1, 2]);
+    let all = layout(&labels, &camera, &LabelSettings { declutter: false, ..settings });
+    assert_eq!(all.len(), 3);
+
+    // Behind the camera and outside the view
+    let hidden = [
+        label(LabelKind::Name, "behind", camera.position + (camera.position - camera.target)),
+        label(LabelKind::Name, "outside", Point3::new(camera.distance * 10.0, 0.0, 0.0)),
+    ];
+    assert!(layout(&hidden, &camera, &settings).is_empty());
+}
+
+// A 2x2 slab on z = 0 named "Slab" and a point named "Node" off its right edge
+fn model() -> AllGeometryData {
+    let mut mesh = slab();
+    mesh.data.set_name("Slab");
+    let mut point = Point::new(1.5, 0.0, 0.0);
+    point.data.set_name("Node");
+    AllGeometryData { meshes: vec![mesh], points: vec![point], ..Default::default() }
+}
+
+#[test]
+fn labels_are_drawn_and_occluded() {
+    let (width, height) = (256, 256);
+    let Some(mut headless) = headless(width, height) else { return };
+    let sources = [model()];
+    let mut camera = Camera::new(width as f32, height as f32);
+    camera.set_view(NamedView::Top);
+    headless.frame_all(&sources, &mut camera);
+    let mut overlay = headless.renderer_mut().overlay();
+    overlay.visible = false;
+    headless.renderer_mut().set_overlay(overlay);
+    let plain = headless.render(&sources, &camera).unwrap();
+
+    // An annotation under the slab: drawn over it, faded or hidden
+    headless.renderer_mut().set_annotations(vec![Annotation { text: "below".to_string(), position: [0.0, 0.0, -1.0], color: None }]);
+    let mut settings = LabelSettings { occlusion: LabelOcclusion::Always, ..Default::default() };
+    let render = |headless: &mut HeadlessRenderer, settings: LabelSettings| {
+        headless.renderer_mut().set_labels(settings);
+        headless.render(&sources, &camera).unwrap()
+    };
+    let always = render(&mut headless, settings);
+    settings.occlusion = LabelOcclusion::Faded;
+    let faded = render(&mut headless, settings);
+    settings.occlusion = LabelOcclusion::Hidden;
+    let hidden = render(&mut headless, settings);
+    let ink = |image: &RgbaImage| (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| image.pixel(x, y)[1] as u32).sum::<u32>();
+    assert!(changed_pixels(&plain, &always) > 50);
+    assert!(ink(&always) < ink(&faded) && ink(&faded) < ink(&plain), "faded labels are lighter");
+    assert_eq!(changed_pixels(&plain, &hidden), 0);
+
+    // Names: the slab's above its center, the point's to the right of its sprite
+    let names = render(&mut headless, LabelSettings { names: true, annotations: false, ..settings });
+    let view_proj = camera.build_view_projection_matrix();
+    let project = |p: Point3<f32>| {
+        let clip = view_proj * p.to_homogeneous();
+        [((clip.x / clip.w + 1.0) * 0.5 * width as f32) as u32, ((1.0 - clip.y / clip.w) * 0.5 * height as f32) as u32]
+    };
+    let changed_in = |[x, y]: [u32; 2], [x0, x1, y0, y1]: [i32; 4]| {
+        let range = |v: u32, a: i32, b: i32| (v as i32 + a).max(0) as u32..(v as i32 + b).min(width as i32) as u32;
+        range(y, y0, y1).flat_map(|y| range(x, x0, x1).map(move |x| (x, y))).filter(|&(x, y)| names.pixel(x, y) != plain.pixel(x, y)).count()
+    };
+    let slab = project(Point3::new(0.0, 0.0, 0.0));
+    assert!(changed_in(slab, [-20, 20, -24, 0]) > 20, "slab name");
+    assert_eq!(changed_in(slab, [-20, 20, 4, 24]), 0);
+    let node = project(Point3::new(1.5, 0.0, 0.0));
+    assert!(changed_in(node, [8, 40, -8, 8]) > 20, "point name");
+    assert_eq!(changed_in(node, [-40, -8, -8, 8]), 0);
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use cgmath::{Matrix4, Point3, Rad, SquareMatrix};
+use openmodel::AllGeometryData;
+use wink::camera::{Camera, NamedView};
+use wink::geometry::GeometryObject;
+use wink::labels::{LabelKind, LabelOcclusion, LabelSettings};
+use wink::measure::{FaceArea, MeasureTarget, MeasureTool, Measurement, Measurements, Snap, SnapKind};
+use wink::picking::PickHit;
+use common::{changed_pixels_near, headless, slab};
+
+// A 2x2 slab on z = 0 centered on the origin
+fn top_camera(width: f32, height: f32) -> Camera {
+    let mut camera = Camera::new(width, height);
+    camera.set_view(NamedView::Top);
+    camera.frame_bounds(Point3::new(-1.0, -1.0, 0.0), Point3::new(1.0, 1.0, 0.0));
+    camera
+}
+
+fn project(view_proj: Matrix4<f32>, viewport: [f32; 2], p: Point3<f32>) -> [f32; 2] {
+    let clip = view_proj * p.to_homogeneous();
+    [(clip.x / clip.w + 1.0) * 0.5 * viewport[0], (1.0 - clip.y / clip.w) * 0.5 * viewport[1]]
+}
+
+fn target(x: f32, y: f32, z: f32) -> Option<MeasureTarget> {
+    Some(MeasureTarget { snap: Snap { position: Point3::new(x, y, z), kind: SnapKind::Surface }, face: None })
+}
+
+#[test]
+fn clicks_snap_to_vertices_midpoints_and_centroids() {
+    let mesh = slab();
+    let face = *mesh.face.keys().next().unwrap();
+    let viewport = [800.0, 600.0];
+    let view_proj = top_camera(viewport[0], viewport[1]).build_view_projection_matrix();
+    let hit = PickHit { key: "slab".to_string(), t: 0.5, position: [0.3, 0.2, 0.0], element: Some(face), vertex_key: None, point_index: None };
+    let snap = |cursor: [f32; 2], clip_planes: &[[f32; 4]]| {
+        MeasureTarget::new(&hit, GeometryObject::Mesh(&mesh, [1.0; 3]), Matrix4::identity(), view_proj, viewport, cursor, clip_planes).snap
+    };
+    let near = |p: Point3<f32>, [dx, dy]: [f32; 2]| {
+        let [x, y] = project(view_proj, viewport, p);
+        [x + dx, y + dy]
+    };
+
+    assert_eq!(snap(near(Point3::new(1.0, 1.0, 0.0), [3.0, -2.0]), &[]), Snap { position: Point3::new(1.0, 1.0, 0.0), kind: SnapKind::Vertex });
+    assert_eq!(snap(near(Point3::new(1.0, 0.0, 0.0), [-4.0, 0.0]), &[]), Snap { position: Point3::new(1.0, 0.0, 0.0), kind: SnapKind::Midpoint });
+    assert_eq!(snap(near(Point3::new(0.0, 0.0, 0.0), [2.0, 2.0]), &[]), Snap { position: Point3::new(0.0, 0.0, 0.0), kind: SnapKind::Centroid });
+    // Nothing near: the hit itself
+    let surface = snap(near(Point3::new(0.5, -0.5, 0.0), [0.0; 2]), &[]);
+    assert_eq!(surface, Snap { position: Point3::new(0.3, 0.2, 0.0), kind: SnapKind::Surface });
+    // Cut away by a clip plane keeping x <= 0.5
+    assert_eq!(snap(near(Point3::new(1.0, 1.0, 0.0), [0.0; 2]), &[[1.0, 0.0, 0.0, -0.5]]).kind, SnapKind::Surface);
+
+    // Face areas in world units
+    let area = |model: Matrix4<f32>| FaceArea::new("slab", &mesh, face, &model).unwrap().area;
+    assert!((area(Matrix4::identity()) - 4.0).abs() < 1e-5);
+    assert!((area(Matrix4::from_nonuniform_scale(2.0, 1.0, 5.0)) - 8.0).abs() < 1e-5);
+    assert!((area(Matrix4::from_angle_x(Rad(0.7)) * Matrix4::from_scale(3.0)) - 36.0).abs() < 1e-4);
+}
+
+#[test]
+fn distances_angles_and_areas() {
+    let mut measurements = Measurements::default();
+    // Without a tool clicks do nothing
+    assert!(measurements.click(target(0.0, 0.0, 0.0)).is_none());
+
+    measurements.set_tool(Some(MeasureTool::Distance));
+    assert!(measurements.click(target(0.0, 0.0, 0.0)).is_none());
+    assert_eq!(measurements.picks().len(), 1);
+    let distance = measurements.click(target(3.0, 4.0, 0.0)).cloned().unwrap();
+    assert_eq!((distance.value(), distance.text(), distance.name()), (5.0, "5".to_string(), "Distance 5".to_string()));
+    assert!(measurements.picks().is_empty());
+
+    measurements.set_tool(Some(MeasureTool::Angle));
+    measurements.click(target(1.0, 0.0, 0.0));
+    measurements.click(target(0.0, 0.0, 0.0));
+    let angle = measurements.click(target(0.0, 2.0, 0.0)).cloned().unwrap();
+    assert!((angle.value() - 90.0).abs() < 1e-4);
+    assert_eq!(angle.text(), "90.0°");
+    // A click on empty space drops the unfinished angle
+    measurements.click(target(1.0, 0.0, 0.0));
+    measurements.click(None);
+    assert!(measurements.picks().is_empty());
+
+    // Faces are added and taken away again, across meshes
+    let mesh = slab();
+    let face = *mesh.face.keys().next().unwrap();
+    let face_target = |key: &str, model: Matrix4<f32>| {
+        Some(MeasureTarget { snap: Snap { position: Point3::new(0.0, 0.0, 0.0), kind: SnapKind::Surface }, face: FaceArea::new(key, &mesh, face, &model) })
+    };
+    measurements.set_tool(Some(MeasureTool::Area));
+    assert!(measurements.click(target(0.0, 0.0, 0.0)).is_none(), "not a mesh face");
+    assert_eq!(measurements.click(face_target("a", Matrix4::identity())).unwrap().value(), 4.0);
+    assert!(measurements.click(face_target("a", Matrix4::identity())).is_none());
+    assert_eq!(measurements.measurements().len(), 2);
+    measurements.click(face_target("a", Matrix4::identity()));
+    let area = measurements.click(face_target("b", Matrix4::from_translation([0.0, 0.0, 2.0].into()))).cloned().unwrap();
+    assert_eq!((area.value(), area.text()), (8.0, "A = 8".to_string()));
+    assert_eq!(area.points(), [Point3::new(0.0, 0.0, 1.0)]);
+    assert_eq!(measurements.measurements().len(), 3);
+
+    let labels = measurements.labels();
+    assert_eq!(labels.len(), 3);
+    assert!(labels.iter().all(|label| label.kind == LabelKind::Measurement));
+    let json = serde_json::to_value(&measurements.measurements()[0]).unwrap();
+    assert_eq!(json, serde_json::json!({"type": "distance", "points": [[0.0, 0.0, 0.0], [3.0, 4.0, 0.0]], "distance": 5.0}));
+
+    // Exported as lines and points named after their value
+    let geometry = measurements.to_geometry();
+    assert_eq!(geometry.lines.len(), 1 + 2 + 8);
+    assert_eq!(geometry.points.len(), 2 + 3 + 1);
+    assert_eq!(geometry.lines[0].data.name(), "Distance 5");
+    assert_eq!((geometry.lines[0].x1, geometry.lines[0].y1), (3.0, 4.0));
+    assert_eq!(geometry.points[5].data.name(), "Area 8");
+
+    measurements.undo();
+    assert!(matches!(measurements.measurements().last(), Some(Measurement::Angle { .. })));
+    measurements.clear();
+    assert!(measurements.is_empty());
+}
+
+#[test]
+fn measurements_are_drawn_over_the_model() {
+    let (width, height) = (256, 256);
+    let Some(mut headless) = headless(width, height) else { return };
+    let sources = [AllGeometryData { meshes: vec![slab()], ..Default::default() }];
+    let camera = top_camera(width as f32, height as f32);
+    let mut overlay = headless.renderer_mut().overlay();
+    overlay.visible = false;
+    headless.renderer_mut().set_overlay(overlay);
+    // Measurements are labeled even with the labels off
+    headless.renderer_mut().set_labels(LabelSettings { visible: false, occlusion: LabelOcclusion::Hidden, ..Default::default() });
+    let plain = headless.render(&sources, &camera).unwrap();
+
+    // A distance under the slab
+    let measurements = headless.renderer_mut().measurements_mut();
+    measurements.set_tool(Some(MeasureTool::Distance));
+    measurements.click(target(-0.8, 0.0, -1.0));
+    measurements.click(target(0.8, 0.0, -1.0));
+    let measured = headless.render(&sources, &camera).unwrap();
+
+    let view_proj = camera.build_view_projection_matrix();
+    let viewport = [width as f32, height as f32];
+    let line = project(view_proj, viewport, Point3::new(-0.4, 0.0, -1.0));
+    assert!(changed_pixels_near(&plain, &measured, line, [-1, 2, -1, 2]) > 0, "dimension line");
+    let marker = project(view_proj, viewport, Point3::new(0.8, 0.0, -1.0));
+    assert!(changed_pixels_near(&plain, &measured, marker, [-3, 4, -3, 4]) > 30, "marker");
+    let middle = project(view_proj, viewport, Point3::new(0.0, 0.0, -1.0));
+    assert!(changed_pixels_near(&plain, &measured, middle, [-8, 8, -24, -4]) > 10, "label above the line");
+    assert_eq!(changed_pixels_near(&plain, &measured, middle, [-8, 8, 4, 24]), 0);
+
+    // Points picked so far are shown too, and removed with the measurements
+    let measurements = headless.renderer_mut().measurements_mut();
+    measurements.clear();
+    measurements.click(target(0.5, 0.5, 0.0));
+    let picked = headless.render(&sources, &camera).unwrap();
+    let pick = project(view_proj, viewport, Point3::new(0.5, 0.5, 0.0));
+    assert!(changed_pixels_near(&plain, &picked, pick, [-3, 4, -3, 4]) > 30, "picked point");
+    headless.renderer_mut().measurements_mut().clear();
+    let cleared = headless.render(&sources, &camera).unwrap();
+    assert_eq!(changed_pixels_near(&plain, &cleared, [128.0, 128.0], [-128, 128, -128, 128]), 0);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use cgmath::{InnerSpace, Point3};
+use wink::camera::{Camera, NamedView};
+use wink::overlay::{gizmo_rect, gizmo_view_at, OverlaySettings, GIZMO_SIZE};
+use openmodel::AllGeometryData;
+use common::headless;
+
+#[test]
+fn overlay_settings_defaults() {
+    let settings: OverlaySettings = serde_json::from_str(r#"{"grid": false, "minor_spacing": 0.5}"#).unwrap();
+    assert!(settings.visible && !settings.grid && settings.axes && settings.gizmo && settings.adaptive);
+    assert_eq!((settings.minor_spacing, settings.major_spacing), (0.5, 10.0));
+}
+
+#[test]
+fn gizmo_faces_snap_to_views() {
+    let (width, height) = (800.0, 600.0);
+    let [x, y, size, _] = gizmo_rect([width, height]);
+    assert_eq!((x + size, size), (width - 12.0, GIZMO_SIZE));
+    let center = [x + size / 2.0, y + size / 2.0];
+
+    let mut camera = Camera::new(width, height);
+    for view in [NamedView::Top, NamedView::Front, NamedView::Right, NamedView::Back, NamedView::Left, NamedView::Bottom] {
+        camera.set_view(view);
+        assert_eq!(gizmo_view_at(&camera, [width, height], center), Some(view));
+    }
+
+    // Looking down on the front face, its upper part shows the top face
+    camera.set_view(NamedView::Front);
+    camera.position.z += camera.distance * 0.5;
+    assert_eq!(gizmo_view_at(&camera, [width, height], [center[0], y + size * 0.3]), Some(NamedView::Top));
+    assert_eq!(gizmo_view_at(&camera, [width, height], [10.0, 10.0]), None);
+}
+
+#[test]
+fn named_views_orbit_the_scene_center() {
+    let mut camera = Camera::new(800.0, 600.0);
+    camera.set_scene_bounds(Point3::new(1.0, 2.0, 0.0), Point3::new(3.0, 4.0, 2.0));
+    camera.pan(5.0, -3.0);
+    camera.set_view(NamedView::Top);
+    assert_eq!(camera.target, Point3::new(2.0, 3.0, 1.0));
+    let eye = (camera.position - camera.target).normalize();
+    assert!((eye.z - 1.0).abs() < 1e-4, "top view looks down, eye direction {eye:?}");
+}
+
+#[test]
+fn overlay_can_be_hidden() {
+    let (width, height) = (128, 128);
+    let Some(mut headless) = headless(width, height) else { return };
+    let sources = [AllGeometryData::default()];
+    let mut camera = Camera::new(width as f32, height as f32);
+    camera.set_view(NamedView::Top);
+    let shown = headless.render(&sources, &camera).unwrap();
+
+    // The gizmo covers the top right corner and the grid the rest
+    let [x, y, size, _] = gizmo_rect([width as f32, height as f32]);
+    let gizmo = ((x + size / 2.0) as u32, (y + size / 2.0) as u32);
+    let background = shown.pixel(4, height - 4);
+    assert_ne!(shown.pixel(gizmo.0, gizmo.1), background);
+
+    let mut settings = headless.renderer_mut().overlay();
+    settings.gizmo = false;
+    headless.renderer_mut().set_overlay(settings);
+    let no_gizmo = headless.render(&sources, &camera).unwrap();
+    assert_ne!(no_gizmo.pixel(gizmo.0, gizmo.1), shown.pixel(gizmo.0, gizmo.1));
+
+    settings.visible = false;
+    headless.renderer_mut().set_overlay(settings);
+    let hidden = headless.render(&sources, &camera).unwrap();
+    assert_ne!(hidden.pixel(width / 2, height / 2), shown.pixel(width / 2, height / 2), "axes cross at the center");
+    let first = hidden.pixel(0, 0);
+    assert!((0..height).all(|y| (0..width).all(|x| hidden.pixel(x, y) == first)), "nothing but the background");
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use cgmath::Point3;
+use openmodel::AllGeometryData;
+use wink::camera::{Camera, NamedView};
+use wink::headless::RgbaImage;
+use wink::post::{reduced_quality, PostSettings};
+use wink::shading::ShadingMode;
+use common::{block, headless, pixel_at};
+
+#[test]
+fn settings_cycle_and_quality() {
+    let settings: PostSettings = serde_json::from_str(r#"{"silhouettes": true, "crease_angle": 45}"#).unwrap();
+    assert!(settings.silhouettes && !settings.ambient_occlusion && settings.is_active());
+    assert_eq!((settings.crease_angle, settings.occlusion_radius), (45.0, PostSettings::default().occlusion_radius));
+
+    let off = PostSettings::default();
+    assert!(!off.is_active());
+    let cycle: Vec<(bool, bool)> = std::iter::successors(Some(off.next()), |s| Some(s.next()))
+        .take(4)
+        .map(|s| (s.ambient_occlusion, s.silhouettes))
+        .collect();
+    assert_eq!(cycle, [(true, false), (false, true), (true, true), (false, false)]);
+    assert_eq!(off.next().next().next().next(), off);
+
+    // WebGL2 gets the cheaper ambient occlusion
+    assert!(reduced_quality(&wgpu::Limits::downlevel_webgl2_defaults()));
+    assert!(!reduced_quality(&wgpu::Limits::downlevel_defaults()));
+    assert!(!reduced_quality(&wgpu::Limits::default()));
+}
+
+fn brightness(pixel: [u8; 4]) -> i32 {
+    pixel[..3].iter().map(|&c| c as i32).sum()
+}
+
+#[test]
+fn occlusion_and_silhouettes_bring_back_depth_cues() {
+    let (width, height) = (384, 288);
+    let Some(mut headless) = headless(width, height) else { return };
+    // A block on a slab, unlit: every face has the same color
+    let sources = [AllGeometryData { meshes: vec![block([-3.0, -3.0, 0.0], 6.0, 0.2), block([0.0, 0.0, 0.0], 1.0, 1.0)], ..Default::default() }];
+    let mut camera = Camera::new(width as f32, height as f32);
+    camera.set_view(NamedView::Iso);
+    headless.frame_all(&sources, &mut camera);
+    let mut overlay = headless.renderer_mut().overlay();
+    overlay.visible = false;
+    headless.renderer_mut().set_overlay(overlay);
+    headless.renderer_mut().set_shading_mode(ShadingMode::Unlit);
+    let mut render = |settings: PostSettings| {
+        headless.renderer_mut().set_post(settings);
+        headless.render(&sources, &camera).unwrap()
+    };
+    let plain = render(PostSettings::default());
+    let occluded = render(PostSettings { ambient_occlusion: true, ..Default::default() });
+    let outlined = render(PostSettings { silhouettes: true, ..Default::default() });
+
+    // The slab is darker where it meets the block, open areas stay as they were
+    let corner = Point3::new(0.5, -0.05, 0.2);
+    let open = Point3::new(-2.0, -2.0, 0.2);
+    assert!(brightness(pixel_at(&occluded, &camera, corner)) + 30 < brightness(pixel_at(&plain, &camera, corner)), "corner");
+    assert!((brightness(pixel_at(&occluded, &camera, open)) - brightness(pixel_at(&plain, &camera, open))).abs() <= 6, "open");
+
+    // Outlines along the block's vertical edge and its contour against the background, not inside faces
+    let dark = |image: &RgbaImage, p| brightness(pixel_at(image, &camera, p)) < brightness(pixel_at(&plain, &camera, p)) - 150;
+    let near_dark = |image: &RgbaImage, p: Point3<f32>| {
+        let clip = camera.build_view_projection_matrix() * p.to_homogeneous();
+        let [x, y] = [(clip.x / clip.w + 1.0) * 0.5 * width as f32, (1.0 - clip.y / clip.w) * 0.5 * height as f32];
+        (-2..=2).flat_map(|dy| (-2..=2).map(move |dx| (dx, dy))).any(|(dx, dy)| {
+            let (x, y) = ((x as i32 + dx) as u32, (y as i32 + dy) as u32);
+            brightness(image.pixel(x, y)) < brightness(plain.pixel(x, y)) - 150
+        })
+    };
+    assert!(near_dark(&outlined, Point3::new(1.0, 0.0, 0.6)), "crease");
+    assert!(near_dark(&outlined, Point3::new(0.0, 1.0, 1.0)), "contour");
+    assert!(!dark(&outlined, Point3::new(0.5, 0.0, 0.5)), "face");
+    assert!(!dark(&outlined, open), "slab");
+    let near_corner = Point3::new(0.5, -0.3, 0.2);
+    assert_eq!(pixel_at(&outlined, &camera, near_corner), pixel_at(&plain, &camera, near_corner), "no occlusion with outlines only");
+}
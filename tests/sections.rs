@@ -0,0 +1,127 @@
+mod common;
+
+use cgmath::Point3;
+use openmodel::AllGeometryData;
+use openmodel::geometry::{Plane, Point, Vector};
+use wink::camera::{Camera, NamedView};
+use wink::geometry::{is_closed, GeometryBuffers, GeometryObject};
+use wink::headless::HeadlessRenderer;
+use wink::section::{plane_equation, Clipping, MAX_CLIP_PLANES};
+use wink::shading::DisplayMode;
+use common::{cube, headless, mesh};
+
+// Horizontal plane at height z, cutting away what is above
+fn horizontal(name: &str, z: f64) -> Plane {
+    Plane::with_name(name.to_string(), Point::new(0.0, 0.0, z), Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0))
+}
+
+#[test]
+fn plane_equations_and_active_planes() {
+    let mut plane = horizontal("Plane", 1.5);
+    assert_eq!(plane_equation(&plane), Some([0.0, 0.0, 1.0, -1.5]));
+    // Data.transformation moves the plane up by 2
+    let mut moved = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 2.0, 1.0];
+    plane.data.set_transformation(moved);
+    assert_eq!(plane_equation(&plane), Some([0.0, 0.0, 1.0, -3.5]));
+    moved[10] = 0.0;
+    plane.data.set_transformation(moved);
+    assert_eq!(plane_equation(&plane), None);
+
+    // Only planes named "section..." of the sources cut the view
+    let sources = [AllGeometryData {
+        planes: vec![horizontal("Section A", 1.5), horizontal("Plane", 1.0), horizontal("section-b", 1.2)],
+        ..Default::default()
+    }];
+    let mut clipping = Clipping::default();
+    clipping.set_sources(&sources);
+    assert_eq!(clipping.active_planes(), [[0.0, 0.0, 1.0, -1.5], [0.0, 0.0, 1.0, -1.2]]);
+
+    clipping.planes = vec![[1.0, 0.0, 0.0, 0.0]; MAX_CLIP_PLANES];
+    assert_eq!(clipping.active_planes().len(), MAX_CLIP_PLANES);
+    assert_eq!(clipping.active_planes()[0], [1.0, 0.0, 0.0, 0.0]);
+
+    // The section box replaces the planes
+    clipping.section_box = Some((Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0)));
+    let planes = clipping.active_planes();
+    assert_eq!(planes.len(), 6);
+    assert!(planes.contains(&[0.0, 0.0, 1.0, -3.0]) && planes.contains(&[0.0, -1.0, 0.0, 0.0]));
+}
+
+#[test]
+fn clip_planes_cut_render_and_picking() {
+    let (width, height) = (64, 64);
+    let Some(mut headless) = headless(width, height) else { return };
+
+    let sources = [AllGeometryData { meshes: vec![cube(1.0)], ..Default::default() }];
+    let mut camera = Camera::new(width as f32, height as f32);
+    camera.set_view(NamedView::Top);
+    headless.frame_all(&sources, &mut camera);
+    headless.renderer_mut().set_display_mode(DisplayMode::Shaded);
+    let center = |image: &wink::headless::RgbaImage| image.pixel(width / 2, height / 2);
+
+    let closed = center(&headless.render(&sources, &camera).unwrap());
+    let ray = camera.ray_through(0.0, 0.0);
+    let pick = |headless: &mut HeadlessRenderer| {
+        let renderer = headless.renderer_mut();
+        let view_proj = camera.build_view_projection_matrix();
+        renderer.scene().pick(&ray, &view_proj, [width as f32, height as f32], [32.0, 32.0], &renderer.clip_planes())
+    };
+    let top = pick(&mut headless).expect("cube under the cursor");
+    assert!((top.position[2] - 2.0).abs() < 1e-3);
+
+    // Cut at half height: the cursor reaches the inside of the bottom face
+    headless.renderer_mut().set_clip_planes(&[horizontal("Plane", 1.5)]);
+    let bottom = pick(&mut headless).expect("bottom face under the cut");
+    assert!((bottom.position[2] - 1.0).abs() < 1e-3);
+
+    // The cap fill colors the back faces seen through the cut, not the closed cube
+    headless.renderer_mut().set_section_fill(Some([1.0, 0.0, 0.0]));
+    let capped = center(&headless.render(&sources, &camera).unwrap());
+    assert!(capped[0] > 200 && capped[1] < 10 && capped[2] < 10, "{capped:?}");
+    headless.renderer_mut().set_clip_planes(&[horizontal("Plane", 2.5)]);
+    assert_eq!(center(&headless.render(&sources, &camera).unwrap()), closed);
+    headless.renderer_mut().set_clip_planes(&[horizontal("Plane", 1.5)]);
+
+    // A section box away from the cube removes it, corners can be given in any order
+    headless.renderer_mut().set_section_box(Some((Point3::new(5.0, 5.0, 5.0), Point3::new(3.0, 3.0, 3.0))));
+    assert_eq!(headless.renderer_mut().section_box().unwrap().0, Point3::new(3.0, 3.0, 3.0));
+    let background = headless.render(&[AllGeometryData::default()], &camera).unwrap();
+    let boxed = headless.render(&sources, &camera).unwrap();
+    assert_eq!(center(&boxed), center(&background));
+    assert!(pick(&mut headless).is_none());
+
+    // A section plane of the sources cuts the same way and is not drawn
+    headless.renderer_mut().set_section_box(None);
+    headless.renderer_mut().set_clip_planes(&[]);
+    let section = horizontal("Section", 1.5);
+    let guid = section.data.guid().to_string();
+    let sectioned = [AllGeometryData { meshes: vec![cube(1.0)], planes: vec![section], ..Default::default() }];
+    assert_eq!(center(&headless.render(&sectioned, &camera).unwrap()), capped);
+    assert_eq!(headless.renderer_mut().clip_planes().len(), 1);
+    assert!(!headless.renderer_mut().scene().keys().any(|key| *key == guid));
+}
+
+#[test]
+fn only_closed_meshes_get_a_section_cap() {
+    // A square facing down, below the plane: seen from the top it shows its back
+    let quad = mesh(&[[0.0, 0.0, 0.5], [1.0, 0.0, 0.5], [1.0, 1.0, 0.5], [0.0, 1.0, 0.5]], &[&[0, 3, 2, 1]]);
+    assert!(is_closed(&cube(0.0)) && !is_closed(&quad));
+    assert!(GeometryBuffers::from_object(GeometryObject::Mesh(&cube(0.0), [0.5; 3])).closed);
+
+    let (width, height) = (64, 64);
+    let Some(mut headless) = headless(width, height) else { return };
+    let sources = [AllGeometryData { meshes: vec![quad], ..Default::default() }];
+    let mut camera = Camera::new(width as f32, height as f32);
+    camera.set_view(NamedView::Top);
+    headless.frame_all(&sources, &mut camera);
+    headless.renderer_mut().set_display_mode(DisplayMode::Shaded);
+    let center = |image: &wink::headless::RgbaImage| image.pixel(width / 2, height / 2);
+
+    let plain = center(&headless.render(&sources, &camera).unwrap());
+    let background = center(&headless.render(&[AllGeometryData::default()], &camera).unwrap());
+    assert_ne!(plain, background);
+    // A plane above the quad with the fill on leaves its back in its own color
+    headless.renderer_mut().set_clip_planes(&[horizontal("Plane", 1.5)]);
+    headless.renderer_mut().set_section_fill(Some([1.0, 0.0, 0.0]));
+    assert_eq!(center(&headless.render(&sources, &camera).unwrap()), plain);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use cgmath::{InnerSpace, Matrix4, Point3, Vector3};
+use openmodel::AllGeometryData;
+use wink::camera::{Camera, NamedView};
+use wink::headless::RgbaImage;
+use wink::shadow::{ground_plane, solar_position, sun_view_proj, SunDate, SunSettings};
+use common::{cube, headless, pixel_at};
+
+fn close(a: f32, b: f32, tolerance: f32) -> bool {
+    (a - b).abs() < tolerance
+}
+
+#[test]
+fn sun_from_angles_and_dates() {
+    let direction = |azimuth, altitude| SunSettings { azimuth, altitude, ..Default::default() }.direction();
+    assert!((direction(90.0, 0.0) - Vector3::unit_x()).magnitude() < 1e-6, "east");
+    assert!((direction(180.0, 45.0) - Vector3::new(0.0, -1.0, 1.0).normalize()).magnitude() < 1e-6, "south");
+
+    // Zurich at noon on the solstices: due south, 90 - 47.4 -/+ 23.44 degrees high
+    let date = |month, day, hour, latitude| SunDate { month, day, hour, latitude };
+    let [azimuth, altitude] = solar_position(&date(6, 21, 12.0, 47.4));
+    assert!(close(azimuth, 180.0, 0.1) && close(altitude, 66.0, 0.5), "{azimuth} {altitude}");
+    let [_, altitude] = solar_position(&date(12, 21, 12.0, 47.4));
+    assert!(close(altitude, 19.2, 0.5), "{altitude}");
+    // Equinox on the equator: rises in the east at six, afternoon sun in the west
+    let [azimuth, altitude] = solar_position(&date(3, 21, 6.0, 0.0));
+    assert!(close(azimuth, 90.0, 1.0) && close(altitude, 0.0, 1.0), "{azimuth} {altitude}");
+    let [azimuth, _] = solar_position(&date(3, 21, 15.0, 0.0));
+    assert!(azimuth > 180.0 && azimuth < 360.0, "{azimuth}");
+
+    // The date replaces the angles, partial settings keep the defaults
+    let settings: SunSettings = serde_json::from_str(r#"{"enabled": true, "date": {"month": 6, "day": 21, "hour": 12, "latitude": 47.4}}"#).unwrap();
+    assert!(settings.shadows && settings.ground_plane);
+    assert_eq!(settings.position(), solar_position(&date(6, 21, 12.0, 47.4)));
+    let bounds = Some((Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)));
+    assert!(settings.light(bounds).unwrap().view_proj.is_some());
+    assert!(SunSettings { shadows: false, ..settings }.light(bounds).unwrap().view_proj.is_none());
+    // Off, or below the horizon at night
+    assert!(SunSettings { enabled: false, ..settings }.light(bounds).is_none());
+    assert!(SunSettings { date: Some(date(6, 21, 23.5, 47.4)), ..settings }.light(bounds).is_none());
+}
+
+#[test]
+fn shadow_map_is_fitted_to_the_model() {
+    let (min, max) = (Point3::new(-2.0, 1.0, 0.0), Point3::new(3.0, 4.0, 10.0));
+    for (azimuth, altitude) in [(0.0, 90.0), (135.0, 45.0), (270.0, 5.0)] {
+        let sun = SunSettings { azimuth, altitude, ..Default::default() }.direction();
+        let view_proj: Matrix4<f32> = sun_view_proj(sun, (min, max));
+        let project = |p: Point3<f32>| Point3::from_homogeneous(view_proj * p.to_homogeneous());
+        let corners: Vec<Point3<f32>> = (0..8)
+            .map(|i| project(Point3::new([min.x, max.x][i & 1], [min.y, max.y][(i >> 1) & 1], [min.z, max.z][i >> 2])))
+            .collect();
+        assert!(corners.iter().all(|p| p.x.abs() <= 1.0 && p.y.abs() <= 1.0 && (0.0..=1.0).contains(&p.z)), "inside at {azimuth}");
+        // Fitted: the box touches the sides of the map
+        let extent = corners.iter().fold(0.0f32, |extent, p| extent.max(p.x.abs()).max(p.y.abs()));
+        assert!(extent > 0.95, "fitted at {azimuth}");
+        // Closer to the sun is closer in depth
+        let center = Point3::new(0.5, 2.5, 5.0);
+        assert!(project(center + sun).z < project(center).z);
+    }
+
+    // The ground plane lies just under the model and reaches well beyond it
+    let [x, y, z, half_size] = ground_plane((min, max));
+    assert_eq!((x, y), (0.5, 2.5));
+    assert!(z < 0.0 && z > -0.1);
+    assert!(half_size >= 10.0);
+}
+
+#[test]
+fn the_sun_casts_shadows_on_the_ground() {
+    let (width, height) = (256, 256);
+    let Some(mut headless) = headless(width, height) else { return };
+    let sources = [AllGeometryData { meshes: vec![cube(0.0)], ..Default::default() }];
+    let mut camera = Camera::new(width as f32, height as f32);
+    camera.set_view(NamedView::Top);
+    camera.frame_bounds(Point3::new(-1.5, -1.5, 0.0), Point3::new(2.5, 2.5, 0.0));
+    let mut overlay = headless.renderer_mut().overlay();
+    overlay.visible = false;
+    headless.renderer_mut().set_overlay(overlay);
+    let mut render = |settings: SunSettings| {
+        headless.renderer_mut().set_sun(settings);
+        headless.render(&sources, &camera).unwrap()
+    };
+
+    // Evening sun from the west: the shadow falls east of the cube
+    let sun = SunSettings { enabled: true, azimuth: 270.0, altitude: 45.0, ..Default::default() };
+    let shadowed = render(sun);
+    let unshadowed = render(SunSettings { shadows: false, ..sun });
+    let (east, west) = (Point3::new(1.5, 0.5, 0.0), Point3::new(-0.5, 0.5, 0.0));
+    let brightness = |image: &RgbaImage, p| pixel_at(image, &camera, p)[..3].iter().map(|&c| c as u32).sum::<u32>();
+    assert!(brightness(&shadowed, east) + 60 < brightness(&unshadowed, east), "shadow east of the cube");
+    assert_eq!(pixel_at(&shadowed, &camera, west), pixel_at(&unshadowed, &camera, west));
+    // The lit top of the cube does not shadow itself
+    let top = Point3::new(0.5, 0.5, 1.0);
+    assert_eq!(pixel_at(&shadowed, &camera, top), pixel_at(&unshadowed, &camera, top));
+
+    // Without the ground plane there is only the background around the cube
+    let background = render(SunSettings { ground_plane: false, ..sun });
+    assert_eq!(pixel_at(&background, &camera, east), background.pixel(0, 0));
+    assert_ne!(pixel_at(&shadowed, &camera, west), background.pixel(0, 0));
+}
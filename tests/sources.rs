@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+use wink::sources::{Config, Loader, Source};
+
+const SAMPLE: &str = include_str!("../src/openmodel/all_geometry.json");
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wink-sources-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn command_line_sources_replace_the_defaults() {
+    assert_eq!(Config::from_args(args(&[])).unwrap(), Config::default());
+
+    let config = Config::from_args(args(&["a.json", "https://example.com/b.json", "--offline", "--no-watch", "--poll-ms", "250"])).unwrap();
+    assert_eq!(config.sources.len(), 2);
+    assert!(config.offline);
+    assert!(config.sources.iter().all(|s| !s.watch && s.poll_ms == Some(250)));
+    // Offline skips the URL
+    let active: Vec<&str> = config.active_sources().map(|s| s.path.as_str()).collect();
+    assert_eq!(active, ["a.json"]);
+
+    // A stream alone shows only the streamed objects
+    let streaming = Config::from_args(args(&["--stream", "ws://localhost:9001"])).unwrap();
+    assert!(streaming.sources.is_empty() && !streaming.uses_embedded_fallback());
+
+    assert_eq!(Config::from_args(args(&["--msaa", "1"])).unwrap().msaa, 1);
+    assert!(Config::from_args(args(&["--msaa", "0"])).is_err());
+    assert!(Config::from_args(args(&["--poll-ms", "soon"])).is_err());
+    assert!(Config::from_args(args(&["--unknown"])).is_err());
+}
+
+#[test]
+fn config_files_resolve_relative_paths() {
+    let dir = temp_dir("config");
+    std::fs::write(dir.join("wink.toml"), r#"
+offline = true
+
+[[sources]]
+path = "models"
+poll_ms = 500
+
+[[sources]]
+path = "https://example.com/model.json"
+enabled = false
+"#).unwrap();
+    std::fs::write(dir.join("wink.json"), r#"{ "sources": [{ "path": "model.json", "watch": false }], "embedded_fallback": false }"#).unwrap();
+
+    let toml = Config::from_file(dir.join("wink.toml")).unwrap();
+    assert!(toml.offline && toml.embedded_fallback);
+    assert_eq!(toml.sources[0].path, dir.join("models").to_string_lossy());
+    assert_eq!(toml.sources[0].poll_ms, Some(500));
+    assert!(!toml.sources[1].enabled);
+
+    // Command line sources are added to those of the file
+    let json = Config::from_args(args(&["--config", &dir.join("wink.json").to_string_lossy(), "extra.json"])).unwrap();
+    assert!(!json.embedded_fallback);
+    assert_eq!(json.sources[0], Source { path: dir.join("model.json").to_string_lossy().into_owned(), enabled: true, watch: false, poll_ms: None });
+    assert_eq!(json.sources[1].path, "extra.json");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn query_string_selects_web_sources() {
+    assert_eq!(Config::from_query(""), Config::default());
+
+    let config = Config::from_query("?source=%2Fgeometry%2Fa.json&source=https%3A%2F%2Fexample.com%2Fb.json&watch=0&poll=200&embedded=false&msaa=1");
+    let paths: Vec<&str> = config.sources.iter().map(|s| s.path.as_str()).collect();
+    assert_eq!(paths, ["/geometry/a.json", "https://example.com/b.json"]);
+    assert!(config.sources.iter().all(|s| !s.watch && s.poll_interval_ms() == 200));
+    assert!(!config.embedded_fallback);
+    assert_eq!(config.msaa, 1);
+}
+
+#[test]
+fn loader_reads_files_and_directories_offline() {
+    let dir = temp_dir("loader");
+    std::fs::create_dir(dir.join("models")).unwrap();
+    std::fs::write(dir.join("models/b.json"), SAMPLE).unwrap();
+    std::fs::write(dir.join("models/notes.txt"), "not geometry").unwrap();
+    // Same content as b.json, loaded only once
+    std::fs::write(dir.join("a.json"), SAMPLE).unwrap();
+
+    let mut config = Config::from_args(args(&["--offline", &dir.join("a.json").to_string_lossy(), &dir.join("models").to_string_lossy(), "https://example.com/c.json"])).unwrap();
+    let mut loader = Loader::new(config.clone());
+    assert_eq!(loader.load().len(), 1);
+
+    // A broken file is skipped; with nothing left the embedded sample is shown unless disabled
+    std::fs::write(dir.join("a.json"), "{").unwrap();
+    std::fs::remove_file(dir.join("models/b.json")).unwrap();
+    assert_eq!(loader.reload().len(), 1);
+    config.embedded_fallback = false;
+    assert!(Loader::new(config).load().is_empty());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
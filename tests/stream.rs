@@ -0,0 +1,80 @@
+use std::net::TcpListener;
+use openmodel::AllGeometryData;
+use openmodel::geometry::Point;
+use tungstenite::Message;
+use wink::geometry::GeometryObject;
+use wink::stream::{self, StreamMessage};
+
+const SAMPLE: &str = include_str!("../src/openmodel/all_geometry.json");
+
+fn object_count(geometry: &AllGeometryData) -> usize {
+    GeometryObject::all(geometry).len()
+}
+
+fn points(points: Vec<Point>) -> AllGeometryData {
+    AllGeometryData { points, ..Default::default() }
+}
+
+// Send each message from an in-process server, then close the connection
+fn serve(messages: Vec<String>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        let mut socket = tungstenite::accept(tcp).unwrap();
+        for message in messages {
+            socket.send(Message::text(message)).unwrap();
+        }
+        socket.close(None).unwrap();
+        // Drain until the client acknowledges the close
+        while socket.read().is_ok() {}
+    });
+    url
+}
+
+#[test]
+fn applies_streamed_commands_by_guid() {
+    let sample: AllGeometryData = serde_json::from_str(SAMPLE).unwrap();
+    let sample_count = object_count(&sample);
+    let removed_guid = sample.meshes[0].data.guid().to_string();
+
+    let mut point = Point::new(1.0, 2.0, 3.0);
+    let guid = point.data.guid().to_string();
+    let add = serde_json::to_string(&StreamMessage::Add { geometry: points(vec![point.clone()]) }).unwrap();
+    point.x = 5.0;
+    let update = serde_json::to_string(&StreamMessage::Update { geometry: points(vec![point]) }).unwrap();
+    let remove = format!(r#"{{"type": "remove", "guids": ["{}"]}}"#, removed_guid);
+
+    // A bare openmodel document is a full scene; invalid messages are skipped
+    let url = serve(vec![SAMPLE.to_string(), add, "not json".to_string(), update, remove]);
+
+    let mut streamed = AllGeometryData::default();
+    let mut counts = Vec::new();
+    stream::receive(&url, |message| {
+        message.apply(&mut streamed);
+        counts.push(object_count(&streamed));
+        true
+    })
+    .unwrap();
+
+    assert_eq!(counts, [sample_count, sample_count + 1, sample_count + 1, sample_count]);
+    let moved: Vec<&Point> = streamed.points.iter().filter(|p| p.data.guid().to_string() == guid).collect();
+    assert_eq!(moved.len(), 1);
+    assert_eq!(moved[0].x, 5.0);
+    assert!(streamed.meshes.iter().all(|m| m.data.guid().to_string() != removed_guid));
+
+    StreamMessage::parse(r#"{"type": "clear"}"#).unwrap().apply(&mut streamed);
+    assert_eq!(object_count(&streamed), 0);
+}
+
+#[test]
+fn partial_messages_and_unreachable_endpoints() {
+    // Lists missing from a message are empty
+    let message = StreamMessage::parse(r#"{"type": "add", "geometry": {"points": []}}"#).unwrap();
+    assert!(matches!(message, StreamMessage::Add { .. }));
+    assert!(StreamMessage::parse(r#"{"type": "rotate"}"#).is_err());
+
+    // Nothing listens on a port the listener just released
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    assert!(stream::receive(&format!("ws://127.0.0.1:{}", port), |_| true).is_err());
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use openmodel::AllGeometryData;
+use openmodel::geometry::{Mesh, Point, PointCloud};
+use openmodel::primitives::Color;
+use wink::camera::{Camera, NamedView};
+use wink::geometry::GeometryBuffers;
+use wink::headless::RgbaImage;
+use wink::shading::DisplayMode;
+use common::{headless, headless_with_sample_count};
+
+// Horizontal square at height z with an RGBA vertex color
+fn square(z: f64, rgb: [f64; 3], alpha: Option<f64>) -> Mesh {
+    let mut mesh = Mesh::new();
+    let keys: Vec<usize> = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]]
+        .iter()
+        .map(|[x, y]| mesh.add_vertex(Point::new(*x, *y, z), None))
+        .collect();
+    for key in &keys {
+        let vertex = mesh.vertex.get_mut(key).unwrap();
+        vertex.set_color(rgb[0], rgb[1], rgb[2]);
+        if let Some(alpha) = alpha {
+            vertex.set_attribute("a", alpha);
+        }
+    }
+    mesh.add_face(keys, None).unwrap();
+    mesh
+}
+
+// Pixel inside one quadrant of the squares, away from the grid lines through the origin
+fn sample(image: &RgbaImage) -> [u8; 4] {
+    image.pixel(image.width * 3 / 8, image.height * 5 / 16)
+}
+
+#[test]
+fn vertex_and_point_alpha_reach_the_buffers() {
+    let mut buffers = GeometryBuffers::new();
+    buffers.append_mesh(&square(0.0, [1.0, 0.0, 0.0], Some(0.5)), [0.8; 3]);
+    // Almost opaque snaps to opaque, so the geometry does not need the transparent pass
+    buffers.append_mesh(&square(1.0, [1.0, 0.0, 0.0], Some(0.9995)), [0.8; 3]);
+    buffers.append_mesh(&square(2.0, [1.0, 0.0, 0.0], None), [0.8; 3]);
+    let alphas: Vec<f32> = buffers.vertices.iter().map(|v| v.color[3]).collect();
+    assert_eq!(alphas, [[0.5; 4], [1.0; 4], [1.0; 4]].concat());
+
+    let cloud = PointCloud::new(vec![Point::new(0.0, 0.0, 0.0)], vec![], vec![Color::new(255, 0, 0, 51)]);
+    buffers.append_point_cloud(&cloud);
+    assert!((buffers.points[0].color[3] - 0.2).abs() < 1e-6);
+}
+
+#[test]
+fn translucent_objects_blend_in_any_order_and_xray_ghosts() {
+    let (width, height) = (64, 64);
+    let Some(mut headless) = headless(width, height) else { return };
+
+    let red = square(0.0, [1.0, 0.0, 0.0], None);
+    let blue = square(1.0, [0.0, 0.0, 1.0], Some(0.5));
+    let green = square(0.5, [0.0, 1.0, 0.0], Some(0.5));
+    let red_guid = red.data.guid().to_string();
+    let scene = |meshes: Vec<Mesh>| [AllGeometryData { meshes, ..Default::default() }];
+
+    let mut camera = Camera::new(width as f32, height as f32);
+    camera.set_view(NamedView::Top);
+    headless.frame_all(&scene(vec![red.clone()]), &mut camera);
+    // No edge overlay across the sampled pixel
+    headless.renderer_mut().set_display_mode(DisplayMode::Shaded);
+    let background = headless.render(&scene(vec![]), &camera).unwrap().pixel(0, 0);
+
+    // Opaque red seen through translucent blue
+    let opaque = sample(&headless.render(&scene(vec![red.clone()]), &camera).unwrap());
+    let blended = sample(&headless.render(&scene(vec![red.clone(), blue.clone()]), &camera).unwrap());
+    assert!(opaque[0] > 100 && opaque[2] < 20, "{opaque:?}");
+    assert!(blended[0] > 40 && blended[2] > 40 && blended[0] < opaque[0], "{blended:?}");
+
+    // Draw order does not matter for translucent layers
+    let forward = sample(&headless.render(&scene(vec![red.clone(), green.clone(), blue.clone()]), &camera).unwrap());
+    let backward = sample(&headless.render(&scene(vec![blue.clone(), green.clone(), red.clone()]), &camera).unwrap());
+    assert!(forward.iter().zip(backward).all(|(a, b)| a.abs_diff(b) <= 2), "{forward:?} {backward:?}");
+
+    // Object opacity and x-ray let the background through the opaque red square
+    assert!(headless.renderer_mut().scene_mut().set_opacity(&red_guid, 0.5));
+    let faded = sample(&headless.render(&scene(vec![red.clone()]), &camera).unwrap());
+    assert!(faded[1] > opaque[1] && faded[1] < background[1], "{faded:?}");
+
+    headless.renderer_mut().scene_mut().set_opacity(&red_guid, 1.0);
+    headless.renderer_mut().set_xray(true);
+    let ghosted = sample(&headless.render(&scene(vec![red.clone()]), &camera).unwrap());
+    assert!(ghosted[1] > faded[1], "{ghosted:?}");
+    // The selected object stays solid
+    headless.renderer_mut().scene_mut().set_selected(Some(&red_guid));
+    let selected = sample(&headless.render(&scene(vec![red]), &camera).unwrap());
+    assert!(selected[1] < ghosted[1], "{selected:?}");
+}
+
+#[test]
+fn translucent_objects_render_at_any_sample_count() {
+    let (width, height) = (64, 64);
+    let red = square(0.0, [1.0, 0.0, 0.0], None);
+    let blue = square(1.0, [0.0, 0.0, 1.0], Some(0.5));
+    let sources = [AllGeometryData { meshes: vec![red, blue], ..Default::default() }];
+    for requested in [1, 4, 16] {
+        let Some(mut headless) = headless_with_sample_count(width, height, requested) else { return };
+        let mut camera = Camera::new(width as f32, height as f32);
+        camera.set_view(NamedView::Top);
+        headless.frame_all(&sources, &mut camera);
+        headless.renderer_mut().set_display_mode(DisplayMode::Shaded);
+        // Through the transparency buffers or, where they are not supported, alpha blended
+        let blended = sample(&headless.render(&sources, &camera).unwrap());
+        assert!(blended[0] > 40 && blended[2] > 40, "{requested}x: {blended:?}");
+    }
+}